@@ -3,6 +3,9 @@ extern crate bencher;
 
 use dose_response::{
     WORLD_SIZE,
+    blocker::Blocker,
+    formula,
+    pathfinding::{DijkstraMap, MonsterCost, Path, PathfindingContext},
     player::{Mind, PlayerInfo},
     point::Point,
     state::Challenge,
@@ -37,5 +40,81 @@ fn b(bench: &mut Bencher) {
     bench.iter(|| world.nearest_dose(Point::new(0, 0), 40))
 }
 
-benchmark_group!(benches, a, b);
+/// Walkable tiles spread around the starting chunk, roughly where
+/// the monsters chasing the player would be.
+fn monster_positions(world: &World) -> Vec<Point> {
+    (0..32)
+        .flat_map(|y| (0..32).map(move |x| Point::new(x, y)))
+        .filter(|&pos| (pos.x + pos.y) % 7 == 0)
+        .filter(|&pos| world.walkable(pos, Blocker::WALL | Blocker::MONSTER, Point::zero()))
+        .collect()
+}
+
+fn monsters_fresh_search(bench: &mut Bencher) {
+    let world = setup();
+    let player_pos = Point::zero();
+    let monsters = monster_positions(&world);
+    bench.iter(|| {
+        for &pos in &monsters {
+            Path::find(
+                pos,
+                player_pos,
+                &world,
+                Blocker::WALL,
+                player_pos,
+                formula::PATHFINDING_MONSTER_LIMIT,
+                &MonsterCost,
+            );
+        }
+    })
+}
+
+fn monsters_reused_context(bench: &mut Bencher) {
+    let world = setup();
+    let player_pos = Point::zero();
+    let monsters = monster_positions(&world);
+    let mut context = PathfindingContext::new();
+    bench.iter(|| {
+        for &pos in &monsters {
+            context.find(
+                pos,
+                player_pos,
+                &world,
+                Blocker::WALL,
+                player_pos,
+                formula::PATHFINDING_MONSTER_LIMIT,
+                &MonsterCost,
+            );
+        }
+    })
+}
+
+fn monsters_chase_map(bench: &mut Bencher) {
+    let world = setup();
+    let player_pos = Point::zero();
+    let monsters = monster_positions(&world);
+    let mut map = DijkstraMap::new();
+    bench.iter(|| {
+        map.build(
+            formula::simulation_area(player_pos),
+            &[player_pos],
+            &world,
+            Blocker::WALL,
+            player_pos,
+            &MonsterCost,
+        );
+        for &pos in &monsters {
+            map.next_step(pos, &world, Blocker::WALL | Blocker::MONSTER, player_pos);
+        }
+    })
+}
+
+benchmark_group!(
+    benches,
+    a,
+    b,
+    monsters_fresh_search,
+    monsters_reused_context,
+    monsters_chase_map
+);
 benchmark_main!(benches);
@@ -316,6 +316,23 @@ fn main() {
             sizes
         };
 
+        // NOTE: the game rasterises the glyphs at runtime for the
+        // sizes that aren't baked in here (see `engine::glyph_atlas`).
+        // It lays them out the same way so it needs to know the order.
+        writeln!(
+            lookup_table_contents,
+            "pub const GLYPH_CHARS: [char; {}] = {:?};",
+            tile_chars.len(),
+            tile_chars,
+        );
+
+        writeln!(
+            lookup_table_contents,
+            "pub const BAKED_GLYPH_SIZES: [i32; {}] = {:?};",
+            all_sizes.len(),
+            all_sizes,
+        );
+
         for &size in &all_sizes {
             let height = size as f32;
             let scale = Scale::uniform(height);
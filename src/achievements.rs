@@ -0,0 +1,178 @@
+use crate::{monster::CompanionBonus, state::Challenge, util};
+
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    error::Error,
+    fs,
+    io::ErrorKind,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How many turns the player must survive to unlock `Achievement::Survivor`.
+pub const SURVIVOR_TURNS: i32 = 1000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Achievement {
+    CleanVictory,
+    GoodCompany,
+    Survivor,
+    HardMode,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 4] = [
+        Achievement::CleanVictory,
+        Achievement::GoodCompany,
+        Achievement::Survivor,
+        Achievement::HardMode,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Achievement::CleanVictory => "Clean",
+            Achievement::GoodCompany => "Good Company",
+            Achievement::Survivor => "Survivor",
+            Achievement::HardMode => "No Second Chances",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Achievement::CleanVictory => "Win the game without using a single dose.",
+            Achievement::GoodCompany => "Meet all three kinds of companions in a single game.",
+            Achievement::Survivor => "Survive 1000 turns.",
+            Achievement::HardMode => "Win with all the challenges turned on.",
+        }
+    }
+}
+
+/// Whether the player has been accompanied by every kind of NPC.
+pub fn met_all_companions(companions_met: &[CompanionBonus]) -> bool {
+    use CompanionBonus::*;
+    [DoubleWillGrowth, HalveExhaustion, ExtraActionPoint]
+        .iter()
+        .all(|bonus| companions_met.contains(bonus))
+}
+
+/// Whether every `Challenge` option is enabled (and it is not a
+/// practice game).
+pub fn all_challenges_on(challenge: Challenge) -> bool {
+    let Challenge {
+        hide_unseen_tiles,
+        fast_depression,
+        one_chance,
+        practice,
+    } = challenge;
+    hide_unseen_tiles && fast_depression && one_chance && !practice
+}
+
+/// The achievements the player has unlocked so far across all the
+/// games. Stored in `paths::achievements_file`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unlocked {
+    /// When was each achievement unlocked, in seconds since the Unix epoch.
+    unlocked: BTreeMap<Achievement, u64>,
+}
+
+impl Unlocked {
+    pub fn load() -> Self {
+        let path = crate::paths::achievements_file();
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                log::error!(
+                    "Could not parse the achievements at '{}': {}",
+                    path.display(),
+                    err
+                );
+                Self::default()
+            }),
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    log::error!(
+                        "Could not read the achievements at '{}': {}",
+                        path.display(),
+                        err
+                    );
+                }
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        crate::paths::ensure_dir(&crate::paths::data_dir());
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(crate::paths::achievements_file(), contents)?;
+        Ok(())
+    }
+
+    /// The date the `achievement` was unlocked on or `None` if it's still locked.
+    pub fn unlocked_on(&self, achievement: Achievement) -> Option<String> {
+        self.unlocked
+            .get(&achievement)
+            .map(|&timestamp| util::format_date(timestamp))
+    }
+
+    pub fn count(&self) -> usize {
+        self.unlocked.len()
+    }
+
+    /// Unlock the `achievement`. Returns `true` if it wasn't unlocked before.
+    pub fn unlock(&mut self, achievement: Achievement, timestamp: u64) -> bool {
+        match self.unlocked.entry(achievement) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(timestamp);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_met_all_companions() {
+        use CompanionBonus::*;
+        assert!(!met_all_companions(&[]));
+        assert!(!met_all_companions(&[DoubleWillGrowth, HalveExhaustion]));
+        assert!(met_all_companions(&[
+            ExtraActionPoint,
+            HalveExhaustion,
+            DoubleWillGrowth
+        ]));
+    }
+
+    #[test]
+    fn test_all_challenges_on() {
+        assert!(all_challenges_on(Challenge::default()));
+        assert!(!all_challenges_on(Challenge {
+            one_chance: false,
+            ..Challenge::default()
+        }));
+    }
+
+    #[test]
+    fn test_unlock_only_once() {
+        let mut unlocked = Unlocked::default();
+        assert!(unlocked.unlock(Achievement::Survivor, 1_700_000_000));
+        assert!(!unlocked.unlock(Achievement::Survivor, 1_800_000_000));
+        assert_eq!(
+            unlocked.unlocked_on(Achievement::Survivor),
+            Some("2023-11-14".to_string())
+        );
+        assert_eq!(unlocked.unlocked_on(Achievement::CleanVictory), None);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut unlocked = Unlocked::default();
+        unlocked.unlock(Achievement::HardMode, 42);
+        let json = serde_json::to_string(&unlocked).unwrap_or_default();
+        let parsed: Option<Unlocked> = serde_json::from_str(&json).ok();
+        assert_eq!(parsed, Some(unlocked));
+    }
+}
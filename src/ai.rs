@@ -154,6 +154,16 @@ pub fn friendly_act(
     (update, action)
 }
 
+/// Whether the NPC keeps away from the player. They don't want
+/// anything to do with them while they're high.
+pub fn avoids_player(actor: &Monster, player_info: PlayerInfo) -> bool {
+    actor.behavior == Behavior::Friendly
+        && actor.ai_state != AIState::NoOp
+        && !actor.accompanying_player
+        && player_info.mind.is_high()
+        && player_info.pos.distance(actor.position) <= formula::ESTRANGED_NPC_AVOID_RADIUS
+}
+
 pub fn noop_act(
     actor: &Monster,
     _player_info: PlayerInfo,
@@ -0,0 +1,94 @@
+use crate::audio::SoundData;
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// File name of the tilemap image within the asset pack.
+pub const TILEMAP: &str = "tiles.png";
+/// File name of the index that maps `Graphic` values to tilemap positions.
+pub const TILEMAP_INDEX: &str = "tiles.toml";
+/// File name of the font used for the UI text and the map glyphs.
+pub const FONT: &str = "font.ttf";
+
+/// Game assets that can be overridden at runtime.
+///
+/// An asset pack is a directory that mirrors the layout of the
+/// `assets` directory in the repository: `tiles.png` with the
+/// `tiles.toml` index, `font.ttf`, `sound/*.ogg` and `music/*.ogg`.
+/// Every file is optional. Anything that's missing (or there's no
+/// asset pack at all) falls back to the data embedded in the binary.
+///
+/// This lets artists iterate on the assets without recompiling the
+/// game and players to use their own tilesets.
+#[derive(Clone, Debug, Default)]
+pub struct AssetPack {
+    root: Option<PathBuf>,
+}
+
+impl AssetPack {
+    /// Use only the assets embedded in the binary.
+    pub fn embedded() -> Self {
+        Self { root: None }
+    }
+
+    pub fn new(root: &Path) -> Self {
+        if root.is_dir() {
+            log::info!("Loading assets from: '{}'", root.display());
+        } else {
+            log::warn!(
+                "Asset pack '{}' is not a directory. Falling back to the embedded assets.",
+                root.display()
+            );
+        }
+        Self {
+            root: Some(root.to_path_buf()),
+        }
+    }
+
+    /// Read the file at `relative_path` from the asset pack.
+    ///
+    /// Returns `None` if there's no asset pack, the file doesn't
+    /// exist or can't be read. The caller is expected to fall back to
+    /// the embedded asset.
+    pub fn read(&self, relative_path: &str) -> Option<Vec<u8>> {
+        let path = self.root.as_ref()?.join(relative_path);
+        match std::fs::read(&path) {
+            Ok(data) => {
+                log::info!("Using asset override: '{}'", path.display());
+                Some(data)
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => {
+                log::error!("Could not read asset '{}': {}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    pub fn tilemap(&self) -> Option<Vec<u8>> {
+        self.read(TILEMAP)
+    }
+
+    pub fn tilemap_index(&self) -> Option<String> {
+        let data = self.read(TILEMAP_INDEX)?;
+        String::from_utf8(data)
+            .map_err(|err| log::error!("The tilemap index is not valid UTF-8: {}", err))
+            .ok()
+    }
+
+    pub fn font(&self) -> Option<Vec<u8>> {
+        self.read(FONT)
+    }
+
+    /// Return the sound at `relative_path` (e.g. `sound/click.ogg`)
+    /// or the `embedded` data if the asset pack doesn't have it.
+    pub fn sound(&self, relative_path: &str, embedded: &'static [u8]) -> SoundData {
+        match self.read(relative_path) {
+            Some(data) => SoundData::External(Arc::from(data)),
+            None => SoundData::Embedded(embedded),
+        }
+    }
+}
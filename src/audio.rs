@@ -1,10 +1,27 @@
-use crate::{random::Random, util};
+use crate::{assets::AssetPack, random::Random, util};
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink, Source};
 
-type Sound = std::io::Cursor<&'static [u8]>;
+/// Encoded sound data. Either embedded in the binary or loaded from
+/// an asset pack.
+#[derive(Clone, Debug)]
+pub enum SoundData {
+    Embedded(&'static [u8]),
+    External(Arc<[u8]>),
+}
+
+impl AsRef<[u8]> for SoundData {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            SoundData::Embedded(data) => data,
+            SoundData::External(data) => data,
+        }
+    }
+}
+
+type Sound = std::io::Cursor<SoundData>;
 
 fn empty_sink() -> Sink {
     Sink::new().0
@@ -22,15 +39,15 @@ pub struct Audio {
 
 impl Audio {
     pub fn without_backend() -> Self {
-        Self::from_output_stream(None)
+        Self::from_output_stream(None, &AssetPack::embedded())
     }
 
-    pub fn new() -> Self {
+    pub fn new(assets: &AssetPack) -> Self {
         let output_stream = OutputStreamBuilder::open_default_stream().ok();
-        Self::from_output_stream(output_stream)
+        Self::from_output_stream(output_stream, assets)
     }
 
-    fn from_output_stream(output_stream: Option<OutputStream>) -> Self {
+    fn from_output_stream(output_stream: Option<OutputStream>, assets: &AssetPack) -> Self {
         log::info!("Setting up the audio stream.");
 
         let rng = Random::from_seed(util::random_seed());
@@ -42,37 +59,68 @@ impl Audio {
         // Start paused, let the game code control when audio starts playing.
         background_sound_queue.pause();
 
-        let walk_1 = Sound::new(&include_bytes!("../assets/sound/walk-1.ogg")[..]);
-        let walk_2 = Sound::new(&include_bytes!("../assets/sound/walk-2.ogg")[..]);
-        let walk_3 = Sound::new(&include_bytes!("../assets/sound/walk-3.ogg")[..]);
-        let walk_4 = Sound::new(&include_bytes!("../assets/sound/walk-4.ogg")[..]);
-        let monster_hit = Sound::new(&include_bytes!("../assets/sound/monster-hit.ogg")[..]);
-        let monster_moved = Sound::new(&include_bytes!("../assets/sound/blip.ogg")[..]);
-        let explosion = Sound::new(&include_bytes!("../assets/sound/explosion.ogg")[..]);
-        let player_hit = Sound::new(&include_bytes!("../assets/sound/player-hit.ogg")[..]);
-        let game_over = Sound::new(&include_bytes!("../assets/sound/game-over.ogg")[..]);
-        let click = Sound::new(&include_bytes!("../assets/sound/click.ogg")[..]);
+        let walk_1 = Sound::new(assets.sound(
+            "sound/walk-1.ogg",
+            include_bytes!("../assets/sound/walk-1.ogg"),
+        ));
+        let walk_2 = Sound::new(assets.sound(
+            "sound/walk-2.ogg",
+            include_bytes!("../assets/sound/walk-2.ogg"),
+        ));
+        let walk_3 = Sound::new(assets.sound(
+            "sound/walk-3.ogg",
+            include_bytes!("../assets/sound/walk-3.ogg"),
+        ));
+        let walk_4 = Sound::new(assets.sound(
+            "sound/walk-4.ogg",
+            include_bytes!("../assets/sound/walk-4.ogg"),
+        ));
+        let monster_hit = Sound::new(assets.sound(
+            "sound/monster-hit.ogg",
+            include_bytes!("../assets/sound/monster-hit.ogg"),
+        ));
+        let monster_moved =
+            Sound::new(assets.sound("sound/blip.ogg", include_bytes!("../assets/sound/blip.ogg")));
+        let explosion = Sound::new(assets.sound(
+            "sound/explosion.ogg",
+            include_bytes!("../assets/sound/explosion.ogg"),
+        ));
+        let player_hit = Sound::new(assets.sound(
+            "sound/player-hit.ogg",
+            include_bytes!("../assets/sound/player-hit.ogg"),
+        ));
+        let game_over = Sound::new(assets.sound(
+            "sound/game-over.ogg",
+            include_bytes!("../assets/sound/game-over.ogg"),
+        ));
+        let click = Sound::new(assets.sound(
+            "sound/click.ogg",
+            include_bytes!("../assets/sound/click.ogg"),
+        ));
 
         Self {
             backgrounds: BackgroundSounds {
                 // Credits: Exit Exit by P C III (CC-BY)
                 // https://freemusicarchive.org/music/P_C_III
                 // https://soundcloud.com/pipe-choir-2/exit-exit
-                exit_exit: Sound::new(
-                    &include_bytes!("../assets/music/P C III - Exit Exit.ogg")[..],
-                ),
+                exit_exit: Sound::new(assets.sound(
+                    "music/P C III - Exit Exit.ogg",
+                    include_bytes!("../assets/music/P C III - Exit Exit.ogg"),
+                )),
                 //https://freemusicarchive.org/music/P_C_III/earth2earth/earth2earth_1392
                 // Credits: earth2earth by P C III (CC-BY)
                 // https://freemusicarchive.org/music/P_C_III
-                family_breaks: Sound::new(
-                    &include_bytes!("../assets/music/P C III - The Family Breaks.ogg")[..],
-                ),
+                family_breaks: Sound::new(assets.sound(
+                    "music/P C III - The Family Breaks.ogg",
+                    include_bytes!("../assets/music/P C III - The Family Breaks.ogg"),
+                )),
                 // https://freemusicarchive.org/music/P_C_III/The_Family_Breaks/The_Family_Breaks_1795
                 // Credit: The Family Breaks by P C III (CC-BY)
                 // https://freemusicarchive.org/music/P_C_III
-                earth2earth: Sound::new(
-                    &include_bytes!("../assets/music/P C III - earth2earth.ogg")[..],
-                ),
+                earth2earth: Sound::new(assets.sound(
+                    "music/P C III - earth2earth.ogg",
+                    include_bytes!("../assets/music/P C III - earth2earth.ogg"),
+                )),
             },
             effects: EffectSounds {
                 walk: [walk_1, walk_2, walk_3, walk_4],
@@ -152,7 +200,7 @@ impl Audio {
 
 impl Default for Audio {
     fn default() -> Self {
-        Audio::new()
+        Audio::new(&AssetPack::embedded())
     }
 }
 
@@ -0,0 +1,311 @@
+use crate::{
+    paths,
+    state::{Challenge, State},
+    util,
+};
+
+use std::{cmp::Ordering, error::Error, fs, io::ErrorKind, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// The seed of the daily challenge for the `date` (`YYYY-MM-DD`).
+///
+/// Everyone playing on the same day gets the same world.
+pub fn seed(date: &str) -> u32 {
+    let hash = blake3::hash(format!("dose-response daily challenge {date}").as_bytes());
+    let bytes = hash.as_bytes();
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// The daily challenge ignores the player's settings so the games
+/// are comparable.
+pub fn challenge() -> Challenge {
+    Challenge {
+        hide_unseen_tiles: true,
+        fast_depression: true,
+        one_chance: true,
+        practice: false,
+    }
+}
+
+/// Today's date (UTC) in the `YYYY-MM-DD` format.
+pub fn today() -> String {
+    util::format_date(util::unix_timestamp())
+}
+
+/// A single daily challenge attempt.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub date: String,
+    pub seed: u32,
+    /// Whether the game is over. An attempt that was never finished
+    /// still counts, there's only one per day.
+    pub finished: bool,
+    pub victory: bool,
+    pub turns: i32,
+    /// Copy of the game's replay. Used to verify the result.
+    pub replay: Option<PathBuf>,
+}
+
+impl Entry {
+    pub fn outcome(&self) -> &'static str {
+        if !self.finished {
+            "Not finished"
+        } else if self.victory {
+            "Won"
+        } else {
+            "Lost"
+        }
+    }
+
+    /// Order the entries from the best to the worst: faster victories
+    /// first, then the losses that took the longest.
+    pub fn rank(&self, other: &Entry) -> Ordering {
+        other
+            .finished
+            .cmp(&self.finished)
+            .then_with(|| other.victory.cmp(&self.victory))
+            .then_with(|| {
+                if self.victory {
+                    self.turns.cmp(&other.turns)
+                } else {
+                    other.turns.cmp(&self.turns)
+                }
+            })
+            .then_with(|| self.date.cmp(&other.date))
+    }
+}
+
+/// All the daily challenge attempts made on this computer. Stored
+/// in `paths::daily_leaderboard_file`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    pub fn load() -> Self {
+        let path = paths::daily_leaderboard_file();
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                log::error!(
+                    "Could not parse the daily leaderboard at '{}': {}",
+                    path.display(),
+                    err
+                );
+                Self::default()
+            }),
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    log::error!(
+                        "Could not read the daily leaderboard at '{}': {}",
+                        path.display(),
+                        err
+                    );
+                }
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        paths::ensure_dir(&paths::data_dir());
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(paths::daily_leaderboard_file(), contents)?;
+        Ok(())
+    }
+
+    pub fn entry(&self, date: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.date == date)
+    }
+
+    pub fn attempted(&self, date: &str) -> bool {
+        self.entry(date).is_some()
+    }
+
+    /// Record that the player started the challenge for `date`.
+    /// Returns `false` if they've already had their attempt.
+    pub fn start(&mut self, date: &str) -> bool {
+        if self.attempted(date) {
+            return false;
+        }
+        self.entries.push(Entry {
+            date: date.to_string(),
+            seed: seed(date),
+            finished: false,
+            victory: false,
+            turns: 0,
+            replay: None,
+        });
+        true
+    }
+
+    /// Record the result of the challenge for `date`.
+    pub fn finish(&mut self, date: &str, victory: bool, turns: i32, replay: Option<PathBuf>) {
+        match self.entries.iter_mut().find(|entry| entry.date == date) {
+            Some(entry) if entry.finished => {
+                log::warn!("The daily challenge for {} is finished already.", date);
+            }
+            Some(entry) => {
+                entry.finished = true;
+                entry.victory = victory;
+                entry.turns = turns;
+                entry.replay = replay;
+            }
+            None => log::error!(
+                "Finished the daily challenge for {} without starting it.",
+                date
+            ),
+        }
+    }
+
+    /// The entries from the best to the worst.
+    pub fn ranked(&self) -> Vec<&Entry> {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.rank(b));
+        entries
+    }
+}
+
+/// Record the daily challenge result of the just finished game.
+///
+/// The replay is copied next to the leaderboard so it stays around
+/// even if the player clears out their replays.
+pub fn record_result(state: &State) {
+    let Some(date) = &state.daily else {
+        return;
+    };
+
+    let replay = state.replay_path.as_ref().and_then(|replay_path| {
+        let daily_dir = paths::daily_dir();
+        paths::ensure_dir(&daily_dir);
+        let destination = daily_dir.join(format!("daily-{date}"));
+        match fs::copy(replay_path, &destination) {
+            Ok(_) => Some(destination),
+            Err(err) => {
+                log::error!(
+                    "Could not copy the daily challenge replay '{}': {}",
+                    replay_path.display(),
+                    err
+                );
+                None
+            }
+        }
+    });
+
+    let mut leaderboard = Leaderboard::load();
+    let victory = state.side == crate::state::Side::Victory;
+    leaderboard.finish(date, victory, state.turn, replay);
+    if let Err(err) = leaderboard.save() {
+        log::error!("Could not save the daily leaderboard: {}", err);
+    }
+}
+
+/// Re-simulate the entry's replay without a window and check the
+/// game ends the same way the entry says it did.
+#[cfg(feature = "replay")]
+pub fn verify(entry: &Entry) -> Result<(), Box<dyn Error>> {
+    use crate::{
+        replay::{self, Outcome},
+        state::OnDivergence,
+    };
+
+    if !entry.finished {
+        throw!("The game was never finished.");
+    }
+    let Some(replay) = &entry.replay else {
+        throw!("The entry has no replay.");
+    };
+    if entry.seed != seed(&entry.date) {
+        throw!("The seed doesn't match the date.");
+    }
+
+    let report = replay::verify(replay, challenge(), OnDivergence::Stop)?;
+    if report.seed != entry.seed {
+        throw!(&format!(
+            "The replay was recorded with seed {}, expected {}.",
+            report.seed, entry.seed
+        ));
+    }
+    if !report.passed() {
+        throw!(&format!(
+            "The replay doesn't match the recorded game:\n{report}"
+        ));
+    }
+
+    let victory = report.outcome == Outcome::Victory;
+    let turns = report.turn.unwrap_or_default();
+    if victory != entry.victory || turns != entry.turns {
+        throw!(&format!(
+            "The replay ended with victory: {}, turns: {}. The leaderboard says victory: {}, turns: {}.",
+            victory, turns, entry.victory, entry.turns
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(date: &str, finished: bool, victory: bool, turns: i32) -> Entry {
+        Entry {
+            date: date.to_string(),
+            seed: seed(date),
+            finished,
+            victory,
+            turns,
+            replay: None,
+        }
+    }
+
+    #[test]
+    fn test_seed_is_stable() {
+        assert_eq!(seed("2024-01-01"), seed("2024-01-01"));
+        assert_ne!(seed("2024-01-01"), seed("2024-01-02"));
+    }
+
+    #[test]
+    fn test_one_attempt_per_day() {
+        let mut leaderboard = Leaderboard::default();
+        assert!(leaderboard.start("2024-01-01"));
+        assert!(!leaderboard.start("2024-01-01"));
+        assert!(leaderboard.start("2024-01-02"));
+
+        leaderboard.finish("2024-01-01", true, 300, None);
+        leaderboard.finish("2024-01-01", false, 1, None);
+        assert_eq!(
+            leaderboard.entry("2024-01-01"),
+            Some(&entry("2024-01-01", true, true, 300))
+        );
+    }
+
+    #[test]
+    fn test_ranking() {
+        let leaderboard = Leaderboard {
+            entries: vec![
+                entry("2024-01-01", true, false, 500),
+                entry("2024-01-02", false, false, 0),
+                entry("2024-01-03", true, true, 900),
+                entry("2024-01-04", true, true, 400),
+                entry("2024-01-05", true, false, 800),
+            ],
+        };
+        let dates = leaderboard
+            .ranked()
+            .iter()
+            .map(|entry| entry.date.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dates,
+            vec![
+                "2024-01-04",
+                "2024-01-03",
+                "2024-01-05",
+                "2024-01-01",
+                "2024-01-02"
+            ]
+        );
+    }
+}
@@ -1,8 +1,9 @@
 #![allow(dead_code)]
 
+use self::glyph_atlas::GlyphAtlas;
 use crate::{
     color::{self, Color, ColorAlpha},
-    graphic::{self, Graphic, TILE_SIZE},
+    graphic::{Graphic, Tileset},
     point::Point,
     rect::Rectangle,
 };
@@ -35,6 +36,8 @@ pub mod sdl3;
 #[cfg(feature = "sdl2-backend")]
 pub mod sdl2;
 
+pub mod glyph_atlas;
+
 pub mod headless;
 
 // Should handle the smallest tile size in 1080p screens and related (e.g.
@@ -288,8 +291,7 @@ fn build_vertices<T: VertexStore>(
                 let (tile_width, tile_height) = (dst.width() as f32, dst.height() as f32);
                 let (tilemap_x, tilemap_y) = (src.top_left().x as f32, src.top_left().y as f32);
                 let (texture_width, texture_height) = match texture {
-                    Texture::Glyph => (tile_width, tile_height),
-                    Texture::Tilemap => (TILE_SIZE as f32, TILE_SIZE as f32),
+                    Texture::Glyph | Texture::Tilemap => (src.width() as f32, src.height() as f32),
                     // NOTE: Egui shouldn't appear in drawcalls, adding it here for completeness
                     Texture::Egui => (tile_width, tile_height),
                 };
@@ -590,7 +592,7 @@ impl Mouse {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Cell {
     pub background_graphic: Graphic,
     pub foreground_graphic: Graphic,
@@ -643,6 +645,11 @@ pub struct Display {
     drawcalls: Vec<Drawcall>,
     pub fade: ColorAlpha,
     clear_background_color: Option<Color>,
+    /// Where to find the graphics in the tilemap texture.
+    pub tileset: Tileset,
+    /// Where to find the glyphs in the glyph texture.
+    pub glyph_atlas: GlyphAtlas,
+    grid_vertices: GridVertices,
 }
 
 #[allow(dead_code)]
@@ -821,167 +828,113 @@ impl Display {
         })
     }
 
-    pub fn push_drawcalls(&self, visual_style: VisualStyle, drawcalls: &mut Vec<Drawcall>) {
-        let display_offset_px = self.offset_px;
-        let display_size_px = self.display_size * self.tile_size;
-
-        if let Some(bg) = self.clear_background_color {
-            let full_screen_rect = Rectangle::from_size(display_size_px);
-            drawcalls.push(Drawcall::Rectangle(full_screen_rect, bg.into()));
+    /// Rebuild the vertices of every grid cell that changed since
+    /// the last call.
+    ///
+    /// The cells are compared with the ones the vertices were built
+    /// from rather than tracked on every write: the game clears and
+    /// renders the whole `Display` each frame, but most of the cells
+    /// end up the same. If anything every cell depends on changes
+    /// (tile size, scrolling, visual style, ...), the whole grid is
+    /// rebuilt.
+    pub fn update_grid_vertices(&mut self, visual_style: VisualStyle, display_size: [f32; 2]) {
+        let inputs = GridInputs {
+            visual_style,
+            tile_size: self.tile_size,
+            offset_px: self.offset_px,
+            clear_background_color: self.clear_background_color,
+            display_size,
+        };
+        let mut grid = std::mem::take(&mut self.grid_vertices);
+        let rebuild_all = grid.inputs != Some(inputs)
+            || grid.tileset != self.tileset
+            || grid.glyph_atlas != self.glyph_atlas
+            || grid.cells.len() != self.map.len();
+        if rebuild_all {
+            grid.inputs = Some(inputs);
+            grid.tileset.clone_from(&self.tileset);
+            grid.glyph_atlas.clone_from(&self.glyph_atlas);
+            grid.cells.clone_from(&self.map);
+            grid.vertices.clear();
+            grid.vertices
+                .resize((self.map.len() + 1) * GRID_SLOT_COMPONENT_COUNT, 0.0);
+            grid.dirty_ranges.clear();
+            grid.dirty_ranges.push((0, grid.vertex_count()));
         }
 
-        let mut deferred_drawcalls = vec![];
+        // NOTE: the first slot is the background behind the whole grid.
+        if rebuild_all {
+            let mut drawcalls = vec![];
+            if let Some(bg) = self.clear_background_color {
+                let full_screen_rect = Rectangle::from_size(self.display_size * self.tile_size);
+                drawcalls.push(Drawcall::Rectangle(full_screen_rect, bg.into()));
+            }
+            grid.write_slot(0, &drawcalls, display_size);
+        }
 
-        // Render the background tiles separately and before all the other drawcalls.
-        for (pos, cell) in self.cells() {
-            let (fg_texture, fg_texture_px_x, fg_texture_px_y) = match visual_style {
-                VisualStyle::Graphical => {
-                    match graphic::tilemap_coords_px(self.tile_size as u32, cell.foreground_graphic)
-                    {
-                        Some((tx, ty)) => (Texture::Tilemap, tx, ty),
-                        // NOTE: Fall back to glyphs if the graphic coordinates can't be provided:
-                        None => {
-                            let (tx, ty) = glyph_coords_px_from_char(
-                                self.tile_size as u32,
-                                cell.foreground_graphic.into(),
-                            )
-                            .unwrap_or((0, 0));
-                            (Texture::Glyph, tx, ty)
-                        }
-                    }
-                }
-                VisualStyle::Textual => {
-                    let (tx, ty) = glyph_coords_px_from_char(
-                        self.tile_size as u32,
-                        cell.foreground_graphic.into(),
-                    )
-                    .unwrap_or((0, 0));
-                    (Texture::Glyph, tx, ty)
+        let mut drawcalls = Vec::with_capacity(3);
+        for (index, (pos, cell)) in self.cells().enumerate() {
+            if !rebuild_all && grid.cells[index] == *cell {
+                continue;
+            }
+            grid.cells[index] = *cell;
+            drawcalls.clear();
+            if let Some(cell_drawcalls) = self.cell_drawcalls(visual_style, pos, cell) {
+                drawcalls.push(cell_drawcalls.background);
+                drawcalls.extend(cell_drawcalls.background_image);
+                // NOTE: the offset images are drawn on top of the
+                // grid by `push_drawcalls` instead.
+                if cell.offset_px.is_zero() {
+                    drawcalls.push(cell_drawcalls.image);
                 }
-            };
-
-            let texture_size = match fg_texture {
-                Texture::Glyph => self.tile_size,
-                Texture::Tilemap => TILE_SIZE,
-                // NOTE: Egui shouldn't appear in drawcalls, adding it here for completeness
-                Texture::Egui => self.tile_size,
-            };
-            let fg_texture_src = Rectangle::from_point_and_size(
-                Point::new(fg_texture_px_x, fg_texture_px_y),
-                Point::from_i32(texture_size),
-            );
-            let background_dst = Rectangle::from_point_and_size(
-                Point::new(
-                    pos.x * self.tile_size + display_offset_px.x,
-                    pos.y * self.tile_size + display_offset_px.y,
-                ),
-                Point::from_i32(self.tile_size),
-            );
+            }
+            grid.write_slot(index + 1, &drawcalls, display_size);
+            if !rebuild_all {
+                grid.mark_slot_dirty(index + 1);
+            }
+        }
 
-            let (bg_texture, bg_texture_px_x, bg_texture_px_y) = match visual_style {
-                VisualStyle::Graphical => {
-                    match graphic::tilemap_coords_px(self.tile_size as u32, cell.background_graphic)
-                    {
-                        Some((tx, ty)) => (Texture::Tilemap, tx, ty),
-                        // NOTE: Fall back to glyphs if the graphic coordinates can't be provided:
-                        None => {
-                            let (tx, ty) = glyph_coords_px_from_char(
-                                self.tile_size as u32,
-                                cell.background_graphic.into(),
-                            )
-                            .unwrap_or((0, 0));
-                            (Texture::Glyph, tx, ty)
-                        }
-                    }
-                }
-                VisualStyle::Textual => {
-                    let (tx, ty) = glyph_coords_px_from_char(
-                        self.tile_size as u32,
-                        cell.background_graphic.into(),
-                    )
-                    .unwrap_or((0, 0));
-                    (Texture::Glyph, tx, ty)
-                }
-            };
-            let bg_texture_src = Rectangle::from_point_and_size(
-                Point::new(bg_texture_px_x, bg_texture_px_y),
-                Point::from_i32(texture_size),
-            );
+        self.grid_vertices = grid;
+    }
 
-            // NOTE: Only render areas within the display size
-            if rect_intersects_area(background_dst, display_size_px) {
-                drawcalls.push(Drawcall::Rectangle(background_dst, cell.empty_color.into()));
+    /// The grid vertices built by `update_grid_vertices`.
+    pub fn grid_vertices(&self) -> &GridVertices {
+        &self.grid_vertices
+    }
 
-                // The background behind the player/monster/item.
-                //
-                // Things are rendered based on a two-item "stack" from the
-                // bottom-up (see `render::render_game`). All tiles start with
-                // their main graphic (empty tile, wall etc.) on the front. Then
-                // we render items, after them monsters/npcs and finally the
-                // player.
-                //
-                // If we're to render something on top of something else (e.g.
-                // food on top of empty tile or monster on top of food), we push
-                // the top-most thing to the background and then render the new
-                // thing on top.
-                //
-                // If we have one thing (empty tile / wall) we have that graphic
-                // in the foreground
-                //
-                // If we have two things (empty tile and monster) we have the
-                // monster on the foreground and the empty tile in the
-                // background
-                //
-                // If we have three things (empty and item and monster), the
-                // empty tile has been overwritten completely, the item is in
-                // the background and the monster is in the foreground.
-                //
-                // In practice, that means `bg_image` is *always* something that's *behind* something else.
-                let bg_image = Drawcall::Image(
-                    bg_texture,
-                    bg_texture_src,
-                    background_dst,
-                    cell.background_color,
-                );
+    /// The ranges of the grid vertices changed since the last call as
+    /// `(index, count)` pairs. Both are in vertices, not components.
+    pub fn take_grid_dirty_ranges(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.grid_vertices.dirty_ranges)
+    }
 
-                match visual_style {
-                    VisualStyle::Graphical => {
-                        // Render whatever "empty" background space image is behind the player/monster:
-                        drawcalls.push(bg_image);
-                    }
-                    VisualStyle::Textual => {
-                        // Do nothing.
-                        //
-                        // We don't want to render the "empty background image"
-                        // behind a monster or player because the dot is
-                        // visually distracting.
-                    }
-                }
+    /// Push the drawcalls that go on top of the grid: the cells with
+    /// an offset, the offset tiles, rectangles and the fade.
+    ///
+    /// The grid itself comes from `update_grid_vertices`.
+    pub fn push_drawcalls(&self, visual_style: VisualStyle, drawcalls: &mut Vec<Drawcall>) {
+        let display_offset_px = self.offset_px;
+        let display_size_px = self.display_size * self.tile_size;
 
-                let image = Drawcall::Image(
-                    fg_texture,
-                    fg_texture_src,
-                    background_dst.offset(cell.offset_px),
-                    cell.foreground,
-                );
-                if cell.offset_px.is_zero() {
-                    drawcalls.push(image);
-                } else {
-                    // NOTE: we can't render the image now because it
-                    // would be overwritten by all the other images on the grid.
-                    //
-                    // So everything that's got an offset to it will
-                    // be deferred and only rendered after all the
-                    // grid-aligned tiles are rendered.
-                    //
-                    // This also means all the offset cells are
-                    // rendered on top of the non-offset ones.
-                    //
-                    // If there are two cells with an offset that
-                    // overlap, they will of course overlap on screen
-                    // too.
-                    deferred_drawcalls.push(image);
-                }
+        for (pos, cell) in self.cells() {
+            if cell.offset_px.is_zero() {
+                continue;
+            }
+            // NOTE: we can't render the image with the rest of the
+            // grid because it would be overwritten by all the other
+            // images on it.
+            //
+            // So everything that's got an offset to it will be
+            // deferred and only rendered after all the grid-aligned
+            // tiles are rendered.
+            //
+            // This also means all the offset cells are rendered on
+            // top of the non-offset ones.
+            //
+            // If there are two cells with an offset that overlap,
+            // they will of course overlap on screen too.
+            if let Some(cell_drawcalls) = self.cell_drawcalls(visual_style, pos, cell) {
+                drawcalls.push(cell_drawcalls.image);
             }
         }
 
@@ -989,33 +942,28 @@ impl Display {
             let (texture, texture_px_x, texture_px_y) = match visual_style {
                 VisualStyle::Graphical => {
                     // TODO: handle background graphics here too!
-                    match graphic::tilemap_coords_px(self.tile_size as u32, tile.graphic) {
+                    match self.tileset.coords_px(tile.graphic) {
                         Some((tx, ty)) => (Texture::Tilemap, tx, ty),
                         // NOTE: Fall back to glyphs if the graphic coordinates can't be provided:
                         None => {
-                            let (tx, ty) = glyph_coords_px_from_char(
-                                self.tile_size as u32,
-                                tile.graphic.into(),
-                            )
-                            .unwrap_or((0, 0));
+                            let (tx, ty) = self
+                                .glyph_atlas
+                                .coords_px(self.tile_size, tile.graphic.into())
+                                .unwrap_or((0, 0));
                             (Texture::Glyph, tx, ty)
                         }
                     }
                 }
                 VisualStyle::Textual => {
-                    let (tx, ty) =
-                        glyph_coords_px_from_char(self.tile_size as u32, tile.graphic.into())
-                            .unwrap_or((0, 0));
+                    let (tx, ty) = self
+                        .glyph_atlas
+                        .coords_px(self.tile_size, tile.graphic.into())
+                        .unwrap_or((0, 0));
                     (Texture::Glyph, tx, ty)
                 }
             };
 
-            let texture_size = match texture {
-                Texture::Glyph => self.tile_size,
-                Texture::Tilemap => TILE_SIZE,
-                // NOTE: Egui shouldn't appear in drawcalls, adding it here for completeness
-                Texture::Egui => self.tile_size,
-            };
+            let texture_size = self.texture_size_px(texture);
             let texture_src = Rectangle::from_point_and_size(
                 Point::new(texture_px_x, texture_px_y),
                 Point::from_i32(texture_size),
@@ -1036,7 +984,6 @@ impl Display {
             drawcalls.push(image);
         }
 
-        drawcalls.extend(deferred_drawcalls);
         drawcalls.extend(self.drawcalls.iter());
 
         if self.fade.alpha > 0 {
@@ -1044,6 +991,225 @@ impl Display {
             drawcalls.push(Drawcall::Rectangle(full_screen_rect, self.fade));
         }
     }
+
+    /// Size (in pixels) of a single tile in the `texture`.
+    fn texture_size_px(&self, texture: Texture) -> i32 {
+        match texture {
+            Texture::Glyph => self.glyph_atlas.glyph_size_px(self.tile_size),
+            Texture::Tilemap => self.tileset.tile_size,
+            // NOTE: Egui shouldn't appear in drawcalls, adding it here for completeness
+            Texture::Egui => self.tile_size,
+        }
+    }
+
+    /// The drawcalls of a single grid cell or `None` if it's outside
+    /// of the display area.
+    fn cell_drawcalls(
+        &self,
+        visual_style: VisualStyle,
+        pos: Point,
+        cell: &Cell,
+    ) -> Option<CellDrawcalls> {
+        let display_offset_px = self.offset_px;
+        let display_size_px = self.display_size * self.tile_size;
+
+        let (fg_texture, fg_texture_px_x, fg_texture_px_y) = match visual_style {
+            VisualStyle::Graphical => {
+                match self.tileset.coords_px(cell.foreground_graphic) {
+                    Some((tx, ty)) => (Texture::Tilemap, tx, ty),
+                    // NOTE: Fall back to glyphs if the graphic coordinates can't be provided:
+                    None => {
+                        let (tx, ty) = self
+                            .glyph_atlas
+                            .coords_px(self.tile_size, cell.foreground_graphic.into())
+                            .unwrap_or((0, 0));
+                        (Texture::Glyph, tx, ty)
+                    }
+                }
+            }
+            VisualStyle::Textual => {
+                let (tx, ty) = self
+                    .glyph_atlas
+                    .coords_px(self.tile_size, cell.foreground_graphic.into())
+                    .unwrap_or((0, 0));
+                (Texture::Glyph, tx, ty)
+            }
+        };
+
+        let fg_texture_src = Rectangle::from_point_and_size(
+            Point::new(fg_texture_px_x, fg_texture_px_y),
+            Point::from_i32(self.texture_size_px(fg_texture)),
+        );
+        let background_dst = Rectangle::from_point_and_size(
+            Point::new(
+                pos.x * self.tile_size + display_offset_px.x,
+                pos.y * self.tile_size + display_offset_px.y,
+            ),
+            Point::from_i32(self.tile_size),
+        );
+
+        // NOTE: Only render areas within the display size
+        if !rect_intersects_area(background_dst, display_size_px) {
+            return None;
+        }
+
+        let (bg_texture, bg_texture_px_x, bg_texture_px_y) = match visual_style {
+            VisualStyle::Graphical => {
+                match self.tileset.coords_px(cell.background_graphic) {
+                    Some((tx, ty)) => (Texture::Tilemap, tx, ty),
+                    // NOTE: Fall back to glyphs if the graphic coordinates can't be provided:
+                    None => {
+                        let (tx, ty) = self
+                            .glyph_atlas
+                            .coords_px(self.tile_size, cell.background_graphic.into())
+                            .unwrap_or((0, 0));
+                        (Texture::Glyph, tx, ty)
+                    }
+                }
+            }
+            VisualStyle::Textual => {
+                let (tx, ty) = self
+                    .glyph_atlas
+                    .coords_px(self.tile_size, cell.background_graphic.into())
+                    .unwrap_or((0, 0));
+                (Texture::Glyph, tx, ty)
+            }
+        };
+        let bg_texture_src = Rectangle::from_point_and_size(
+            Point::new(bg_texture_px_x, bg_texture_px_y),
+            Point::from_i32(self.texture_size_px(bg_texture)),
+        );
+
+        // The background behind the player/monster/item.
+        //
+        // Things are rendered based on a two-item "stack" from the
+        // bottom-up (see `render::render_game`). All tiles start with
+        // their main graphic (empty tile, wall etc.) on the front. Then
+        // we render items, after them monsters/npcs and finally the
+        // player.
+        //
+        // If we're to render something on top of something else (e.g.
+        // food on top of empty tile or monster on top of food), we push
+        // the top-most thing to the background and then render the new
+        // thing on top.
+        //
+        // If we have one thing (empty tile / wall) we have that graphic
+        // in the foreground
+        //
+        // If we have two things (empty tile and monster) we have the
+        // monster on the foreground and the empty tile in the
+        // background
+        //
+        // If we have three things (empty and item and monster), the
+        // empty tile has been overwritten completely, the item is in
+        // the background and the monster is in the foreground.
+        //
+        // In practice, that means `bg_image` is *always* something that's *behind* something else.
+        let background_image = match visual_style {
+            VisualStyle::Graphical => {
+                // Render whatever "empty" background space image is behind the player/monster:
+                Some(Drawcall::Image(
+                    bg_texture,
+                    bg_texture_src,
+                    background_dst,
+                    cell.background_color,
+                ))
+            }
+            VisualStyle::Textual => {
+                // Do nothing.
+                //
+                // We don't want to render the "empty background image"
+                // behind a monster or player because the dot is
+                // visually distracting.
+                None
+            }
+        };
+
+        Some(CellDrawcalls {
+            background: Drawcall::Rectangle(background_dst, cell.empty_color.into()),
+            background_image,
+            image: Drawcall::Image(
+                fg_texture,
+                fg_texture_src,
+                background_dst.offset(cell.offset_px),
+                cell.foreground,
+            ),
+        })
+    }
+}
+
+/// The drawcalls that make up a single grid cell, bottom to top.
+struct CellDrawcalls {
+    background: Drawcall,
+    background_image: Option<Drawcall>,
+    image: Drawcall,
+}
+
+/// Every grid cell gets this many vertices: two triangles for each of
+/// the background, background image and the foreground image.
+const VERTICES_PER_CELL: usize = 18;
+const GRID_SLOT_COMPONENT_COUNT: usize = VERTICES_PER_CELL * VERTEX_COMPONENT_COUNT;
+
+/// Everything besides the cell itself its vertices depend on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct GridInputs {
+    visual_style: VisualStyle,
+    tile_size: i32,
+    offset_px: Point,
+    clear_background_color: Option<Color>,
+    display_size: [f32; 2],
+}
+
+/// Vertices of the `Display` grid kept between frames.
+///
+/// The background behind the grid and every cell own a fixed slot of
+/// `VERTICES_PER_CELL` vertices. The ones a cell doesn't use are
+/// degenerate triangles that don't draw anything. That way a changed
+/// cell only rewrites (and uploads) its own slot and a frame where
+/// nothing moved doesn't touch the grid at all.
+#[derive(Default)]
+pub struct GridVertices {
+    vertices: Vec<f32>,
+    /// The cells the vertices were built from.
+    cells: Vec<Cell>,
+    inputs: Option<GridInputs>,
+    tileset: Tileset,
+    glyph_atlas: GlyphAtlas,
+    /// Changed `(index, count)` vertex ranges, see `Display::take_grid_dirty_ranges`.
+    dirty_ranges: Vec<(usize, usize)>,
+    scratch: Vec<f32>,
+}
+
+impl GridVertices {
+    /// The vertex buffer (i.e. the `f32` components) of the grid.
+    pub fn vertices(&self) -> &[f32] {
+        &self.vertices
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / VERTEX_COMPONENT_COUNT
+    }
+
+    fn write_slot(&mut self, slot: usize, drawcalls: &[Drawcall], display_size: [f32; 2]) {
+        self.scratch.clear();
+        build_vertices(drawcalls, &mut self.scratch, display_size);
+        debug_assert!(self.scratch.len() <= GRID_SLOT_COMPONENT_COUNT);
+        self.scratch.resize(GRID_SLOT_COMPONENT_COUNT, 0.0);
+        let start = slot * GRID_SLOT_COMPONENT_COUNT;
+        self.vertices[start..start + GRID_SLOT_COMPONENT_COUNT].copy_from_slice(&self.scratch);
+    }
+
+    fn mark_slot_dirty(&mut self, slot: usize) {
+        let index = slot * VERTICES_PER_CELL;
+        match self.dirty_ranges.last_mut() {
+            // NOTE: merge the neighbouring cells into a single range
+            // so a changed row is uploaded in one go.
+            Some((last_index, last_count)) if *last_index + *last_count == index => {
+                *last_count += VERTICES_PER_CELL;
+            }
+            _ => self.dirty_ranges.push((index, VERTICES_PER_CELL)),
+        }
+    }
 }
 
 /// Returns `true` if the `Rectangle` intersects the area that starts at `(0, 0)`
@@ -1052,3 +1218,62 @@ fn rect_intersects_area(rect: Rectangle, area: Point) -> bool {
 }
 
 include!(concat!(env!("OUT_DIR"), "/glyph_lookup_table.rs"));
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rendered_display(display: &mut Display, player_pos: Point) {
+        display.clear(color::BLACK);
+        display.set_cell(Point::new(1, 1), Graphic::Tree1, color::WHITE, color::BLACK);
+        display.set_cell(
+            player_pos,
+            Graphic::CharacterBelly,
+            color::WHITE,
+            color::BLACK,
+        );
+        display.update_grid_vertices(VisualStyle::Graphical, [100.0, 100.0]);
+    }
+
+    #[test]
+    fn test_grid_vertices_rebuilt_at_first() {
+        let mut display = Display::new(Point::new(100, 100), 10, 10);
+        rendered_display(&mut display, Point::new(5, 5));
+        let vertex_count = display.grid_vertices().vertex_count();
+        assert_eq!(vertex_count, (12 * 12 + 1) * VERTICES_PER_CELL);
+        assert_eq!(display.take_grid_dirty_ranges(), vec![(0, vertex_count)]);
+    }
+
+    #[test]
+    fn test_grid_vertices_unchanged() {
+        let mut display = Display::new(Point::new(100, 100), 10, 10);
+        rendered_display(&mut display, Point::new(5, 5));
+        display.take_grid_dirty_ranges();
+
+        rendered_display(&mut display, Point::new(5, 5));
+        assert_eq!(display.take_grid_dirty_ranges(), vec![]);
+    }
+
+    #[test]
+    fn test_grid_vertices_changed_cells() {
+        let mut display = Display::new(Point::new(100, 100), 10, 10);
+        rendered_display(&mut display, Point::new(5, 5));
+        display.take_grid_dirty_ranges();
+
+        // NOTE: the player moved one tile right, so the two cells are
+        // next to each other and their ranges get merged.
+        rendered_display(&mut display, Point::new(6, 5));
+        // NOTE: the cell at (5, 5) plus the one-tile padding in a
+        // 12 tiles wide grid. The first slot is the background.
+        let slot = 6 * 12 + 6 + 1;
+        assert_eq!(
+            display.take_grid_dirty_ranges(),
+            vec![(slot * VERTICES_PER_CELL, 2 * VERTICES_PER_CELL)]
+        );
+
+        display.offset_px = Point::new(0, 3);
+        rendered_display(&mut display, Point::new(6, 5));
+        let vertex_count = display.grid_vertices().vertex_count();
+        assert_eq!(display.take_grid_dirty_ranges(), vec![(0, vertex_count)]);
+    }
+}
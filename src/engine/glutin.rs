@@ -1,6 +1,7 @@
 #![allow(unsafe_code)]
 
 use crate::{
+    assets::AssetPack,
     color::Color,
     engine::{
         self,
@@ -14,9 +15,11 @@ use crate::{
     state::State,
 };
 
-use std::{error::Error, num::NonZeroU32, time::Instant};
-
-use game_loop::game_loop;
+use std::{
+    error::Error,
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
 
 use glutin::{
     context::{NotCurrentContext, PossiblyCurrentContext},
@@ -48,7 +51,14 @@ fn key_code_from_backend(
     let key_code = match physical_key {
         PhysicalKey::Code(WinitKeyCode::Enter) => Some(keys::KeyCode::Enter),
         PhysicalKey::Code(WinitKeyCode::Escape) => Some(keys::KeyCode::Esc),
+        PhysicalKey::Code(WinitKeyCode::Backspace) => Some(keys::KeyCode::Backspace),
         PhysicalKey::Code(WinitKeyCode::Space) => Some(keys::KeyCode::Space),
+        PhysicalKey::Code(WinitKeyCode::Equal | WinitKeyCode::NumpadAdd) => {
+            Some(keys::KeyCode::Plus)
+        }
+        PhysicalKey::Code(WinitKeyCode::Minus | WinitKeyCode::NumpadSubtract) => {
+            Some(keys::KeyCode::Minus)
+        }
 
         PhysicalKey::Code(WinitKeyCode::Numpad0) => Some(keys::KeyCode::D0),
         PhysicalKey::Code(WinitKeyCode::Numpad1) => Some(keys::KeyCode::D1),
@@ -134,8 +144,9 @@ fn key_code_from_backend(
     None
 }
 
-#[derive(Debug)]
-struct TriggerUpdateEvent {}
+/// How often to check for the gamepad events while waiting for the
+/// player's input.
+const GAMEPAD_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 struct App<S: SettingsStore + 'static> {
     display_builder: DisplayBuilder,
@@ -153,6 +164,9 @@ struct App<S: SettingsStore + 'static> {
     pre_fullscreen_window_pos: Point,
     exit_state: Result<(), Box<dyn std::error::Error>>,
     last_tick_time: Instant,
+    next_update_time: Instant,
+    /// We've received an input event the game hasn't processed yet.
+    input_since_update: bool,
 }
 
 impl<S: SettingsStore + 'static> App<S> {
@@ -172,6 +186,8 @@ impl<S: SettingsStore + 'static> App<S> {
             pre_fullscreen_window_pos: Default::default(),
             exit_state: Ok(()),
             last_tick_time: Instant::now(),
+            next_update_time: Instant::now(),
+            input_since_update: false,
         }
     }
 
@@ -262,7 +278,7 @@ impl<S: SettingsStore + 'static> App<S> {
 
             let dpi = window.scale_factor();
             log::info!("Window HIDPI factor: {:?}", dpi);
-            self.loop_state.dpi = dpi.floor();
+            self.loop_state.set_dpi(dpi);
 
             log::info!("Window inner size (physical): {:?}", window.inner_size());
 
@@ -302,13 +318,7 @@ impl<S: SettingsStore + 'static> App<S> {
             );
         }
 
-        // Try setting vsync.
-        if let Err(res) = gl_surface.set_swap_interval(
-            gl_context,
-            SwapInterval::Wait(NonZeroU32::new(1).ok_or("NonZero is in fact zero")?),
-        ) {
-            log::error!("Error setting vsync: {res:?}");
-        }
+        set_vsync(&gl_surface, gl_context, self.loop_state.settings.vsync);
 
         assert!(
             self.app_state
@@ -319,10 +329,106 @@ impl<S: SettingsStore + 'static> App<S> {
         self.opengl_app.replace(self.loop_state.opengl_app());
 
         self.last_tick_time = Instant::now();
+        self.next_update_time = self.last_tick_time;
 
         Ok(())
     }
 
+    fn update(&mut self, event_loop: &ActiveEventLoop) {
+        let frame_start_time = Instant::now();
+        let dt = frame_start_time.duration_since(self.last_tick_time);
+        self.last_tick_time = frame_start_time;
+        self.input_since_update = false;
+
+        self.loop_state
+            .egui_context
+            .begin_pass(self.loop_state.egui_raw_input());
+
+        self.loop_state.update_fps(dt);
+
+        match self.loop_state.update_game(dt, &mut self.settings_store) {
+            UpdateResult::QuitRequested => event_loop.exit(),
+            UpdateResult::KeepGoing => {}
+        }
+
+        if let Some(vsync) = self.loop_state.vsync_action()
+            && let Some(AppState { gl_surface, .. }) = self.app_state.as_ref()
+            && let Some(gl_context) = self.gl_context.as_ref()
+        {
+            set_vsync(gl_surface, gl_context, vsync);
+        }
+
+        let output = self.loop_state.end_egui_pass();
+
+        for command in &output.platform_output.commands {
+            if let egui::OutputCommand::OpenUrl(url) = command
+                && let Err(err) = webbrowser::open(&url.url)
+            {
+                log::warn!("Error opening URL {} in the external browser!", url.url);
+                log::warn!("{}", err);
+            }
+        }
+
+        // NOTE: `RedrawRequested` only renders when there are new
+        // shapes. Leaving them out skips the frame (see `fps_cap`).
+        self.egui_shapes = if self.loop_state.render_due() {
+            Some(output.shapes)
+        } else {
+            None
+        };
+
+        if output.textures_delta.set.is_empty() {
+            // We don't need to set/update any textures
+        } else {
+            for (_texture_id, image_delta) in output.textures_delta.set {
+                match image_delta.image {
+                    egui::epaint::image::ImageData::Color(color_image) => {
+                        log::warn!(
+                            "Received ImageDelta::Color(ColorImage) of size: {:?}. Ignoring as we're not set up to handle this.",
+                            color_image.size
+                        );
+                    }
+                    egui::epaint::image::ImageData::Font(font_image) => {
+                        log::warn!(
+                            "We need to update the egui texture map FontImage of size: {:?}",
+                            font_image.size
+                        );
+                        let font_image = loop_state::egui_font_image_apply_delta(
+                            self.loop_state.font_texture.clone(),
+                            image_delta.pos,
+                            font_image,
+                        );
+                        self.loop_state.font_texture = font_image.clone();
+
+                        let egui_texture = loop_state::build_texture_from_egui(font_image);
+                        let (width, height) = egui_texture.dimensions();
+
+                        if let Some(opengl_app) = &mut self.opengl_app {
+                            opengl_app.eguimap_size_px = [width as f32, height as f32];
+                            opengl_app.upload_texture(opengl_app.eguimap, "egui", &egui_texture);
+                        }
+                    }
+                }
+            }
+        }
+
+        if output.textures_delta.free.is_empty() {
+            // Don't print anything
+        } else {
+            // NOTE: I don't think we need to free anything.
+            // We're just uploading the single egui-based
+            // texture.
+            log::warn!("Texture IDs to free");
+            for texture_id in output.textures_delta.free {
+                dbg!(texture_id);
+            }
+        }
+
+        if let Some(AppState { window, .. }) = self.app_state.as_ref() {
+            window.request_redraw();
+        }
+    }
+
     fn exiting(&mut self) {
         log::info!(
             "Drawcall count: {}. Capacity: {}.",
@@ -360,7 +466,7 @@ impl<S: SettingsStore + 'static> App<S> {
     }
 }
 
-impl<S: SettingsStore + 'static> ApplicationHandler<TriggerUpdateEvent> for App<S> {
+impl<S: SettingsStore + 'static> ApplicationHandler for App<S> {
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -369,6 +475,10 @@ impl<S: SettingsStore + 'static> ApplicationHandler<TriggerUpdateEvent> for App<
     ) {
         use winit::event::{KeyEvent, WindowEvent};
 
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            self.input_since_update = true;
+        }
+
         match event {
             WindowEvent::Resized(size) if size.width != 0 && size.height != 0 => {
                 log::info!("WindowEvent::Resized: {:?}", size);
@@ -402,6 +512,15 @@ impl<S: SettingsStore + 'static> ApplicationHandler<TriggerUpdateEvent> for App<
                 }
             }
 
+            // NOTE: the window moved to a monitor with a different
+            // scaling or the player changed it in the system
+            // settings. The `Resized` event with the new physical
+            // size follows.
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                log::info!("WindowEvent::ScaleFactorChanged: {:?}", scale_factor);
+                self.loop_state.set_dpi(scale_factor);
+            }
+
             WindowEvent::Moved(new_pos) => {
                 if self.loop_state.settings.fullscreen || self.loop_state.switched_from_fullscreen {
                     // Don't update the window position
@@ -618,85 +737,54 @@ impl<S: SettingsStore + 'static> ApplicationHandler<TriggerUpdateEvent> for App<
         }
     }
 
-    fn user_event(&mut self, event_loop: &ActiveEventLoop, _event: TriggerUpdateEvent) {
-        let frame_start_time = Instant::now();
-        let dt = frame_start_time.duration_since(self.last_tick_time);
-        self.last_tick_time = frame_start_time;
-
-        self.loop_state
-            .egui_context
-            .begin_pass(self.loop_state.egui_raw_input());
-
-        self.loop_state.update_fps(dt);
-
-        match self.loop_state.update_game(dt, &mut self.settings_store) {
-            UpdateResult::QuitRequested => event_loop.exit(),
-            UpdateResult::KeepGoing => {}
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.app_state.is_none() {
+            return;
         }
 
-        let output = self.loop_state.egui_context.end_pass();
-
-        for command in &output.platform_output.commands {
-            if let egui::OutputCommand::OpenUrl(url) = command
-                && let Err(err) = webbrowser::open(&url.url)
-            {
-                log::warn!("Error opening URL {} in the external browser!", url.url);
-                log::warn!("{}", err);
+        let target_dt = Duration::from_secs(1) / formula::FPS as u32;
+        // NOTE: allow for 1ms variance between the actual and the
+        // scheduled update time. Same as the SDL backends.
+        let inc = Duration::from_millis(1);
+        let now = Instant::now();
+
+        let update_ready = match self.loop_state.idle_timeout() {
+            Some(idle_timeout) => {
+                self.input_since_update
+                    || now.duration_since(self.last_tick_time) >= idle_timeout
+                    || self.loop_state.gamepad_event_pending()
             }
-        }
-
-        self.egui_shapes = Some(output.shapes);
-
-        if output.textures_delta.set.is_empty() {
-            // We don't need to set/update any textures
-        } else {
-            for (_texture_id, image_delta) in output.textures_delta.set {
-                match image_delta.image {
-                    egui::epaint::image::ImageData::Color(color_image) => {
-                        log::warn!(
-                            "Received ImageDelta::Color(ColorImage) of size: {:?}. Ignoring as we're not set up to handle this.",
-                            color_image.size
-                        );
-                    }
-                    egui::epaint::image::ImageData::Font(font_image) => {
-                        log::warn!(
-                            "We need to update the egui texture map FontImage of size: {:?}",
-                            font_image.size
-                        );
-                        let font_image = loop_state::egui_font_image_apply_delta(
-                            self.loop_state.font_texture.clone(),
-                            image_delta.pos,
-                            font_image,
-                        );
-                        self.loop_state.font_texture = font_image.clone();
-
-                        let egui_texture = loop_state::build_texture_from_egui(font_image);
-                        let (width, height) = egui_texture.dimensions();
+            None => now + inc >= self.next_update_time,
+        };
 
-                        if let Some(opengl_app) = &mut self.opengl_app {
-                            opengl_app.eguimap_size_px = [width as f32, height as f32];
-                            opengl_app.upload_texture(opengl_app.eguimap, "egui", &egui_texture);
-                        }
-                    }
-                }
+        if update_ready {
+            // NOTE: if we've fallen behind (e.g. we've just woken up
+            // from the idle wait), don't try to catch up on all the
+            // frames we've missed. Just start counting from now.
+            self.next_update_time += target_dt;
+            if self.next_update_time < now {
+                self.next_update_time = now;
             }
+            self.update(event_loop);
         }
 
-        if output.textures_delta.free.is_empty() {
-            // Don't print anything
-        } else {
-            // NOTE: I don't think we need to free anything.
-            // We're just uploading the single egui-based
-            // texture.
-            log::warn!("Texture IDs to free");
-            for texture_id in output.textures_delta.free {
-                dbg!(texture_id);
+        // NOTE: nothing's happening in the game, sleep until the
+        // player does something or the idle timeout runs out. But
+        // winit doesn't know about the gamepad events (we use gilrs
+        // for that) so we need to wake up and check them every now
+        // and then.
+        let wake_up_time = match self.loop_state.idle_timeout() {
+            Some(idle_timeout) => {
+                let idle_deadline = self.last_tick_time + idle_timeout;
+                if self.loop_state.gamepad_connected() {
+                    idle_deadline.min(Instant::now() + GAMEPAD_POLL_INTERVAL)
+                } else {
+                    idle_deadline
+                }
             }
-        }
-
-        if let Some(AppState { window, .. }) = self.app_state.as_ref() {
-            window.request_redraw();
-        }
+            None => self.next_update_time,
+        };
+        event_loop.set_control_flow(ControlFlow::WaitUntil(wake_up_time));
     }
 
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
@@ -710,6 +798,21 @@ impl<S: SettingsStore + 'static> ApplicationHandler<TriggerUpdateEvent> for App<
     }
 }
 
+fn set_vsync(
+    gl_surface: &Surface<WindowSurface>,
+    gl_context: &PossiblyCurrentContext,
+    vsync: bool,
+) {
+    let interval = if vsync {
+        SwapInterval::Wait(NonZeroU32::MIN)
+    } else {
+        SwapInterval::DontWait
+    };
+    if let Err(err) = gl_surface.set_swap_interval(gl_context, interval) {
+        log::error!("Error setting the swap interval to {interval:?}: {err:?}");
+    }
+}
+
 struct AppState {
     gl_surface: Surface<WindowSurface>,
     // NOTE: Window should be dropped after all resources created using its
@@ -802,6 +905,7 @@ pub fn main_loop<S>(
     window_title: &str,
     settings_store: S,
     initial_state: Box<State>,
+    assets: AssetPack,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
     S: SettingsStore + 'static,
@@ -833,47 +937,17 @@ where
         initial_default_background,
         initial_state,
         egui_context,
+        &assets,
     );
 
-    let event_loop = EventLoop::<TriggerUpdateEvent>::with_user_event().build()?;
+    let event_loop = EventLoop::new()?;
 
-    // Update/redraw continuously (rather than just wait for user input)
-    event_loop.set_control_flow(ControlFlow::Poll);
+    // NOTE: `App::about_to_wait` schedules the next update. Either
+    // at the fixed `formula::FPS` rate or when the game is waiting
+    // for the player's input.
+    event_loop.set_control_flow(ControlFlow::Wait);
     log::debug!("Created event loop: {:?}", event_loop);
 
-    let event_loop_proxy = event_loop.create_proxy();
-
-    let _join_handle = std::thread::spawn(move || {
-        game_loop(
-            event_loop_proxy,
-            formula::FPS as u32,
-            0.1,
-            |g| {
-                // update game
-                if let Err(e) = g.game.send_event(TriggerUpdateEvent {}) {
-                    log::debug!("The event loop has closed: {e}. Closing the update thread.");
-                    g.exit();
-                }
-            },
-            |_g| {
-                // render game
-
-                // NOTE: leaving this empty. Without explicit
-                // throttling, this is emited as many times as it
-                // could and there's no need. We're triggering the
-                // `RedrawRequested` event during the game update and
-                // that's exactly when and how many times we want it.
-
-                // There's no independent rendering or animations here
-                // now so this works perfectly and there's nothing to
-                // do here.
-            },
-        );
-    });
-
-    // Set up a loop here that triggers these user events
-    // and implement the user event handler in the App struct
-
     let desired_size = {
         let size = loop_state.desired_window_size_px();
         LogicalSize::new(size.0, size.1)
@@ -921,14 +995,5 @@ where
     let mut app = App::new(display_builder, loop_state, settings_store);
     event_loop.run_app(&mut app)?;
 
-    // NOTE: I'm not sure whether we need to join the thread (this
-    // basically blocks until the thread finishes). I reckon the OS
-    // will just reap the running thread. At any rate, the thread
-    // should finish once the event loop is gone. I just don't want to
-    // risk some weird scenario that would keep the thread running and
-    // hang the game on exit.
-    //
-    // join_handle.join();
-
     app.exit_state
 }
@@ -0,0 +1,269 @@
+//! The layout of the glyph texture the `Display` renders its text and
+//! ASCII graphics from.
+//!
+//! The glyphs for the `AVAILABLE_TILE_SIZES` and
+//! `AVAILABLE_TEXT_SIZES` are baked into the binary by `build.rs`.
+//! That's all we need on a regular display. But on a high-DPI one,
+//! every logical pixel is rendered as `scale` physical pixels and the
+//! baked glyphs would have to be stretched (and blurred). So we
+//! rasterise the glyphs at their physical size at runtime instead.
+
+use crate::engine::{
+    AVAILABLE_TEXT_SIZES, AVAILABLE_TILE_SIZES, BAKED_GLYPH_SIZES, GLYPH_CHARS,
+    GLYPHMAP_TEXTURE_HEIGHT, GLYPHMAP_TEXTURE_WIDTH,
+};
+
+/// The narrowest glyph texture we make. The baked one is this wide too.
+const MIN_TEXTURE_WIDTH: i32 = 512;
+
+/// Where each glyph is in the glyph texture.
+///
+/// The glyphs of each size are packed in rows of `GLYPH_CHARS`, the
+/// biggest size at the top. This matches the texture baked by
+/// `build.rs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphAtlas {
+    /// Physical pixels per logical one the glyphs are rasterised for.
+    pub scale: f32,
+    /// Width and height of the whole glyph texture in pixels.
+    pub texture_size_px: (i32, i32),
+    /// The rasterised glyph sizes in physical pixels. Biggest first.
+    sizes_px: Vec<i32>,
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        Self::baked()
+    }
+}
+
+impl GlyphAtlas {
+    /// The glyph texture baked into the binary.
+    pub fn baked() -> Self {
+        Self {
+            scale: 1.0,
+            texture_size_px: (
+                GLYPHMAP_TEXTURE_WIDTH as i32,
+                GLYPHMAP_TEXTURE_HEIGHT as i32,
+            ),
+            sizes_px: BAKED_GLYPH_SIZES.to_vec(),
+        }
+    }
+
+    /// The atlas with all the tile and text sizes the player can pick
+    /// rasterised for the given `scale` (i.e. the display DPI).
+    pub fn for_scale(scale: f32) -> Self {
+        let logical_sizes = AVAILABLE_TILE_SIZES
+            .iter()
+            .chain(AVAILABLE_TEXT_SIZES.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        Self::new(scale, &logical_sizes)
+    }
+
+    pub fn new(scale: f32, logical_sizes: &[i32]) -> Self {
+        let mut sizes_px = logical_sizes
+            .iter()
+            .map(|&size| physical_size_px(size, scale))
+            .collect::<Vec<_>>();
+        sizes_px.sort_by(|a, b| b.cmp(a));
+        sizes_px.dedup();
+
+        let width = sizes_px
+            .first()
+            .map_or(MIN_TEXTURE_WIDTH, |&size| {
+                (size as u32).next_power_of_two() as i32
+            })
+            .max(MIN_TEXTURE_WIDTH);
+        let height = sizes_px
+            .iter()
+            .map(|&size| size * lines(size, width))
+            .sum::<i32>();
+        Self {
+            scale,
+            texture_size_px: (width, (height.max(1) as u32).next_power_of_two() as i32),
+            sizes_px,
+        }
+    }
+
+    /// The size of the glyphs in the texture that cover
+    /// `logical_size` pixels on the screen.
+    pub fn glyph_size_px(&self, logical_size: i32) -> i32 {
+        physical_size_px(logical_size, self.scale)
+    }
+
+    /// Top-left corner of the glyph in the texture (in pixels).
+    pub fn coords_px(&self, logical_size: i32, chr: char) -> Option<(i32, i32)> {
+        self.physical_coords_px(self.glyph_size_px(logical_size), chr)
+    }
+
+    /// Every glyph in the texture: its size, character and top-left
+    /// corner.
+    pub fn glyphs(&self) -> impl Iterator<Item = (i32, char, (i32, i32))> + '_ {
+        self.sizes_px.iter().flat_map(move |&size| {
+            GLYPH_CHARS.iter().filter_map(move |&chr| {
+                let coords = self.physical_coords_px(size, chr)?;
+                Some((size, chr, coords))
+            })
+        })
+    }
+
+    fn physical_coords_px(&self, size: i32, chr: char) -> Option<(i32, i32)> {
+        let index = GLYPH_CHARS.iter().position(|&c| c == chr)? as i32;
+        let mut offset_y = 0;
+        for &size_px in &self.sizes_px {
+            let glyphs_per_line = self.texture_size_px.0 / size_px;
+            if size_px == size {
+                let column = index % glyphs_per_line;
+                let line = index / glyphs_per_line;
+                return Some((column * size_px, offset_y + line * size_px));
+            }
+            offset_y += size_px * lines(size_px, self.texture_size_px.0);
+        }
+        None
+    }
+}
+
+/// The number of physical pixels that cover `logical_size_px` at the
+/// given DPI `scale`.
+pub fn physical_size_px(logical_size_px: i32, scale: f32) -> i32 {
+    ((logical_size_px as f32 * scale).round() as i32).max(1)
+}
+
+/// The number of texture lines all the glyphs of the given size take.
+fn lines(size_px: i32, texture_width: i32) -> i32 {
+    let glyphs_per_line = (texture_width / size_px).max(1);
+    (GLYPH_CHARS.len() as i32 + glyphs_per_line - 1) / glyphs_per_line
+}
+
+/// The font `build.rs` bakes the glyphs from.
+pub const EMBEDDED_FONT: &[u8] = include_bytes!("../../fonts/mononoki-Regular.ttf");
+
+/// Render the glyph texture described by the `atlas` from the given
+/// TrueType or OpenType `font_data`.
+///
+/// Passing the `EMBEDDED_FONT` makes the runtime glyphs look just like
+/// the baked ones.
+#[cfg(any(
+    feature = "glutin-backend",
+    feature = "sdl2-backend",
+    feature = "sdl3-backend"
+))]
+pub fn rasterise(atlas: &GlyphAtlas, font_data: &[u8]) -> image::RgbaImage {
+    use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+
+    let (texture_width, texture_height) = atlas.texture_size_px;
+    let mut glyphmap = image::RgbaImage::new(texture_width as u32, texture_height as u32);
+
+    let font = match FontRef::try_from_slice(font_data) {
+        Ok(font) => font,
+        Err(err) => {
+            log::error!("Could not load the glyph font: {}", err);
+            log::warn!("Falling back to the embedded font.");
+            match FontRef::try_from_slice(EMBEDDED_FONT) {
+                Ok(font) => font,
+                Err(err) => {
+                    log::error!("Could not load the embedded glyph font: {}", err);
+                    return glyphmap;
+                }
+            }
+        }
+    };
+
+    for (size, chr, (tilepos_x, tilepos_y)) in atlas.glyphs() {
+        let scaled_font = font.as_scaled(PxScale::from(size as f32));
+        let glyph_id = font.glyph_id(chr);
+        let offset_x = (size as f32 - scaled_font.h_advance(glyph_id)) / 2.0;
+        let glyph = glyph_id.with_scale_and_position(
+            scaled_font.scale(),
+            point(
+                tilepos_x as f32 + offset_x,
+                tilepos_y as f32 + scaled_font.ascent(),
+            ),
+        );
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            // NOTE: e.g. the space has nothing to draw
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, coverage| {
+            let x = x as i32 + bounds.min.x as i32;
+            let y = y as i32 + bounds.min.y as i32;
+            // The glyph could still clip the boundaries of the texture
+            if x >= 0 && x < texture_width && y >= 0 && y < texture_height {
+                let alpha = (coverage * 255.0) as u8;
+                glyphmap.put_pixel(x as u32, y as u32, image::Rgba([255, 255, 255, alpha]));
+            }
+        });
+    }
+
+    glyphmap
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::glyph_coords_px_from_char;
+
+    #[test]
+    fn baked_layout_matches_the_build_script() {
+        let atlas = GlyphAtlas::baked();
+        for &size in &BAKED_GLYPH_SIZES {
+            for &chr in &GLYPH_CHARS {
+                assert_eq!(
+                    atlas.coords_px(size, chr),
+                    glyph_coords_px_from_char(size as u32, chr),
+                    "size: {size}, char: {chr:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unscaled_atlas_is_the_baked_one() {
+        assert_eq!(GlyphAtlas::for_scale(1.0), GlyphAtlas::baked());
+    }
+
+    #[test]
+    fn scaled_glyphs_fit_the_texture() {
+        for scale in [1.25, 1.5, 1.75, 2.0, 3.0] {
+            let atlas = GlyphAtlas::for_scale(scale);
+            let (width, height) = atlas.texture_size_px;
+            for (size, chr, (x, y)) in atlas.glyphs() {
+                assert!(x + size <= width, "scale: {scale}, char: {chr:?}");
+                assert!(y + size <= height, "scale: {scale}, char: {chr:?}");
+            }
+            assert_eq!(
+                atlas.glyphs().count(),
+                atlas.sizes_px.len() * GLYPH_CHARS.len()
+            );
+            assert_eq!(atlas.glyph_size_px(20), physical_size_px(20, scale));
+        }
+    }
+
+    #[cfg(any(
+        feature = "glutin-backend",
+        feature = "sdl2-backend",
+        feature = "sdl3-backend"
+    ))]
+    #[test]
+    fn rasterises_the_glyphs_in_their_place() {
+        let atlas = GlyphAtlas::for_scale(1.5);
+        let glyphmap = rasterise(&atlas, EMBEDDED_FONT);
+        assert_eq!(
+            (glyphmap.width() as i32, glyphmap.height() as i32),
+            atlas.texture_size_px
+        );
+
+        let covered = |chr: char| {
+            let size = atlas.glyph_size_px(40);
+            let (x, y) = atlas.coords_px(40, chr).unwrap_or_default();
+            (x..x + size)
+                .flat_map(|x| (y..y + size).map(move |y| (x, y)))
+                .filter(|&(x, y)| glyphmap.get_pixel(x as u32, y as u32)[3] > 0)
+                .count()
+        };
+        assert_eq!(covered(' '), 0);
+        assert!(covered('@') > 0);
+    }
+}
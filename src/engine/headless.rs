@@ -13,9 +13,21 @@ use std::time::Duration;
 use egui::Context;
 
 pub fn main_loop<S>(
-    mut settings_store: S,
+    settings_store: S,
     initial_state: Box<State>,
 ) -> Result<(), Box<dyn std::error::Error>>
+where
+    S: SettingsStore + 'static,
+{
+    simulate(settings_store, initial_state).map(|_final_state| ())
+}
+
+/// Run the game without a window or any input until it stops and
+/// return its final state. Only makes sense for replays.
+pub fn simulate<S>(
+    mut settings_store: S,
+    initial_state: Box<State>,
+) -> Result<Box<State>, Box<dyn std::error::Error>>
 where
     S: SettingsStore + 'static,
 {
@@ -33,11 +45,9 @@ where
     let mut audio = Audio::without_backend();
     let mut game_state = initial_state;
 
-    let tile_width_px = settings.tile_size;
-    let text_width_px = settings.text_size;
-    let metrics = &Metrics {
-        tile_width_px,
-        text_width_px,
+    let mut metrics = Metrics {
+        tile_width_px: settings.tile_size,
+        text_width_px: settings.text_size,
     };
 
     let dt = Duration::from_millis(16);
@@ -53,7 +63,7 @@ where
             mouse,
             &mut gamepad,
             &mut settings,
-            metrics,
+            &metrics,
             &mut settings_store,
             &mut display,
             &mut audio,
@@ -73,7 +83,7 @@ where
                 Mouse::new(),
                 &mut gamepad,
                 &mut settings,
-                metrics,
+                &metrics,
                 &mut settings_store,
                 &mut display,
                 &mut audio,
@@ -83,6 +93,13 @@ where
             log::debug!("Finished the frame skip");
         }
 
+        // NOTE: the game can zoom the view. The mouse positions in the
+        // replay depend on the tile size so follow it here, too.
+        if display.tile_size != settings.tile_size {
+            display = Display::new(window_size_px, settings.tile_size, settings.text_size);
+            metrics.tile_width_px = settings.tile_size;
+        }
+
         match update_result {
             RunningState::Running => {}
             RunningState::NewGame(_new_state) => throw!(
@@ -95,5 +112,5 @@ where
         }
     }
 
-    Ok(())
+    Ok(game_state)
 }
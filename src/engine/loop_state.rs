@@ -1,16 +1,28 @@
 use crate::{
+    assets::AssetPack,
     audio::Audio,
     color::Color,
-    engine::{self, Display, DisplayInfo, Drawcall, Mouse, TextMetrics, Vertex, opengl::OpenGlApp},
+    engine::{
+        self, Display, DisplayInfo, Drawcall, Mouse, TextMetrics, Vertex,
+        glyph_atlas::{self, GlyphAtlas},
+        opengl::OpenGlApp,
+    },
+    formula,
     gamepad::{self, Gamepad},
+    graphic::Tileset,
     keys::Key,
     palette::Palette,
     point::Point,
     settings::{Settings, Store as SettingsStore},
     state::State,
+    trace,
 };
 
-use std::{convert::TryInto, sync::Arc, time::Duration};
+use std::{
+    convert::TryInto,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use egui::{self, Event, RawInput, epaint::image::FontImage};
 
@@ -115,16 +127,20 @@ pub fn egui_font_image_apply_delta(
     }
 }
 
-pub fn egui_load_font(ctx: &egui::Context) {
+pub fn egui_load_font(ctx: &egui::Context, assets: &AssetPack) {
     let font_definitions = {
         let family = egui::FontFamily::Monospace;
         let font_name = String::from("Mononoki");
 
+        // NOTE: the `Display` glyphs are rasterised from the same
+        // font in `LoopState::rebuild_glyphmap`.
+        let font_data = match assets.font() {
+            Some(data) => egui::FontData::from_owned(data),
+            None => egui::FontData::from_static(glyph_atlas::EMBEDDED_FONT),
+        };
+
         let mut def = egui::FontDefinitions::default();
-        def.font_data.insert(
-            font_name.clone(),
-            egui::FontData::from_static(include_bytes!("../../fonts/mononoki-Regular.ttf")).into(),
-        );
+        def.font_data.insert(font_name.clone(), font_data.into());
 
         if let Some(fam) = def.families.get_mut(&family) {
             fam.insert(0, font_name);
@@ -172,6 +188,11 @@ pub struct LoopState {
     pub audio: Audio,
     pub dpi: f64,
     pub glyphmap: RgbaImage,
+    /// The `glyphmap` must be uploaded to the GPU again.
+    pub glyphmap_changed: bool,
+    /// The asset pack font the glyphs are rasterised from instead of
+    /// the embedded one.
+    pub glyph_font: Option<Vec<u8>>,
     pub tilemap: RgbaImage,
     pub egui_context: egui::Context,
     pub gilrs: Option<Gilrs>,
@@ -188,6 +209,9 @@ pub struct LoopState {
     pub switched_from_fullscreen: bool,
     pub frames_in_current_second: i32,
     pub fps: i32,
+    /// When egui wants to be repainted again (as of the last pass).
+    pub egui_repaint_delay: Duration,
+    pub last_render_time: Option<Instant>,
 
     // NOTE: This will wrap after running continuously for over 64
     // years at 60 FPS. 32 bits are just fine.
@@ -200,6 +224,7 @@ impl LoopState {
         default_background: Color,
         game_state: Box<State>,
         egui_context: egui::Context,
+        assets: &AssetPack,
     ) -> Self {
         // TODO: do this for every Display creatio / window resize
         let window_size_px =
@@ -210,7 +235,7 @@ impl LoopState {
             engine::VERTEX_COMPONENT_COUNT * 4
         );
 
-        let display = Display::new(window_size_px, settings.tile_size, settings.text_size);
+        let mut display = Display::new(window_size_px, settings.tile_size, settings.text_size);
 
         log::debug!(
             "Requested display in tiles: {} x {}",
@@ -218,20 +243,15 @@ impl LoopState {
             display.display_size.y
         );
 
-        let glyphmap = {
-            let data = &include_bytes!(concat!(env!("OUT_DIR"), "/glyph.png"))[..];
-            image::load_from_memory_with_format(data, image::ImageFormat::Png)
-                .unwrap_or_else(|e| {
-                    log::error!("Error loading the glyph tilemap image: {}", e);
-                    log::warn!("Generating an empty 32x32 pixels image as a fallback.");
-                    image::DynamicImage::new_rgba8(32, 32)
-                })
-                .to_rgba8()
+        let glyph_font = assets.font();
+        let glyphmap = match &glyph_font {
+            Some(font_data) => glyph_atlas::rasterise(&display.glyph_atlas, font_data),
+            None => baked_glyphmap(),
         };
         log::debug!("Loaded glyph tilemap.");
 
-        let mut tilemap = {
-            // NOTE: including a manually-edited tileset based on Bountiful Bits
+        // NOTE: including a manually-edited tileset based on Bountiful Bits
+        let embedded_tilemap = || {
             let data = &include_bytes!("../../assets/tiles.png")[..];
             image::load_from_memory_with_format(data, image::ImageFormat::Png)
                 .unwrap_or_else(|e| {
@@ -241,6 +261,42 @@ impl LoopState {
                 })
                 .to_rgba8()
         };
+
+        let external_tilemap = assets.tilemap().and_then(|data| {
+            image::load_from_memory_with_format(&data, image::ImageFormat::Png)
+                .map_err(|e| log::error!("Error loading the asset pack tilemap image: {}", e))
+                .ok()
+                .map(|image| image.to_rgba8())
+        });
+
+        let mut tilemap = match external_tilemap {
+            Some(tilemap) => {
+                let texture_size_px = (tilemap.width() as i32, tilemap.height() as i32);
+                let tileset = assets.tilemap_index().map_or_else(
+                    || {
+                        log::warn!(
+                            "The asset pack has a tilemap but no index. Using the default tile positions."
+                        );
+                        let mut tileset = Tileset::default();
+                        tileset.texture_size_px = texture_size_px;
+                        Ok(tileset)
+                    },
+                    |index| Tileset::from_index(&index, texture_size_px),
+                );
+                match tileset {
+                    Ok(tileset) => {
+                        display.tileset = tileset;
+                        tilemap
+                    }
+                    Err(err) => {
+                        log::error!("Could not load the tilemap index: {}", err);
+                        log::warn!("Falling back to the embedded tilemap.");
+                        embedded_tilemap()
+                    }
+                }
+            }
+            None => embedded_tilemap(),
+        };
         log::debug!("Loaded the graphics tilemap.");
         // Normalise the tilemap colours.
         //
@@ -256,7 +312,7 @@ impl LoopState {
         log::debug!("Normalised the graphics tilemap colours.");
         let tilemap = tilemap; // Disable `mut`
 
-        egui_load_font(&egui_context);
+        egui_load_font(&egui_context, assets);
 
         let style = egui_style(&game_state.palette, settings.text_size as f32);
         egui_context.set_style(Arc::new(style));
@@ -292,9 +348,11 @@ impl LoopState {
             settings,
             previous_settings,
             display,
-            audio: Audio::new(),
+            audio: Audio::new(assets),
             dpi: 1.0,
             glyphmap,
+            glyphmap_changed: false,
+            glyph_font,
             tilemap,
             egui_context,
             gilrs,
@@ -311,10 +369,48 @@ impl LoopState {
             switched_from_fullscreen: false,
             frames_in_current_second: 0,
             fps: 0,
+            egui_repaint_delay: Duration::ZERO,
+            last_render_time: None,
             current_frame_id: 0,
         }
     }
 
+    /// Update the number of physical pixels per logical one. E.g.
+    /// when the window moves to a monitor with a different scaling.
+    ///
+    /// The game keeps its layout in logical pixels. But the glyphs
+    /// are rasterised for the physical ones to keep them crisp.
+    pub fn set_dpi(&mut self, dpi: f64) {
+        if dpi <= 0.0 || dpi == self.dpi {
+            return;
+        }
+        log::info!("Changing the DPI from {} to {}", self.dpi, dpi);
+        self.dpi = dpi;
+
+        let glyph_atlas = GlyphAtlas::for_scale(dpi as f32);
+        if glyph_atlas != self.display.glyph_atlas {
+            log::info!(
+                "Rebuilding the glyphs for the {} DPI scale: {:?}",
+                dpi,
+                glyph_atlas.texture_size_px
+            );
+            self.display.glyph_atlas = glyph_atlas;
+            self.rebuild_glyphmap();
+        }
+    }
+
+    /// Render the glyphs for the current `GlyphAtlas`. The baked ones
+    /// are used unless we need a different size or font.
+    fn rebuild_glyphmap(&mut self) {
+        let atlas = &self.display.glyph_atlas;
+        self.glyphmap = match &self.glyph_font {
+            Some(font_data) => glyph_atlas::rasterise(atlas, font_data),
+            None if *atlas == GlyphAtlas::baked() => baked_glyphmap(),
+            None => glyph_atlas::rasterise(atlas, glyph_atlas::EMBEDDED_FONT),
+        };
+        self.glyphmap_changed = true;
+    }
+
     pub fn opengl_app(&self) -> OpenGlApp {
         let vs_source = include_str!("../shader_150.glslv");
         let fs_source = include_str!("../shader_150.glslf");
@@ -346,25 +442,88 @@ impl LoopState {
         }
     }
 
+    /// How long the engine can wait for the player's input before
+    /// updating the game again. `None` means the game must keep
+    /// updating at the full `formula::FPS` rate.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        if !self.settings.power_saving
+            || crate::game::needs_continuous_updates(&self.game_state)
+            || self.egui_repaint_delay.is_zero()
+        {
+            return None;
+        }
+
+        // NOTE: keep updating at a slower rate even when idle. The
+        // ambient animations, egui's delayed repaints (e.g. tooltips)
+        // and the background music queue all rely on that.
+        Some(self.egui_repaint_delay.min(formula::IDLE_UPDATE_INTERVAL))
+    }
+
+    /// Returns `true` if enough time has passed since the last
+    /// rendered frame to render a new one without going over the
+    /// `fps_cap` setting.
+    ///
+    /// NOTE: the game still updates at `formula::FPS` (the animation
+    /// timers count frames), the cap only skips the rendering.
+    pub fn render_due(&mut self) -> bool {
+        let now = Instant::now();
+        let due = match self.last_render_time {
+            Some(last_render_time) if self.settings.fps_cap < formula::FPS as u32 => {
+                let frame_duration = Duration::from_secs(1) / self.settings.fps_cap.max(1);
+                // NOTE: allow the same 1ms variance the backends use
+                // when scheduling the updates.
+                now.duration_since(last_render_time) + Duration::from_millis(1) >= frame_duration
+            }
+            _ => true,
+        };
+        if due {
+            self.last_render_time = Some(now);
+        }
+        due
+    }
+
+    /// Returns `true` if there's an unprocessed gamepad event. The
+    /// event is kept in the queue for the next update.
+    pub fn gamepad_event_pending(&mut self) -> bool {
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return false;
+        };
+        match gilrs.next_event() {
+            Some(event) => {
+                gilrs.insert_event(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn gamepad_connected(&self) -> bool {
+        self.gilrs
+            .as_ref()
+            .is_some_and(|gilrs| gilrs.gamepads().next().is_some())
+    }
+
+    /// Finish the egui pass and remember when egui wants to be
+    /// repainted next.
+    pub fn end_egui_pass(&mut self) -> egui::FullOutput {
+        let output = self.egui_context.end_pass();
+        self.egui_repaint_delay = output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map_or(Duration::ZERO, |viewport| viewport.repaint_delay);
+        output
+    }
+
     pub fn update_game(
         &mut self,
         dt: Duration,
         settings_store: &mut dyn SettingsStore,
     ) -> UpdateResult {
         use crate::game::RunningState;
+        let _span = trace::span("update");
         let tile_width_px = self.settings.tile_size;
         let text_width_px = self.settings.text_size;
 
-        let loop_state_dpi = self.dpi as f32;
-        if self.egui_context.pixels_per_point() != loop_state_dpi {
-            log::info!(
-                "Updating egui's dpi from: {} to {}",
-                self.egui_context.pixels_per_point(),
-                loop_state_dpi
-            );
-            self.egui_context.set_pixels_per_point(loop_state_dpi);
-        }
-
         self.game_state.keyboard_scroll_delta = [0.0, 0.0];
 
         if let Some(gilrs) = self.gilrs.as_mut() {
@@ -448,7 +607,15 @@ impl LoopState {
         let text_size = self.settings.text_size as f32;
         let mouse = self.game_state.mouse;
         let mouse_pos = [mouse.screen_pos.x as f32, mouse.screen_pos.y as f32].into();
-        let mut events = vec![Event::PointerMoved(mouse_pos)];
+        let mut events = vec![];
+
+        // NOTE: egui asks for an immediate repaint whenever it gets
+        // any input event. Only send the ones that actually change
+        // something so it can tell when we can wait for the player.
+        let previous_mouse_pos = self.egui_context.input(|i| i.pointer.latest_pos());
+        if previous_mouse_pos != Some(mouse_pos) {
+            events.push(Event::PointerMoved(mouse_pos));
+        }
 
         let scroll_delta = if mouse.scroll_delta == [0.0, 0.0] {
             self.game_state.keyboard_scroll_delta
@@ -459,11 +626,13 @@ impl LoopState {
             ]
         };
 
-        events.push(egui::Event::MouseWheel {
-            unit: egui::MouseWheelUnit::Point,
-            delta: scroll_delta.into(),
-            modifiers: egui::Modifiers::NONE,
-        });
+        if scroll_delta != [0.0, 0.0] {
+            events.push(egui::Event::MouseWheel {
+                unit: egui::MouseWheelUnit::Point,
+                delta: scroll_delta.into(),
+                modifiers: egui::Modifiers::NONE,
+            });
+        }
 
         if mouse.left_clicked {
             events.push(Event::PointerButton {
@@ -486,7 +655,7 @@ impl LoopState {
                 modifiers: Default::default(),
             });
         }
-        RawInput {
+        let mut raw_input = RawInput {
             screen_rect: Some(egui::Rect::from_min_size(
                 Default::default(),
                 [
@@ -496,12 +665,33 @@ impl LoopState {
                 .into(),
             )),
             events,
-
-            // TODO: I think we'll have to call `Context::set_zoom_factor` to handle DPI from now on
-            // todo go here
-            //pixels_per_point: self.dpi,
             ..Default::default()
-        }
+        };
+
+        // NOTE: egui renders its text for the physical pixels too.
+        // This keeps it in sync when the window moves between
+        // monitors with a different scaling.
+        raw_input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.dpi as f32);
+
+        raw_input
+    }
+
+    /// Build a new `Display` for the current tile and text size,
+    /// keeping the tileset and glyph atlas of the previous one.
+    fn recreate_display(&mut self, screen_size_px: Point) {
+        let tileset = std::mem::take(&mut self.display.tileset);
+        let glyph_atlas = std::mem::take(&mut self.display.glyph_atlas);
+        self.display = Display::new(
+            screen_size_px,
+            self.settings.tile_size,
+            self.settings.text_size,
+        );
+        self.display.tileset = tileset;
+        self.display.glyph_atlas = glyph_atlas;
     }
 
     /// The inputs are in LOGICAL pixels.
@@ -511,11 +701,7 @@ impl LoopState {
         if self.display.screen_size_px != new_window_size_px {
             self.settings.window_width = new_width as u32;
             self.settings.window_height = new_height as u32;
-            self.display = Display::new(
-                new_window_size_px,
-                self.settings.tile_size,
-                self.settings.text_size,
-            );
+            self.recreate_display(new_window_size_px);
         }
     }
 
@@ -528,11 +714,7 @@ impl LoopState {
             );
             self.settings.tile_size = new_tilesize_px;
             // Recreate the display, because the tile count is now different:
-            self.display = Display::new(
-                self.display.screen_size_px,
-                self.settings.tile_size,
-                self.settings.text_size,
-            );
+            self.recreate_display(self.display.screen_size_px);
         } else {
             log::warn!(
                 "Trying to switch to a tilesize that's not available: {}. Only these ones exist: {:?}",
@@ -550,11 +732,7 @@ impl LoopState {
                 new_text_size_px
             );
             self.settings.text_size = new_text_size_px;
-            self.display = Display::new(
-                self.display.screen_size_px,
-                self.settings.tile_size,
-                self.settings.text_size,
-            );
+            self.recreate_display(self.display.screen_size_px);
 
             // Update the current egui font size:
             let style = egui_style(&self.game_state.palette, self.settings.text_size as f32);
@@ -626,9 +804,16 @@ impl LoopState {
         }
     }
 
-    pub fn render(&self, gl: &OpenGlApp, dpi: f64, batches: &[([f32; 4], i32, i32)]) {
+    pub fn render(&mut self, gl: &mut OpenGlApp, dpi: f64, batches: &[([f32; 4], i32, i32)]) {
         let display_info = self.display_info(dpi);
-        gl.render(self.default_background, display_info, &self.vertex_buffer);
+        let grid_dirty_ranges = self.display.take_grid_dirty_ranges();
+        gl.render(
+            self.default_background,
+            display_info,
+            self.display.grid_vertices().vertices(),
+            &grid_dirty_ranges,
+            &self.vertex_buffer,
+        );
 
         for &(clip_rect, vertex_index, vertex_count) in batches {
             gl.render_clipped_vertices(display_info, clip_rect, (vertex_index, vertex_count));
@@ -642,17 +827,32 @@ impl LoopState {
         dpi: f64,
         extra_batches: &[([f32; 4], i32, i32)],
     ) {
+        let _span = trace::span("render");
+
+        if self.glyphmap_changed {
+            opengl_app.update_glyphmap(&self.glyphmap);
+            self.glyphmap_changed = false;
+        }
+
         // NOTE: Check if the Egui texture has changed and needs rebuilding
         // NOTE: the `ctx.texture()` call will panic if we hadn't
         // called `begin_frame`. But that absolutely should have
         // happened by now.
 
+        let display_info = self.display_info(dpi);
+        let display_px = display_info.display_px;
+
+        // NOTE: the grid vertices persist between frames and only the
+        // cells that changed get rebuilt. Everything else is rebuilt
+        // into `vertex_buffer` and drawn on top of the grid.
+        self.display
+            .update_grid_vertices(self.settings.visual_style, display_px);
+        let grid_vertex_count = self.display.grid_vertices().vertex_count() as i32;
+
         self.push_drawcalls_to_display();
 
         let current_vertex_buffer_capacity = self.vertex_buffer.capacity();
         self.vertex_buffer.clear();
-        let display_info = self.display_info(dpi);
-        let display_px = display_info.display_px;
         engine::build_vertices(&self.drawcalls, &mut self.vertex_buffer, display_px);
 
         let vertex_store: &mut dyn engine::VertexStore = &mut self.vertex_buffer;
@@ -665,7 +865,7 @@ impl LoopState {
         ];
 
         let mut batches = vec![];
-        let noclip_vertex_count = vertex_store.count() as i32;
+        let noclip_vertex_count = grid_vertex_count + vertex_store.count() as i32;
         batches.push((noclip_rect, 0, noclip_vertex_count));
         for &(clip, index, count) in extra_batches {
             batches.push((clip, index + noclip_vertex_count, count));
@@ -683,6 +883,12 @@ impl LoopState {
             );
         }
 
+        let vertex_count =
+            grid_vertex_count as usize + self.vertex_buffer.len() / engine::VERTEX_COMPONENT_COUNT;
+        self.game_state
+            .stats
+            .record_render(self.drawcalls.len(), vertex_count);
+
         self.render(opengl_app, dpi, &batches);
     }
 
@@ -701,6 +907,20 @@ impl LoopState {
         }
     }
 
+    /// The new vsync setting if it changed in the last update.
+    pub fn vsync_action(&self) -> Option<bool> {
+        if self.previous_settings.vsync == self.settings.vsync {
+            None
+        } else {
+            log::info!(
+                "[{}] Switching vsync to: {}",
+                self.current_frame_id,
+                self.settings.vsync
+            );
+            Some(self.settings.vsync)
+        }
+    }
+
     pub fn check_window_size_needs_updating(&mut self) -> ResizeWindowAction {
         if self.previous_settings.tile_size != self.settings.tile_size {
             self.change_tilesize_px(self.settings.tile_size);
@@ -715,3 +935,15 @@ impl LoopState {
         ResizeWindowAction::NoChange
     }
 }
+
+/// The glyph texture baked into the binary by `build.rs`.
+fn baked_glyphmap() -> RgbaImage {
+    let data = &include_bytes!(concat!(env!("OUT_DIR"), "/glyph.png"))[..];
+    image::load_from_memory_with_format(data, image::ImageFormat::Png)
+        .unwrap_or_else(|e| {
+            log::error!("Error loading the glyph tilemap image: {}", e);
+            log::warn!("Generating an empty 32x32 pixels image as a fallback.");
+            image::DynamicImage::new_rgba8(32, 32)
+        })
+        .to_rgba8()
+}
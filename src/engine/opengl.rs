@@ -21,6 +21,8 @@ pub struct OpenGlApp {
     pub fragment_shader: GLuint,
     pub vao: GLuint,
     pub vbo: GLuint,
+    /// Size of the `vbo` GPU buffer in `f32` components.
+    pub vbo_capacity: usize,
     pub glyphmap: GLuint,
     pub glyphmap_size_px: [f32; 2],
     pub tilemap: GLuint,
@@ -88,6 +90,13 @@ impl OpenGlApp {
         self.upload_texture(self.tilemap, "tilemap", tilemap);
     }
 
+    /// Replace the glyph texture, e.g. with one rasterised for a
+    /// different DPI.
+    pub fn update_glyphmap(&mut self, glyphmap: &RgbaImage) {
+        self.glyphmap_size_px = [glyphmap.width() as f32, glyphmap.height() as f32];
+        self.upload_texture(self.glyphmap, "glyphmap", glyphmap);
+    }
+
     pub fn upload_texture(&mut self, id: GLuint, name: &str, texture: &RgbaImage) {
         let (width, height) = texture.dimensions();
         // NOTE(shadower): as far as I can tell (though the opengl
@@ -202,9 +211,23 @@ impl OpenGlApp {
         }
     }
 
-    pub fn render(&self, clear_color: Color, display_info: DisplayInfo, vertex_buffer: &[f32]) {
+    /// Upload the vertices and set up the rendering state.
+    ///
+    /// The GPU buffer holds the `grid_vertices` followed by the
+    /// `vertex_buffer`. It persists between frames and only the
+    /// `grid_dirty_ranges` (`(index, count)` in vertices) of the grid
+    /// are uploaded. The `vertex_buffer` is uploaded every time.
+    pub fn render(
+        &mut self,
+        clear_color: Color,
+        display_info: DisplayInfo,
+        grid_vertices: &[f32],
+        grid_dirty_ranges: &[(usize, usize)],
+        vertex_buffer: &[f32],
+    ) {
         let program = self.program;
         let vbo = self.vbo;
+        let float_size = mem::size_of::<GLfloat>();
         // SAFETY:
         // TODO
         unsafe {
@@ -225,17 +248,62 @@ impl OpenGlApp {
                 display_info.viewport_size[1] as i32,
             );
             check_gl_error("Viewport");
-            // Copy data to the vertex buffer
             gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
             check_gl_error("BindBuffer");
-            // TODO: look at BufferSubData here -- that should reuse the allocation
-            gl::BufferData(
+
+            // NOTE: (re)allocate the buffer only when it's too small.
+            // The old contents are gone after that so the whole grid
+            // must be uploaded again.
+            let component_count = grid_vertices.len() + vertex_buffer.len();
+            let mut upload_whole_grid = false;
+            if component_count > self.vbo_capacity {
+                self.vbo_capacity = component_count.max(crate::engine::VERTEX_BUFFER_CAPACITY);
+                log::info!(
+                    "Allocating a vertex buffer of {} components.",
+                    self.vbo_capacity
+                );
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (self.vbo_capacity * float_size) as GLsizeiptr,
+                    ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+                check_gl_error("BufferData");
+                upload_whole_grid = true;
+            }
+
+            let component_ranges: Vec<(usize, usize)> = if upload_whole_grid {
+                vec![(0, grid_vertices.len())]
+            } else {
+                grid_dirty_ranges
+                    .iter()
+                    .map(|&(index, count)| {
+                        (
+                            index * crate::engine::VERTEX_COMPONENT_COUNT,
+                            count * crate::engine::VERTEX_COMPONENT_COUNT,
+                        )
+                    })
+                    .collect()
+            };
+            for (start, len) in component_ranges {
+                if let Some(components) = grid_vertices.get(start..start + len) {
+                    gl::BufferSubData(
+                        gl::ARRAY_BUFFER,
+                        (start * float_size) as GLintptr,
+                        (len * float_size) as GLsizeiptr,
+                        components.as_ptr() as *const os::raw::c_void,
+                    );
+                    check_gl_error("BufferSubData grid");
+                }
+            }
+
+            gl::BufferSubData(
                 gl::ARRAY_BUFFER,
-                (vertex_buffer.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                (grid_vertices.len() * float_size) as GLintptr,
+                (vertex_buffer.len() * float_size) as GLsizeiptr,
                 vertex_buffer.as_ptr() as *const os::raw::c_void,
-                gl::DYNAMIC_DRAW,
             );
-            check_gl_error("BufferData");
+            check_gl_error("BufferSubData");
 
             let rgba: ColorAlpha = clear_color.into();
             let glcolor: [f32; 4] = rgba.into();
@@ -1,4 +1,5 @@
 use crate::{
+    assets::AssetPack,
     color::Color,
     engine::{
         self,
@@ -19,17 +20,20 @@ use egui::{
 };
 
 use sdl2::{
-    EventPump,
+    EventPump, VideoSubsystem,
     event::{Event, WindowEvent},
     keyboard::{self, Keycode as BackendKey},
-    video::Window,
+    video::{SwapInterval, Window},
 };
 
 fn key_code_from_backend(backend_code: BackendKey) -> Option<KeyCode> {
     match backend_code {
         BackendKey::Return => Some(KeyCode::Enter),
         BackendKey::Escape => Some(KeyCode::Esc),
+        BackendKey::Backspace => Some(KeyCode::Backspace),
         BackendKey::Space => Some(KeyCode::Space),
+        BackendKey::Plus | BackendKey::Equals | BackendKey::KpPlus => Some(KeyCode::Plus),
+        BackendKey::Minus | BackendKey::KpMinus => Some(KeyCode::Minus),
 
         BackendKey::Num0 => Some(KeyCode::D0),
         BackendKey::Num1 => Some(KeyCode::D1),
@@ -102,10 +106,13 @@ fn key_code_from_backend(backend_code: BackendKey) -> Option<KeyCode> {
     }
 }
 
+/// How often to check for the gamepad events while waiting for the
+/// player's input.
+const GAMEPAD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 struct Game<S> {
     loop_state: LoopState,
     event_pump: EventPump,
-    dpi: f64,
     window: Window,
     opengl_app: OpenGlApp,
     egui_shapes: Vec<ClippedShape>,
@@ -114,10 +121,14 @@ struct Game<S> {
 }
 
 impl<S: SettingsStore> Game<S> {
-    fn update_and_render(&mut self, dt: Duration) -> bool {
+    /// Process the input, update the game and render it.
+    ///
+    /// `waking_event` is the event the main loop got while it was
+    /// waiting for the player's input (if any).
+    fn update_and_render(&mut self, dt: Duration, waking_event: Option<Event>) -> bool {
         self.loop_state.update_fps(dt);
 
-        for event in self.event_pump.poll_iter() {
+        for event in waking_event.into_iter().chain(self.event_pump.poll_iter()) {
             log::trace!("{:?}", event);
             match event {
                 Event::Quit { .. } => {
@@ -132,6 +143,15 @@ impl<S: SettingsStore> Game<S> {
                     self.loop_state.handle_window_size_changed(width, height);
                 }
 
+                // NOTE: the window moved to a monitor with a different
+                // scaling or the scaling itself changed.
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::DisplayChanged(..),
+                    ..
+                } => {
+                    self.loop_state.set_dpi(window_dpi(&self.window));
+                }
+
                 Event::KeyDown {
                     keycode: Some(backend_code),
                     scancode,
@@ -173,7 +193,8 @@ impl<S: SettingsStore> Game<S> {
                 }
 
                 Event::MouseMotion { x, y, .. } => {
-                    self.loop_state.update_mouse_position(self.dpi, x, y);
+                    self.loop_state
+                        .update_mouse_position(self.loop_state.dpi, x, y);
                 }
 
                 Event::MouseButtonDown { mouse_btn, .. } => {
@@ -251,7 +272,11 @@ impl<S: SettingsStore> Game<S> {
             }
         }
 
-        let output = self.loop_state.egui_context.end_pass();
+        if let Some(vsync) = self.loop_state.vsync_action() {
+            set_vsync(self.window.subsystem(), vsync);
+        }
+
+        let output = self.loop_state.end_egui_pass();
 
         for command in &output.platform_output.commands {
             if let egui::OutputCommand::OpenUrl(url) = command
@@ -328,6 +353,10 @@ impl<S: SettingsStore> Game<S> {
             ResizeWindowAction::NoChange => {}
         }
 
+        if !self.loop_state.render_due() {
+            return true;
+        }
+
         self.ui_paint_batches = self
             .loop_state
             .egui_context
@@ -349,11 +378,37 @@ impl<S: SettingsStore> Game<S> {
     }
 }
 
+/// The number of physical pixels per logical one.
+///
+/// SDL reports the window size (and mouse position) in logical pixels
+/// but we render into the drawable which is in physical ones.
+fn window_dpi(window: &Window) -> f64 {
+    let (width, _height) = window.size();
+    let (drawable_width, _drawable_height) = window.drawable_size();
+    if width == 0 {
+        1.0
+    } else {
+        f64::from(drawable_width) / f64::from(width)
+    }
+}
+
+fn set_vsync(video_subsystem: &VideoSubsystem, vsync: bool) {
+    let interval = if vsync {
+        SwapInterval::VSync
+    } else {
+        SwapInterval::Immediate
+    };
+    if let Err(err) = video_subsystem.gl_set_swap_interval(interval) {
+        log::warn!("Could not set the swap interval to {:?}: {}", interval, err);
+    }
+}
+
 pub fn main_loop<S>(
     initial_default_background: Color,
     window_title: &str,
     settings_store: S,
     initial_state: Box<State>,
+    assets: AssetPack,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
     S: SettingsStore + 'static,
@@ -362,13 +417,19 @@ where
     let egui_context = Context::default();
 
     log::info!("Initialising the game state.");
-    let loop_state = LoopState::initialise(
+    let mut loop_state = LoopState::initialise(
         settings_store.load(),
         initial_default_background,
         initial_state,
         egui_context,
+        &assets,
     );
 
+    // NOTE: without this, Windows stretches the whole window on a
+    // scaled display. With it, SDL reports the window size in logical
+    // pixels and the drawable size in physical ones (see `window_dpi`).
+    sdl2::hint::set("SDL_WINDOWS_DPI_SCALING", "1");
+
     log::info!("Initialising SDL2.");
     let sdl_context = sdl2::init()?;
     log::info!("Initialising the SDL2 video subsystem.");
@@ -390,6 +451,7 @@ where
         )
         .resizable()
         .opengl()
+        .allow_highdpi()
         .position_centered()
         .build()?;
 
@@ -461,17 +523,16 @@ where
         }
     }
 
-    // NOTE: SDL2 doesn't seem to handle display DPI. I believe it
-    // scales things under-the-hood (at least that's what happens on
-    // my macos machine).
-    let dpi = 1.0;
+    loop_state.set_dpi(window_dpi(&window));
 
-    // NOTE: We don't need to force vsync. I think SDL2 (at least with its
-    // OpenGL backend) doesn't let you -- it's there only for its canvas.
+    // NOTE: the fixed-time loop below paces the updates on its own.
+    // Vsync only makes the buffer swap wait for the monitor refresh.
     //
-    // But also I think we don't need to rely on it thanks to our
-    // fixed-time loop which will enforce that we do largely sleep
-    // between refresh and game update intervals.
+    // With vsync on, we keep whatever swap interval SDL picked as we
+    // always did. It's only forced when the player turns vsync off.
+    if !loop_state.settings.vsync {
+        set_vsync(&video_subsystem, false);
+    }
 
     log::info!("Creating the SDL event pump.");
     let event_pump = sdl_context.event_pump()?;
@@ -479,7 +540,6 @@ where
     let mut game = Game {
         loop_state,
         event_pump,
-        dpi,
         window,
         opengl_app,
         egui_shapes: vec![],
@@ -499,13 +559,38 @@ where
     let start_time = Instant::now();
 
     let mut tick = 0;
+    let mut next_update_time = start_time;
     let mut current_time = start_time;
     let mut running = true;
 
     log::info!("Starting the game loop.");
     while running {
-        let elapsed_time = Instant::now().duration_since(start_time);
-        let update_ready = (elapsed_time + inc) >= (tick * target_dt);
+        let idle_timeout = game.loop_state.idle_timeout();
+        let (update_ready, waking_event) = match idle_timeout {
+            Some(idle_timeout) => {
+                // NOTE: nothing's happening in the game. Sleep until
+                // the player does something or the timeout runs out.
+                //
+                // SDL doesn't know about the gamepad events (we use
+                // gilrs for that) so we need to wake up and check
+                // them every now and then.
+                let remaining =
+                    idle_timeout.saturating_sub(Instant::now().duration_since(current_time));
+                let wait = if game.loop_state.gamepad_connected() {
+                    remaining.min(GAMEPAD_POLL_INTERVAL)
+                } else {
+                    remaining
+                };
+                let event = game
+                    .event_pump
+                    .wait_event_timeout(wait.as_millis().max(1) as u32);
+                let timed_out = Instant::now().duration_since(current_time) >= idle_timeout;
+                let update_ready =
+                    event.is_some() || timed_out || game.loop_state.gamepad_event_pending();
+                (update_ready, event)
+            }
+            None => ((Instant::now() + inc) >= next_update_time, None),
+        };
 
         if update_ready {
             let now = Instant::now();
@@ -513,7 +598,15 @@ where
             current_time = now;
             tick += 1;
 
-            running = game.update_and_render(dt);
+            // NOTE: if we've fallen behind (e.g. we've just woken up
+            // from the idle wait), don't try to catch up on all the
+            // frames we've missed. Just start counting from now.
+            next_update_time += target_dt;
+            if next_update_time < now {
+                next_update_time = now;
+            }
+
+            running = game.update_and_render(dt, waking_event);
 
             let frame_dt = Instant::now().duration_since(current_time);
 
@@ -534,12 +627,8 @@ where
                 Instant::now().duration_since(current_time)
             );
             // Expectation: dt ~ target_dt
-            log::trace!(
-                "Expected time based on fixed_dt: {:?}, actual elapsed time: {:?}",
-                target_dt * tick,
-                Instant::now().duration_since(start_time)
-            );
-        } else {
+            log::trace!("Frame: {}, dt: {:?}", tick, dt);
+        } else if idle_timeout.is_none() {
             // Catch up to the next scheduled game update (based on
             // `target_dt`) one increment at a time:
             std::thread::sleep(inc);
@@ -549,10 +638,9 @@ where
             // how long to sleep until the next frame starts.
         }
     }
-    // Expectation: dt ~ target_dt
     log::info!(
-        "Expected time based on fixed_dt: {:?}, actual elapsed time: {:?}",
-        target_dt * tick,
+        "Updated {} frames in: {:?}",
+        tick,
         Instant::now().duration_since(start_time)
     );
 
@@ -0,0 +1,237 @@
+use crate::{player::Modifier, point::Point};
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::{self, Debug, Formatter},
+    io::Write,
+    rc::Rc,
+};
+
+/// How many entries we keep around for the sidebar. The output (if
+/// any) gets everything.
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub turn: i32,
+    pub text: String,
+}
+
+/// A textual description of what happened in the game.
+///
+/// The map is purely visual so this is the only way for players who
+/// can't see it (or can't see it well) to know what's going on. The
+/// entries are shown in the sidebar and can be written out (e.g. to
+/// stdout) for a text-to-speech tool to read them.
+#[derive(Default)]
+pub struct EventLog {
+    entries: VecDeque<Entry>,
+    turn: i32,
+    // NOTE: this is shared between all the games in a single session
+    // so that starting a new game or loading one keeps writing into
+    // the same place.
+    output: Option<Rc<RefCell<dyn Write>>>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write every new entry into `output` as well.
+    pub fn with_output<W: Write + 'static>(output: W) -> Self {
+        Self {
+            output: Some(Rc::new(RefCell::new(output))),
+            ..Self::default()
+        }
+    }
+
+    /// An empty log writing to the same output as this one.
+    pub fn for_new_game(&self) -> Self {
+        Self {
+            output: self.output.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the turn the subsequent entries belong to.
+    pub fn set_turn(&mut self, turn: i32) {
+        self.turn = turn;
+    }
+
+    pub fn push<S: Into<String>>(&mut self, text: S) {
+        let entry = Entry {
+            turn: self.turn,
+            text: text.into(),
+        };
+        log::debug!("Event log: {}", entry.text);
+
+        if let Some(output) = &self.output {
+            let result = match output.try_borrow_mut() {
+                Ok(mut output) => writeln!(output, "[{}] {}", entry.turn, entry.text)
+                    .and_then(|()| output.flush()),
+                Err(err) => {
+                    log::error!("The event log output is already in use: {}", err);
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                log::error!("Could not write to the event log output: {}", err);
+            }
+        }
+
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Debug for EventLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventLog")
+            .field("entries", &self.entries)
+            .field("turn", &self.turn)
+            .field("output", &self.output.is_some())
+            .finish()
+    }
+}
+
+/// The compass direction from `from` towards `to`, e.g. `north-east`.
+///
+/// Returns `here` if the points are identical.
+pub fn direction(from: Point, to: Point) -> &'static str {
+    let dx = to.x - from.x;
+    let dy = to.y - from.y;
+    // NOTE: only use the diagonal direction if the point is roughly
+    // diagonal. Something 5 tiles east and 1 north is "east".
+    let horizontal = if dy.abs() >= 2 * dx.abs() {
+        0
+    } else {
+        dx.signum()
+    };
+    let vertical = if dx.abs() >= 2 * dy.abs() {
+        0
+    } else {
+        dy.signum()
+    };
+    match (horizontal, vertical) {
+        (0, -1) => "north",
+        (1, -1) => "north-east",
+        (1, 0) => "east",
+        (1, 1) => "south-east",
+        (0, 1) => "south",
+        (-1, 1) => "south-west",
+        (-1, 0) => "west",
+        (-1, -1) => "north-west",
+        _ => "here",
+    }
+}
+
+/// Describe where `to` is relative to `from`, e.g. `3 tiles north-east`.
+pub fn relative_position(from: Point, to: Point) -> String {
+    let distance = from.tile_distance(to);
+    if distance == 0 {
+        return "here".to_string();
+    }
+    let tiles = if distance == 1 { "tile" } else { "tiles" };
+    format!("{} {} {}", distance, tiles, direction(from, to))
+}
+
+/// Describe what the `effect` does to the player, e.g. `Will -1`.
+pub fn describe_effect(effect: Modifier) -> String {
+    use Modifier::*;
+    match effect {
+        Death => "you lose".into(),
+        Attribute {
+            will,
+            state_of_mind,
+        } => {
+            let mut changes = vec![];
+            if will != 0 {
+                changes.push(format!("Will {will:+}"));
+            }
+            if state_of_mind != 0 {
+                changes.push(format!("Mind {state_of_mind:+}"));
+            }
+            if changes.is_empty() {
+                "no effect".into()
+            } else {
+                changes.join(", ")
+            }
+        }
+        Intoxication { state_of_mind, .. } => format!("Mind {state_of_mind:+}"),
+        Panic(turns) => format!("panic for {turns} turns"),
+        Stun(turns) => format!("stunned for {turns} turns"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_direction() {
+        let origin = Point::new(0, 0);
+        assert_eq!(direction(origin, origin), "here");
+        assert_eq!(direction(origin, Point::new(0, -3)), "north");
+        assert_eq!(direction(origin, Point::new(3, -3)), "north-east");
+        assert_eq!(direction(origin, Point::new(3, -2)), "north-east");
+        assert_eq!(direction(origin, Point::new(5, -1)), "east");
+        assert_eq!(direction(origin, Point::new(-1, 4)), "south");
+        assert_eq!(direction(origin, Point::new(-2, 2)), "south-west");
+        assert_eq!(direction(origin, Point::new(-7, 0)), "west");
+    }
+
+    #[test]
+    fn test_relative_position() {
+        let origin = Point::new(0, 0);
+        assert_eq!(relative_position(origin, origin), "here");
+        assert_eq!(relative_position(origin, Point::new(1, 0)), "1 tile east");
+        assert_eq!(
+            relative_position(origin, Point::new(3, -3)),
+            "3 tiles north-east"
+        );
+    }
+
+    #[test]
+    fn test_describe_effect() {
+        assert_eq!(
+            describe_effect(Modifier::Attribute {
+                will: -1,
+                state_of_mind: 0
+            }),
+            "Will -1"
+        );
+        assert_eq!(
+            describe_effect(Modifier::Attribute {
+                will: 0,
+                state_of_mind: -20
+            }),
+            "Mind -20"
+        );
+        assert_eq!(describe_effect(Modifier::Stun(3)), "stunned for 3 turns");
+    }
+
+    #[test]
+    fn test_entries_are_capped() {
+        let mut log = EventLog::new();
+        for index in 0..(MAX_ENTRIES + 10) {
+            log.push(format!("entry {index}"));
+        }
+        assert_eq!(log.entries().count(), MAX_ENTRIES);
+        assert_eq!(
+            log.entries().next().map(|entry| entry.text.as_str()),
+            Some("entry 10")
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    ai::AIState,
+    event_log, formula,
+    monster::{Kind, Monster},
+    player::{Bonus, Modifier},
+    point::Point,
+    rect::Rectangle,
+    state::State,
+    windows::help,
+};
+
+/// Describe what the player can see at the `pos` tile. One entry per line.
+///
+/// This follows the same visibility rules as the renderer so it never
+/// reveals anything that's not on the screen.
+pub fn describe(state: &State, pos: Point) -> Vec<String> {
+    let player = &state.player;
+    let radius = formula::exploration_radius(player.mind);
+    let in_fov = player.pos.distance(pos) < (radius as f32);
+    let uncovered_map = state.map_uncovered();
+
+    let Some(cell) = state.world.cell(pos) else {
+        return vec!["Nothing".to_string()];
+    };
+    let cell_visible = in_fov || cell.always_visible || state.uncovered_map;
+    if !(cell_visible || cell.explored || uncovered_map) {
+        return vec!["Unexplored".to_string()];
+    }
+
+    let mut lines = vec![];
+
+    if let Some(annotation) = state.annotation(pos) {
+        lines.push(format!("Marked: {}", annotation.name));
+    }
+    if state.waypoint == Some(pos) {
+        lines.push("Your waypoint".to_string());
+    }
+
+    if pos == player.pos {
+        lines.push(format!(
+            "You: {}, Will {}",
+            player.mind,
+            player.will.to_int()
+        ));
+    }
+
+    let monster = state
+        .world
+        .monsters(Rectangle::center(pos, Point::zero()))
+        .find(|monster| monster.position == pos);
+    if let Some(monster) = monster {
+        let monster_visible = in_fov
+            || monster.accompanying_player
+            || cell.always_visible
+            || uncovered_map
+            || player.bonus == Bonus::SeeMonstersAndItems;
+        if monster_visible {
+            describe_monster(monster, &mut lines);
+        }
+    }
+
+    let items_visible = in_fov
+        || cell.explored
+        || cell.always_visible
+        || player.bonus == Bonus::SeeMonstersAndItems
+        || uncovered_map;
+    if items_visible {
+        for item in &cell.items {
+            lines.push(format!(
+                "{}: {}",
+                item.kind.name(),
+                help::item_legend(item.kind)
+            ));
+            match item.modifier {
+                Modifier::Intoxication { state_of_mind, .. } => {
+                    lines.push(format!("Strength: {state_of_mind}"));
+                    let resist_radius =
+                        formula::player_resist_radius(item.irresistible, player.will.to_int());
+                    if resist_radius > 0 {
+                        lines.push(format!("Irresistible within {resist_radius} tiles"));
+                    } else {
+                        lines.push("You can resist it".to_string());
+                    }
+                }
+                modifier => lines.push(format!("Effect: {}", event_log::describe_effect(modifier))),
+            }
+        }
+    }
+
+    let (tile_name, tile_description) = help::tile_legend(cell.tile.kind);
+    lines.push(format!("{tile_name}: {tile_description}"));
+
+    lines
+}
+
+fn describe_monster(monster: &Monster, lines: &mut Vec<String>) {
+    let (name, description) = help::monster_legend(monster.kind);
+    lines.push(format!("{name}: {description}"));
+
+    match monster.kind {
+        Kind::Npc => {
+            if let Some(bonus) = monster.companion_bonus {
+                lines.push(format!("Bonus: {bonus}"));
+            }
+            if monster.accompanying_player {
+                lines.push("Accompanying you".to_string());
+            }
+        }
+        Kind::Signpost => {}
+        _ => {
+            let ai_state = match monster.ai_state {
+                AIState::Idle => Some("Idle"),
+                AIState::Chasing => Some("Chasing you"),
+                AIState::CheckingOut(_) => Some("Checking something out"),
+                AIState::NoOp => None,
+            };
+            if let Some(ai_state) = ai_state {
+                lines.push(format!("State: {ai_state}"));
+            }
+            lines.push(format!(
+                "Attack: {}",
+                event_log::describe_effect(monster.attack_damage())
+            ));
+        }
+    }
+}
@@ -16,6 +16,11 @@ use num_rational::{Ratio, Rational32};
 
 pub const FPS: f32 = 60.0;
 
+/// How often the game updates when it's waiting for the player's
+/// input in the power-saving mode. The ambient animations (e.g. the
+/// pulsing doses) are slow enough to look fine at this rate.
+pub const IDLE_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
 const WIDE_SIDEBAR_WIDTH_PX: i32 = 375;
 const NARROW_SIDEBAR_WIDTH_PX: i32 = 300;
 
@@ -132,6 +137,9 @@ pub const CHASING_DISTANCE: i32 = 5;
 pub const HOWLING_DISTANCE: i32 = 15;
 
 pub const ESTRANGED_NPC_MAX_AP: i32 = 2;
+/// How close a high player can get before the NPCs start keeping
+/// away from them.
+pub const ESTRANGED_NPC_AVOID_RADIUS: f32 = 5.0;
 
 pub const FRIENDLY_NPC_FREEZE_RADIUS: f32 = 3.0;
 
@@ -139,9 +147,16 @@ pub const PATHFINDING_MONSTER_LIMIT: i32 = 50;
 pub const PATHFINDING_PLAYER_MOUSE_LIMIT: i32 = 50;
 pub const PATHFINDING_DOSE_RESIST_LIMIT: i32 = 50;
 pub const PATHFINDING_VNPC_REACHABILITY_LIMIT: i32 = 500;
+/// The `pathfinding::ChunkGraph` limits. They count the chunk
+/// entrances rather than tiles.
+pub const PATHFINDING_PLAYER_CHUNK_LIMIT: i32 = 500;
+pub const PATHFINDING_VNPC_CHUNK_LIMIT: i32 = 500;
 
 pub const PLAYER_PATH_WALKING_DELAY: Duration = Duration::from_millis(300);
 
+/// How many turns the player can take back in the practice mode.
+pub const UNDO_TURNS: usize = 20;
+
 pub const ANIMATION_ATTACK_DURATION: Duration = Duration::from_millis(75);
 pub const ANIMATION_MOVE_DURATION: Duration = Duration::from_millis(100);
 
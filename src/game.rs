@@ -1,42 +1,38 @@
 use crate::{
-    ai,
-    animation::{self, AreaOfEffect},
+    achievements::{self, Achievement},
+    animation,
     audio::{Audio, Effect},
     blocker::Blocker,
-    color,
+    color, daily,
     engine::{Display, Mouse, TextMetrics},
-    formula,
+    event_log, examine, formula,
     gamepad::Gamepad,
-    graphic::Graphic,
-    item,
+    history, item,
     keys::{Key, KeyCode, Keys},
-    level::TileKind,
     monster::{self, CompanionBonus},
-    palette::Palette,
-    pathfinding,
-    player::{self, Modifier},
-    point::{self, Point},
+    pathfinding::{Path, PlayerCost},
+    player::{self, CauseOfDeath},
+    point::Point,
     random::Random,
-    ranged_int::{InclusiveRange, Ranged},
     rect::Rectangle,
     render,
     settings::{Settings, Store as SettingsStore},
+    simulation::{self, GameEvent},
     state::{
-        self, Challenge, Command, GameSession, Input, MotionAnimation, Side, State,
+        self, Challenge, Command, Divergence, GameSession, Input, OnDivergence, Side, State,
         VerificationWrapper,
     },
     stats::{FrameStats, Stats},
     timer::{Stopwatch, Timer},
     ui, util,
     window::{self, Window},
-    windows::{endgame, help, main_menu, message, settings, sidebar},
-    world::World,
+    windows::{
+        annotate, endgame, help, main_menu, map, message, performance, report, settings, sidebar,
+        statistics,
+    },
 };
 
-use std::{
-    collections::{HashMap, VecDeque},
-    time::Duration,
-};
+use std::{collections::VecDeque, time::Duration};
 
 use egui::{Context, Ui};
 
@@ -138,6 +134,17 @@ pub fn update(
     if gamepad.west {
         new_keys.push(KeyCode::I.into());
     }
+    // NOTE: other windows would take the examine key as a regular
+    // letter (e.g. when typing an annotation).
+    if gamepad.north && state.window_stack.top() == Window::Game {
+        new_keys.push(KeyCode::L.into());
+    }
+    if gamepad.left_trigger {
+        new_keys.push(KeyCode::Minus.into());
+    }
+    if gamepad.right_trigger {
+        new_keys.push(KeyCode::Plus.into());
+    }
 
     // NOTE: This does add diagonal movement when pressing both D-pad buttons at once.
     //
@@ -174,6 +181,10 @@ pub fn update(
         new_keys.push(KeyCode::Right.into());
     } else if gamepad.start {
         new_keys.push(KeyCode::Menu.into());
+    } else if gamepad.select && matches!(state.window_stack.top(), Window::Game | Window::Map) {
+        // NOTE: M opens and closes the map. The settings would take
+        // it as muting the music.
+        new_keys.push(KeyCode::M.into());
     }
 
     gamepad.reset_buttons();
@@ -198,14 +209,25 @@ pub fn update(
         None
     };
 
-    let input = {
+    let mut input = {
         let mut i = Input {
             keys: new_keys.to_vec(),
             mouse,
             tick_id: state.tick_id,
             verification: VerificationWrapper::None,
+            waypoint: None,
+            undo: false,
         };
 
+        // NOTE: the map window doesn't log any input so the waypoint
+        // picked there is recorded by the game window's next tick.
+        // The same goes for the undo as the sidebar buttons aren't
+        // logged either.
+        if state.window_stack.top() == Window::Game {
+            i.waypoint = state.pending_waypoint.take();
+            i.undo = std::mem::take(&mut state.pending_undo);
+        }
+
         if cfg!(feature = "verifications") {
             // NOTE: we're not logging a verification every frame!
             if state.debug {
@@ -218,39 +240,89 @@ pub fn update(
         i
     };
 
-    if state.window_stack.top() == Window::Game && state.player.alive() {
-        state::log_input(&mut state.input_logger, input);
-        log::trace!(
-            "[TICK {}] state.player.pos: {}",
-            state.tick_id,
-            state.player.pos
-        );
-    }
-
     if state.replay && state.player.alive() && !state.paused {
         let replay_input_index = state.tick_id as usize - 1;
         assert_eq!(state.tick_id, state.previous_tick + 1);
-        if let Some(input) = state.inputs.get(replay_input_index) {
-            assert_eq!(state.tick_id, input.tick_id);
+        if let Some(input_to_replay) = state.inputs.get(replay_input_index) {
+            assert_eq!(state.tick_id, input_to_replay.tick_id);
 
-            match &input.verification {
+            let differences = match &input_to_replay.verification {
                 VerificationWrapper::Verification(expected) => {
-                    let actual = state.verification();
-                    verify_states(expected, &actual);
+                    expected.differences(&state.verification())
                 }
                 VerificationWrapper::Hash(replay_hash_bytes) => {
                     let replay_hash = blake3::Hash::from_bytes(*replay_hash_bytes);
                     let actual_hash = state.verification().hash();
-                    assert_eq!(replay_hash, actual_hash);
+                    if replay_hash == actual_hash {
+                        vec![]
+                    } else {
+                        // NOTE: only the replays recorded with `--debug`
+                        // store the full `Verification` so this is all
+                        // we can tell.
+                        vec![format!(
+                            "Expected state hash: {replay_hash}, actual: {actual_hash}"
+                        )]
+                    }
                 }
 
                 // NOTE: by definition, the lack of a verification is
                 // treated as an automatic pass:
-                VerificationWrapper::None => (),
+                VerificationWrapper::None => vec![],
+            };
+
+            if !differences.is_empty() {
+                match state.on_divergence {
+                    OnDivergence::Panic => {
+                        for difference in &differences {
+                            log::error!("{}", difference);
+                        }
+                        // Use if+panic rather than `assert_eq`. The latter prints out both objects and they can be MASSIVE.
+                        // We've got a more targeted expected/actual printout above so all we need here is to crash.
+                        #[allow(clippy::panic)]
+                        {
+                            panic!("Validation failed!");
+                        }
+                    }
+                    OnDivergence::Stop => {
+                        state.divergence = Some(Divergence {
+                            tick_id: state.tick_id,
+                            turn: state.turn,
+                            differences,
+                        });
+                        return RunningState::Stopped;
+                    }
+                    OnDivergence::Continue => {
+                        for difference in &differences {
+                            log::warn!("[TICK {}] {}", state.tick_id, difference);
+                        }
+                        if state.divergence.is_none() {
+                            state.divergence = Some(Divergence {
+                                tick_id: state.tick_id,
+                                turn: state.turn,
+                                differences,
+                            });
+                        }
+                    }
+                }
             }
 
-            state.keys.extend(input.keys.iter().copied());
-            state.mouse = input.mouse;
+            // NOTE: when re-recording a replay, this writes the
+            // recorded input along with the verification of the
+            // current rules.
+            input.keys.clone_from(&input_to_replay.keys);
+            input.mouse = input_to_replay.mouse;
+            input.waypoint = input_to_replay.waypoint;
+            input.undo = input_to_replay.undo;
+
+            state.keys.extend(input_to_replay.keys.iter().copied());
+            state.mouse = input_to_replay.mouse;
+        } else if state.on_divergence == OnDivergence::Continue {
+            // NOTE: a diverged game can outlive its recording.
+            log::warn!(
+                "The replay ran out of inputs at tick {}, stopping.",
+                state.tick_id
+            );
+            return RunningState::Stopped;
         } else {
             #[allow(clippy::panic)]
             {
@@ -271,6 +343,22 @@ pub fn update(
         state.mouse = mouse;
     }
 
+    if let Some(waypoint) = input.waypoint {
+        state.set_waypoint(waypoint);
+    }
+    if input.undo && state.undo() {
+        state.event_log.push("You went back a turn.");
+    }
+
+    if state.window_stack.top() == Window::Game && state.player.alive() {
+        state::log_input(&mut state.input_logger, input);
+        log::trace!(
+            "[TICK {}] state.player.pos: {}",
+            state.tick_id,
+            state.player.pos
+        );
+    }
+
     // Quit the game when Q is pressed or on replay and requested
     if ((state.side == Side::Victory || !state.player.alive()) && state.exit_after)
         || (state.replay
@@ -279,6 +367,7 @@ pub fn update(
                 || (!state.player.alive() && state.screen_fading.is_none())))
     {
         show_exit_stats(&state.stats);
+        performance::save_trace_recording();
         return RunningState::Stopped;
     }
 
@@ -301,6 +390,7 @@ pub fn update(
         && state.extra_animations.is_empty()
         && state.pos_timer.finished()
         && state.keys.is_empty()
+        && !(state.auto_walking && state.waypoint.is_some())
         && victory_npc_accompanies_player.is_none()
     {
         return RunningState::Skip;
@@ -312,6 +402,37 @@ pub fn update(
         settings.fullscreen = !settings.fullscreen;
     }
 
+    // Show the frame timings on F3 and record a trace on F4
+    if cfg!(feature = "stats") {
+        if state.keys.matches_code(KeyCode::F3) {
+            state.show_performance_overlay = !state.show_performance_overlay;
+        }
+        if state.show_performance_overlay && state.keys.matches_code(KeyCode::F4) {
+            performance::toggle_trace_recording();
+        }
+    }
+
+    // Zoom the game view in or out. The engine recreates the display
+    // with the new tile size and the map area will cover more or
+    // fewer tiles.
+    //
+    // NOTE: replays recorded by the older rules may have scrolled the
+    // wheel without zooming and their mouse positions assume the
+    // tile size they were recorded with.
+    if state.window_stack.top() == Window::Game && state.rules_version >= 4 {
+        let mouse_inside_map = state.mouse.tile_pos.x < state.map_size.x;
+        let scroll = if mouse_inside_map {
+            state.mouse.scroll_delta[1]
+        } else {
+            0.0
+        };
+        if state.keys.matches_code(KeyCode::Plus) || scroll > 0.0 {
+            settings.tile_size = zoomed_tile_size(settings.tile_size, true);
+        } else if state.keys.matches_code(KeyCode::Minus) || scroll < 0.0 {
+            settings.tile_size = zoomed_tile_size(settings.tile_size, false);
+        }
+    }
+
     // Hide the timed message box if it ran out
     let mut window_timed_out = false;
     if let Window::Message { ttl, .. } = state.window_stack.top_mut() {
@@ -398,6 +519,23 @@ pub fn update(
                     // Clear any fade set by the gameplay rendering
                     display.fade = color::INVISIBLE;
                 }
+                Window::Statistics => {
+                    if top_level {
+                        game_update_result =
+                            statistics::process(state, ui, gamepad, display, audio);
+                    }
+                    // Clear any fade set by the gameplay rendering
+                    display.fade = color::INVISIBLE;
+                }
+                Window::Achievements => {
+                    if top_level {
+                        game_update_result = crate::windows::achievements::process(
+                            state, ui, gamepad, display, audio,
+                        );
+                    }
+                    // Clear any fade set by the gameplay rendering
+                    display.fade = color::INVISIBLE;
+                }
                 Window::Endgame => {
                     display.fade = color::INVISIBLE;
                     if top_level {
@@ -410,6 +548,32 @@ pub fn update(
                         };
                     }
                 }
+                Window::Daily => {
+                    if top_level {
+                        game_update_result =
+                            crate::windows::daily::process(state, ui, gamepad, display, audio);
+                    }
+                    // Clear any fade set by the gameplay rendering
+                    display.fade = color::INVISIBLE;
+                }
+                Window::Report => {
+                    display.fade = color::INVISIBLE;
+                    if top_level {
+                        game_update_result = report::process(state, ui, display, audio);
+                    }
+                }
+                Window::Map => {
+                    display.fade = color::INVISIBLE;
+                    if top_level {
+                        game_update_result = map::process(state, ui, gamepad, display, audio, dt);
+                    }
+                }
+                Window::Annotate { pos } => {
+                    display.fade = color::INVISIBLE;
+                    if top_level {
+                        game_update_result = annotate::process(state, ui, *pos, display, audio);
+                    }
+                }
                 Window::Message { title, message, .. } => {
                     if top_level {
                         game_update_result = message::process(state, ui, title, message, display)
@@ -418,15 +582,19 @@ pub fn update(
             }
         }
 
+        if state.show_performance_overlay {
+            performance::show(state, ui);
+        }
+
         if state.replay {
             use egui::widgets::Image;
 
-            let tilesize = crate::graphic::TILE_SIZE as f32;
-            let tilemap_width = crate::engine::TILEMAP_TEXTURE_WIDTH as f32;
-            let tilemap_height = crate::engine::TILEMAP_TEXTURE_HEIGHT as f32;
+            let tilesize = display.tileset.tile_size as f32;
+            let (tilemap_width, tilemap_height) = display.tileset.texture_size_px;
+            let (tilemap_width, tilemap_height) = (tilemap_width as f32, tilemap_height as f32);
             let uv = egui::Rect::from_min_size(
-                // NOTE: coordinates
-                [110.0 / tilemap_width, 10.0 / tilemap_height].into(),
+                // NOTE: the mouse pointer is the tile at (11, 1)
+                [(tilesize / tilemap_width) * 11.0, tilesize / tilemap_height].into(),
                 // NOTE: the doubling here means draw 2x2 tiles (total of four)
                 [
                     (tilesize / tilemap_width) * 2.0,
@@ -500,6 +668,7 @@ pub fn update(
 
     if cfg!(feature = "stats") {
         state.stats.push(FrameStats {
+            frame: dt,
             update: update_duration,
             drawcalls: drawcall_duration,
         });
@@ -512,11 +681,37 @@ pub fn update(
         && cfg!(feature = "stats")
     {
         show_exit_stats(&state.stats);
+        performance::save_trace_recording();
     }
 
     game_update_result
 }
 
+/// Returns `true` when the game has to be updated every frame. That
+/// is when something's animating, the monsters are taking their turn,
+/// the player is walking along a path or a replay is playing.
+///
+/// Otherwise nothing happens until the player does something, so the
+/// engine can wait for their input instead.
+pub fn needs_continuous_updates(state: &State) -> bool {
+    state.replay
+        || !state.commands.is_empty()
+        || state.side != Side::Player
+        || !state.pos_timer.finished()
+        || !state.path_walking_timer.finished()
+        || state.explosion_animation.is_some()
+        || !state.extra_animations.is_empty()
+        || state.screen_fading.is_some()
+        || state.mouse.left_is_down
+        || (state.auto_walking && state.waypoint.is_some())
+        || state.pending_waypoint.is_some()
+        || state.pending_undo
+        || matches!(
+            state.window_stack.top(),
+            Window::Message { ttl: Some(_), .. }
+        )
+}
+
 fn enqueue_background_music(audio: &mut Audio, rng: &mut Random) {
     if audio.background_sound_queue.len() <= 1 {
         let sound = if cfg!(feature = "recording") {
@@ -560,12 +755,17 @@ fn process_game(
         return RunningState::Running;
     }
 
-    if option.is_none() {
+    // NOTE: `Esc` leaves the examine mode rather than opening the menu.
+    if option.is_none() && state.examine_cursor.is_none() {
         option = if state.keys.matches_code(KeyCode::Esc) || state.keys.matches_code(KeyCode::Menu)
         {
             Some(Action::MainMenu)
         } else if state.keys.matches_code(KeyCode::QuestionMark) {
             Some(Action::Help)
+        } else if state.keys.matches_code(KeyCode::U) && state.can_undo() {
+            Some(Action::Undo)
+        } else if state.keys.matches_code(KeyCode::M) {
+            Some(Action::Map)
         } else {
             None
         };
@@ -574,6 +774,8 @@ fn process_game(
     if let Some(
         Action::MainMenu
         | Action::Help
+        | Action::Map
+        | Action::Undo
         | Action::UseFood
         | Action::UseDose
         | Action::UseCardinalDose
@@ -593,6 +795,15 @@ fn process_game(
             state.window_stack.push(Window::Help);
             return RunningState::Running;
         }
+        Some(Action::Map) => {
+            state.map_view.centre_on(state.player.pos);
+            state.window_stack.push(Window::Map);
+            return RunningState::Running;
+        }
+        Some(Action::Undo) => {
+            state.pending_undo = true;
+            return RunningState::Running;
+        }
         Some(sidebar_action) => {
             let sidebar_command = match sidebar_action {
                 Action::UseFood => Some(Command::UseFood),
@@ -665,9 +876,11 @@ fn process_game(
     }
 
     if cfg!(feature = "cheating") && state.keys.matches_code(KeyCode::V) && state.cheating {
-        place_victory_npc(state);
+        simulation::place_victory_npc(state);
     }
 
+    process_examine(state, gamepad, gamepad_highlighted_tile, highlighted_tiles);
+
     // TODO: NOTE: this now doesn't work on replays because state.keys only contains the replay keys
     let paused_one_step = state.paused && state.keys.matches_code(KeyCode::Right);
 
@@ -685,30 +898,8 @@ fn process_game(
         state.offset_px = Point::new(x as i32, y as i32);
     }
 
-    let mut entire_turn_ended = false;
-
-    let simulation_area = formula::simulation_area(state.player.pos);
-
-    if (!state.paused || paused_one_step) && state.side != Side::Victory {
-        let monster_count = state.world.monsters(simulation_area).count();
-        let monster_with_ap_count = state
-            .world
-            .monsters(simulation_area)
-            .filter(|m| m.has_ap(1))
-            .count();
-        let monster_cumulative_ap: i32 = state
-            .world
-            .monsters(simulation_area)
-            .map(|m| m.ap.to_int())
-            .sum();
-        log::trace!(
-            "Player AP: {}, monsters: {}, active mon: {}, total mon AP: {}",
-            state.player.ap(),
-            monster_count,
-            monster_with_ap_count,
-            monster_cumulative_ap
-        );
-
+    let advance = (!state.paused || paused_one_step) && state.side != Side::Victory;
+    if advance {
         if state.inventory_focused {
             // Don't turn keys into commands. The player should not be
             // controllable while the inventory is active.
@@ -716,7 +907,28 @@ fn process_game(
             // TODO: we need to do the same for mouse. Or make sure we
             // close inventory on click at least.
         } else {
-            process_keys(&mut state.keys, &mut state.commands);
+            if state.waypoint.is_some() && state.keys.matches_code(KeyCode::G) {
+                state.auto_walking = true;
+            }
+            let mut commands = VecDeque::new();
+            process_keys(&mut state.keys, &mut commands);
+            // NOTE: any other command takes the control back from
+            // the auto-walk.
+            if commands.iter().any(|command| *command != Command::WalkPath) {
+                state.auto_walking = false;
+            }
+            state.commands.extend(commands);
+        }
+
+        if state.mouse.left_clicked {
+            state.auto_walking = false;
+        }
+        if state.waypoint == Some(state.player.pos) {
+            state.waypoint = None;
+            if state.auto_walking {
+                state.auto_walking = false;
+                state.event_log.push("You've reached the waypoint.");
+            }
         }
 
         if state.mouse.left_clicked || state.mouse.right_clicked {
@@ -733,47 +945,53 @@ fn process_game(
         let mouse_inside_map = Rectangle::from_size(state.map_size)
             .contains_excluding_bottom_right(state.mouse.tile_pos);
 
-        let visible = state.mouse_world_position().inside_circular_area(
-            state.player.pos,
-            formula::exploration_radius(state.player.mind),
-        );
+        let path_allowed = state.mouse_path_allowed();
 
-        if state.game_session.started() && state.player.alive() && !state.inventory_focused {
+        if state.examine_cursor.is_some() {
+            // NOTE: the mouse and gamepad move the examine cursor instead.
+            state.player_path.clear();
+        } else if state.game_session.started() && state.player.alive() && !state.inventory_focused {
             let source = state.player.pos;
 
-            if let Some(destination_offset) = gamepad_highlighted_tile {
+            if let Some(waypoint) = state.waypoint
+                && state.auto_walking
+            {
+                let path = player_path_to(state, waypoint);
+                if path.is_empty() {
+                    state.auto_walking = false;
+                    state
+                        .event_log
+                        .push("You can't find a way to the waypoint.");
+                } else if state.commands.is_empty() && state.path_walking_timer.finished() {
+                    state.commands.push_back(Command::WalkPath);
+                }
+                for point in path.clone() {
+                    let screen_pos = state.screen_pos_from_world_pos(point);
+                    highlighted_tiles.push(screen_pos);
+                }
+                state.player_path = path;
+            } else if let Some(destination_offset) = gamepad_highlighted_tile {
                 let destination = source + destination_offset;
-                let check_irresistible = true;
-                let path = pathfinding::Path::find(
+                let cost = PlayerCost {
+                    will: state.player.will.to_int(),
+                };
+                let path = state.pathfinding.find(
                     source,
                     destination,
                     &state.world,
                     Blocker::WALL,
                     state.player.pos,
-                    state.player.will.to_int(),
-                    check_irresistible,
                     formula::PATHFINDING_PLAYER_MOUSE_LIMIT,
-                    &pathfinding::player_cost,
+                    &cost,
                 );
                 for point in path.clone() {
                     let screen_pos = state.screen_pos_from_world_pos(point);
                     highlighted_tiles.push(screen_pos);
                 }
                 state.player_path = path;
-            } else if mouse_inside_map && visible {
+            } else if mouse_inside_map && path_allowed {
                 let destination = state.mouse_world_position();
-                let check_irresistible = true;
-                let path = pathfinding::Path::find(
-                    source,
-                    destination,
-                    &state.world,
-                    Blocker::WALL,
-                    state.player.pos,
-                    state.player.will.to_int(),
-                    check_irresistible,
-                    formula::PATHFINDING_PLAYER_MOUSE_LIMIT,
-                    &pathfinding::player_cost,
-                );
+                let path = player_path_to(state, destination);
                 for point in path.clone() {
                     let screen_pos = state.screen_pos_from_world_pos(point);
                     highlighted_tiles.push(screen_pos);
@@ -783,185 +1001,26 @@ fn process_game(
                 state.player_path.clear();
             }
         }
-
-        state.player.motion_animation.update(dt);
-        for monster in state.world.monsters_mut(simulation_area) {
-            monster.motion_animation.update(dt);
-        }
-        for motion_animation in &mut state.extra_animations {
-            motion_animation.animation.update(dt);
-        }
-
-        // NOTE: Process 1 action point of the player and then 1 AP of
-        // all monsters. This means that their turns will alternate.
-        // E.g. if the player has 2 APs and they're close to a
-        // Depression, the player will move 1 turn first, then
-        // Depression 1 etc.
-
-        let player_ap = state.player.ap();
-        log::trace!("Player AP before processing: {player_ap}");
-        if state.player.ap() >= 1 {
-            process_player(state, display, audio, simulation_area);
-        }
-        let player_took_action = player_ap > state.player.ap();
-        let monsters_can_move = state.player.ap() == 0 || player_took_action;
-        log::trace!(
-            "Player AP: {player_ap}, Player took action: {player_took_action}, Monsters can move: {monsters_can_move}"
-        );
-
-        if state.explosion_animation.is_none() {
-            if monsters_can_move {
-                process_monsters(
-                    &mut state.world,
-                    &mut state.player,
-                    simulation_area,
-                    display.tile_size,
-                    &mut state.rng,
-                    audio,
-                    &state.palette,
-                    &mut state.extra_animations,
-                );
-            } else {
-                log::trace!("Monsters waiting for player.");
-            }
-        } else {
-            log::trace!("Monster's waiting for the explosion to end.");
-        }
-
-        // NOTE: the anxiety counter bar is hidden at the start, but
-        // we want to show it as soon as it increases.
-        if player_took_action && !state.player.anxiety_counter.is_min() {
-            state.show_anxiety_counter = true;
-        }
-
-        if player_took_action
-            && state.player.mind.is_high()
-            && let Some(victory_npc_id) = state.victory_npc_id.take()
-        {
-            log::info!("Player got High, the Victory NPC disappears!");
-            if let Some(vnpc) = state.world.monster_mut(victory_npc_id) {
-                // TODO: move this (and other init stuff from
-                // Monster::new) to custom functions?
-                vnpc.kind = monster::Kind::Signpost;
-                vnpc.behavior = ai::Behavior::Immobile;
-                vnpc.ai_state = ai::AIState::NoOp
-            }
-        }
-
-        // Reset all action points only after everyone is at zero:
-        let player_turn_ended = !state.player.has_ap(1);
-        let monster_turn_ended = state
-            .world
-            .monsters(simulation_area)
-            .filter(|m| m.has_ap(1))
-            .count()
-            == 0;
-
-        entire_turn_ended = player_turn_ended && monster_turn_ended;
-        log::trace!(
-            "Entire turn ended: {}, player turn ended: {}, monster turn ended: {}",
-            entire_turn_ended,
-            player_turn_ended,
-            monster_turn_ended
-        );
-    }
-
-    // Reset the player & monster action points
-    // NOTE: doing this only after we've logged the validations. Actually maybe we want to do this
-    // before we start turn processing??
-    if entire_turn_ended {
-        log::trace!("Starting new turn for player and monsters.");
-        state.player.new_turn();
-        for monster in state.world.monsters_mut(simulation_area) {
-            monster.new_turn();
-        }
-    }
-
-    if entire_turn_ended {
-        log::trace!("Turn {} has ended.", state.turn);
-        state.turn += 1;
-    }
-
-    // NOTE: Load up new chunks if necessary
-    if entire_turn_ended {
-        for pos in simulation_area.points() {
-            state.world.ensure_chunk_at_pos(pos);
-        }
     }
 
-    // Run the dose explosion effect here:
-    if let Some(ref anim) = state.explosion_animation {
-        for (pos, _, effect) in anim.tiles() {
-            if effect.contains(animation::TileEffect::KILL) {
-                kill_monster(pos, &mut state.world, audio);
-            }
-            if effect.contains(animation::TileEffect::SHATTER)
-                && let Some(cell) = state.world.cell_mut(pos)
-            {
-                cell.tile.kind = TileKind::Empty;
-                cell.tile.graphic = Graphic::Empty;
-                cell.items.clear();
-            }
-        }
+    // NOTE: remember the start of every turn so the player can come
+    // back to it in the practice mode.
+    if advance
+        && state.challenge.practice
+        && state.player.alive()
+        && state.game_session == GameSession::InProgress
+    {
+        state.save_undo_snapshot();
     }
 
-    // Set the fadeout animation on death
+    // NOTE: `log_event` moves the log to the next turn whenever one
+    // ends.
+    state.event_log.set_turn(state.turn);
+    simulation::tick(state, dt, display.tile_size, advance);
     if player_was_alive && !state.player.alive() {
-        use crate::player::CauseOfDeath::*;
-        log::info!("Player died.");
-
-        audio.play_sound(Effect::GameOver, Duration::from_millis(0));
-        let cause_of_death = formula::cause_of_death(&state.player);
-        let fade_color = if cfg!(feature = "recording") {
-            state.palette.fade_to_black_animation
-        } else {
-            match cause_of_death {
-                Some(Exhausted) => state.palette.exhaustion_animation,
-                Some(Overdosed) => state.palette.overdose_animation,
-                Some(_) => state.palette.death_animation,
-                None => {
-                    // NOTE: this shouldn't happen (there should always be
-                    // a cause of death) but if it did, we won't crash
-                    state.palette.death_animation
-                }
-            }
-        };
-
-        let fade = formula::mind_fade_value(state.player.mind);
-
-        let fade_out_ms = if cfg!(feature = "recording") {
-            2500
-        } else if state.replay_full_speed {
-            500
-        } else {
-            2500
-        };
-
-        let fade_in_ms = if cfg!(feature = "recording") {
-            1300
-        } else if state.replay_full_speed {
-            200
-        } else {
-            500
-        };
-
-        let initial_fade_percentage = 1.0 - fade;
-        if state.challenge.one_chance {
-            state.game_session = GameSession::Ended;
-            state.show_endscreen_and_uncover_map_during_fadein = true;
-            log::trace!("Game real time: {:?}", state.clock);
-        } else {
-            // NOTE: Don't die, reset the player to the initial state instead:
-            state.player.reset();
-        }
-        state.screen_fading = Some(animation::ScreenFade::new(
-            fade_color,
-            Duration::from_millis(fade_out_ms),
-            Duration::from_millis(200),
-            Duration::from_millis(fade_in_ms),
-            initial_fade_percentage,
-        ));
+        simulation::player_died(state);
     }
+    process_events(state, audio);
 
     let explored = state
         .world
@@ -979,8 +1038,17 @@ fn process_game(
         && mouse_window_pos_px.x <= game_area_px.x
         && mouse_window_pos_px.y <= game_area_px.y;
 
+    show_annotations(state, ui, display);
+
+    if let Some(cursor) = state.examine_cursor {
+        show_examine_panel(state, ui, display, cursor);
+    }
+
     // NOTE: show tooltip of a hovered-over object
-    let tooltip = if !explored && settings.hide_unseen_tiles || !pointer_inside_game_area {
+    let tooltip = if !explored && settings.hide_unseen_tiles
+        || !pointer_inside_game_area
+        || state.examine_cursor.is_some()
+    {
         None
     } else if state.mouse_world_position() == state.player.pos {
         Some("Player Character")
@@ -999,16 +1067,6 @@ fn process_game(
         }
     }
 
-    // NOTE: update the dose/food explosion animations
-    state.explosion_animation = state.explosion_animation.take().and_then(|mut animation| {
-        animation.update(dt);
-        if animation.finished() {
-            None
-        } else {
-            Some(animation)
-        }
-    });
-
     // NOTE: re-centre the display if the player reached the end of the screen
     let no_left_mouse = !state.mouse.left_is_down && !state.mouse.left_clicked;
     if state.pos_timer.finished() && no_left_mouse {
@@ -1058,645 +1116,455 @@ fn process_game(
         }
     }
 
-    // NOTE: Remove any animations that are already finished.
-    state.extra_animations.retain(|a| a.animation.in_progress());
     RunningState::Running
 }
 
-fn process_monsters(
-    world: &mut World,
-    player: &mut player::Player,
-    area: Rectangle,
-    tile_size: i32,
-    rng: &mut Random,
-    audio: &mut Audio,
-    palette: &Palette,
-    extra_animations: &mut Vec<MotionAnimation>,
-) {
-    if !player.alive() {
-        return;
-    }
-    log::trace!("Processing monsters");
-
-    // NOTE: one quarter of the map area should be a decent overestimate
-    let monster_count_estimate = area.size().x * area.size().y / 4;
-    assert!(monster_count_estimate > 0);
-    let mut monster_positions_vec = world
-        .monsters(area)
-        .filter(|m| m.has_ap(1))
-        .map(|m| (m.ap.to_int(), m.position))
-        .collect::<Vec<_>>();
-    // NOTE: `world.monsters` does not give a stable result so we need to sort
-    // it here to ensure correct replays.
-    // NOTE: there's always at most one monster at a given position so this should always produce
-    // the same ordering.
-    //
-    // We sort by action points (so depression always goes first), by
-    // distance to player (so a closer monster can move first and make
-    // space for another one near by) and then by coordinates just to
-    // have some awy to always produce a stable ordering.
-    monster_positions_vec
-        .sort_by_key(|&(ap, pos)| (ap, player.pos.distance(pos) as i32, pos.x, pos.y));
-    let mut monster_positions_to_process: VecDeque<_> = monster_positions_vec.into();
-    log::trace!(
-        "Monsters to process: {}",
-        monster_positions_to_process.len()
+/// The path the player would walk to get to `destination`.
+fn player_path_to(state: &mut State, destination: Point) -> Path {
+    let cost = PlayerCost {
+        will: state.player.will.to_int(),
+    };
+    let path = state.pathfinding.find(
+        state.player.pos,
+        destination,
+        &state.world,
+        Blocker::WALL,
+        state.player.pos,
+        formula::PATHFINDING_PLAYER_MOUSE_LIMIT,
+        &cost,
     );
-
-    while let Some((_, monster_position)) = monster_positions_to_process.pop_front() {
-        if !player.alive() {
-            // Don't process any new monsters if the player's dead
-            // because we want the game to stop and freeze at that
-            // time.
-            //
-            // But we still want all the effects (e.g. attack
-            // animatino) that started to finish so we don't want to
-            // quit the loop as soon as the player dies.
-            continue;
-        }
-        if let Some(monster_readonly) = world.monster_on_pos(monster_position).cloned() {
-            let action = {
-                let (update, action) = monster_readonly.act(player.info(), world, rng);
-                if let Some(monster) = world.monster_on_pos(monster_position) {
-                    monster.ai_state = update.ai_state;
-                    monster.ap = Ranged::new(
-                        monster.ap.to_int(),
-                        InclusiveRange(monster.ap.min(), update.max_ap),
-                    );
-
-                    monster.spend_ap(1);
-                }
-                action
-            };
-
-            let (animated_monster_position, animation) = match action {
-                Action::Move(destination) => {
-                    assert_eq!(monster_position, monster_readonly.position);
-
-                    let pos = monster_readonly.position;
-
-                    let path_changed = monster_readonly
-                        .path
-                        .last()
-                        .is_none_or(|&cached_destination| cached_destination != destination);
-
-                    // NOTE: we keep a cache of any previously calculated
-                    // path in `monster.path`. If the precalculated path
-                    // is blocked or there is none, calculate a new one
-                    // and cache it. Otherwise, just walk it.
-                    let (newpos, newpath) = if monster_readonly.path.is_empty()
-                        || path_changed
-                        || !world.walkable(
-                            monster_readonly.path[0],
-                            monster_readonly.blockers,
-                            player.pos,
-                        ) {
-                        // Calculate a new path or recalculate the existing one.
-                        let check_irresistible = false;
-                        let mut path = pathfinding::Path::find(
-                            pos,
-                            destination,
-                            world,
-                            monster_readonly.blockers,
-                            player.pos,
-                            player.will.to_int(),
-                            check_irresistible,
-                            formula::PATHFINDING_MONSTER_LIMIT,
-                            &pathfinding::monster_cost,
-                        );
-                        let newpos = path.next().unwrap_or(pos);
-                        // Cache the path-finding result
-                        let newpath = path.collect();
-                        (newpos, newpath)
-                    } else {
-                        (monster_readonly.path[0], monster_readonly.path[1..].into())
-                    };
-
-                    world.move_monster(pos, newpos, player.pos);
-                    let monster_visible = newpos
-                        .inside_circular_area(player.pos, formula::exploration_radius(player.mind));
-                    if monster_visible {
-                        let delay = audio.random_delay();
-                        audio.play_sound(Effect::MonsterMoved, delay);
-                    }
-                    if let Some(monster) = world.monster_on_pos(newpos) {
-                        monster.path = newpath;
-                        if monster.has_ap(1) {
-                            monster.trail = Some(newpos);
-                        }
-                    }
-
-                    let anim = animation::Move::ease(
-                        pos * tile_size,
-                        newpos * tile_size,
-                        formula::ANIMATION_MOVE_DURATION,
-                    );
-                    assert_eq!(anim.finished(), false);
-                    (newpos, anim)
-                }
-
-                Action::Attack(target_pos, damage) => {
-                    assert_eq!(target_pos, player.pos);
-                    player.take_effect(damage);
-                    audio.play_sound(Effect::PlayerHit, Duration::from_millis(0));
-
-                    let anim = animation::Move::bounce(
-                        monster_readonly.position * (tile_size / 3),
-                        target_pos * (tile_size / 3),
-                        formula::ANIMATION_ATTACK_DURATION,
-                    );
-
-                    if monster_readonly.die_after_attack {
-                        kill_monster(monster_readonly.position, world, audio);
-                        extra_animations.push(MotionAnimation {
-                            pos: monster_readonly.position,
-                            graphic: monster_readonly.graphic(),
-                            color: monster_readonly.color(palette),
-                            animation: anim.clone(),
-                        });
-                    }
-                    if !player.alive() {
-                        // NOTE: this monster killed the player, set the perpetrator.
-                        player.perpetrator = Some(monster_readonly.clone());
-                    }
-
-                    (monster_readonly.position, anim)
-                }
-
-                Action::Use(_) => {
-                    log::error!("Trying to run the Use action on a monster. That's not defined!");
-                    (monster_readonly.position, animation::Move::none())
-                }
-            };
-
-            if let Some(monster) = world.monster_on_pos(animated_monster_position) {
-                monster.motion_animation = animation;
-            }
-        }
+    // NOTE: the far away explored tiles are out of the regular
+    // search's reach.
+    if path.is_empty() && state.rules_version >= 3 {
+        state.chunk_graph.find(
+            state.player.pos,
+            destination,
+            &state.world,
+            Blocker::WALL,
+            state.player.pos,
+            formula::PATHFINDING_PLAYER_CHUNK_LIMIT,
+            &mut state.pathfinding,
+            &cost,
+        )
+    } else {
+        path
     }
 }
 
-fn process_player_action(
-    player: &mut player::Player,
-    commands: &mut VecDeque<Command>,
-    world: &mut World,
-    simulation_area: Rectangle,
-    explosion_animation: &mut Option<Box<dyn AreaOfEffect>>,
-    rng: &mut Random,
-    window_stack: &mut crate::windows::Windows<Window>,
-    bumped_into_a_monster: &mut bool,
-    tile_size: i32,
-    palette: &Palette,
-    audio: &mut Audio,
-) {
-    log::trace!("Processing player action");
-    if !player.alive() {
-        log::trace!("Processing player action, but the player is dead.");
-        return;
+/// Hand the `GameEvent`s the simulation queued up this frame over
+/// to everything that reacts to them.
+fn process_events(state: &mut State, audio: &mut Audio) {
+    let events = std::mem::take(&mut state.events);
+    for event in &events {
+        log::debug!("Game event: {:?}", event);
+        log_event(state, event);
+        play_event_sound(audio, event);
+        record_event_stats(state, event);
+        animate_event(state, event);
+        show_event_windows(state, event);
+        unlock_event_achievements(state, event);
+        interrupt_auto_walk(state, event);
     }
-    if !player.has_ap(1) {
-        log::trace!(
-            "Processing player action, but the player has no AP: {}",
-            player.ap()
-        );
+}
+
+/// Stop walking to the waypoint when something needs the player's
+/// attention.
+fn interrupt_auto_walk(state: &mut State, event: &GameEvent) {
+    if !state.auto_walking {
         return;
     }
+    let reason = match event {
+        GameEvent::MonsterMoved {
+            kind,
+            visible: true,
+            ..
+        } if kind.is_monster() => "You stop: something is moving nearby.",
+        GameEvent::MonsterAttacked { .. } => "You stop: you've been attacked.",
+        GameEvent::ItemPickedUp(kind) if kind.is_dose() => "You stop: you picked up a dose.",
+        _ => return,
+    };
+    state.auto_walking = false;
+    state.event_log.push(reason);
+}
 
-    if let Some(command) = commands.pop_front() {
-        log::trace!("Player Command: {:?}", command);
-        let mut action = match command {
-            Command::N => Action::Move(player.pos + (0, -1)),
-            Command::S => Action::Move(player.pos + (0, 1)),
-            Command::W => Action::Move(player.pos + (-1, 0)),
-            Command::E => Action::Move(player.pos + (1, 0)),
-
-            Command::NW => Action::Move(player.pos + (-1, -1)),
-            Command::NE => Action::Move(player.pos + (1, -1)),
-            Command::SW => Action::Move(player.pos + (-1, 1)),
-            Command::SE => Action::Move(player.pos + (1, 1)),
-
-            Command::UseFood => Action::Use(item::Kind::Food),
-            Command::UseDose => Action::Use(item::Kind::Dose),
-            Command::UseCardinalDose => Action::Use(item::Kind::CardinalDose),
-            Command::UseDiagonalDose => Action::Use(item::Kind::DiagonalDose),
-            Command::UseStrongDose => Action::Use(item::Kind::StrongDose),
-
-            // NOTE: ignore, this has been processed elsewhere
-            Command::WalkPath => return,
-
-            Command::ShowMessageBox {
-                ttl,
-                title,
-                message,
-            } => {
-                window_stack.push(window::timed_message_box(title, message, ttl));
-                return;
+/// Describe what happened in the event log.
+fn log_event(state: &mut State, event: &GameEvent) {
+    use crate::player::CauseOfDeath::*;
+    let text = match event {
+        GameEvent::MonsterSpotted {
+            kind,
+            pos,
+            player_pos,
+        } => format!(
+            "{} is {}",
+            kind.name(),
+            event_log::relative_position(*player_pos, *pos)
+        ),
+        GameEvent::MonsterAttacked { kind, effect } => format!(
+            "{} attacks you, {}",
+            kind.name(),
+            event_log::describe_effect(*effect)
+        ),
+        GameEvent::MonsterKilled { kind, .. } if kind.is_monster() => {
+            format!("{} is gone", kind.name())
+        }
+        GameEvent::PlayerStunned => "You are stunned and can't move.".to_string(),
+        GameEvent::PlayerPanicked => "You panic and run.".to_string(),
+        GameEvent::AnxietyFaced { counter, max } => {
+            format!("You face Anxiety, anxiety counter {counter} of {max}")
+        }
+        GameEvent::WillGrew { will } => format!("Your Will grows to {will}"),
+        GameEvent::HungerFought { effect } => format!(
+            "You fight off Hunger, {}",
+            event_log::describe_effect(*effect)
+        ),
+        GameEvent::ItemPickedUp(item::Kind::Food) => "You picked up Food".to_string(),
+        GameEvent::ItemPickedUp(kind) => format!("You picked up a {}", kind.name()),
+        GameEvent::DoseIrresistible(kind) => format!("You can't resist the {}", kind.name()),
+        GameEvent::FoodEaten { effect } => {
+            format!("You ate Food, {}", event_log::describe_effect(*effect))
+        }
+        GameEvent::DoseUsed { kind, mind } => {
+            format!("You used a {}, you are {}", kind.name(), mind)
+        }
+        GameEvent::NpcMet(bonus) => format!("An NPC joins you: {bonus}"),
+        GameEvent::CompanionLeft => "Your previous companion leaves you.".to_string(),
+        GameEvent::TurnEnded { turn } => {
+            state.event_log.set_turn(*turn);
+            return;
+        }
+        GameEvent::PlayerDied {
+            cause, perpetrator, ..
+        } => {
+            let perpetrator = perpetrator.map(monster::Kind::name);
+            match (cause, perpetrator) {
+                (Some(Exhausted), Some(name)) => format!("You are exhausted because of {name}."),
+                (Some(Exhausted), None) => "You are exhausted.".to_string(),
+                (Some(Overdosed), _) => "You overdosed.".to_string(),
+                (Some(LostWill), Some(name)) => format!("You lost all Will due to {name}."),
+                (Some(LostWill), None) => "You lost all Will.".to_string(),
+                (Some(Killed), Some(name)) => format!("You were defeated by {name}."),
+                (Some(Killed), None) | (None, _) => "You lost.".to_string(),
             }
-        };
-        log::trace!("Action from Command: {:?}", action);
-
-        if player.stun.to_int() > 0 {
-            action = Action::Move(player.pos);
-        } else if player.panic.to_int() > 0 {
-            let new_pos =
-                world.random_neighbour_position(rng, player.pos, Blocker::WALL, player.pos);
-            action = Action::Move(new_pos);
-        } else if let Some((dose_pos, dose)) = world.nearest_dose(player.pos, 5) {
-            let resist_radius =
-                formula::player_resist_radius(dose.irresistible, player.will.to_int()) as usize;
-            if player.pos.tile_distance(dose_pos) < resist_radius as i32 {
-                // We're already in the resist radius so we don't care about the cost.
-                let check_irresistible = false;
-                let mut path = pathfinding::Path::find(
-                    player.pos,
-                    dose_pos,
-                    world,
-                    Blocker::WALL,
-                    player.pos,
-                    player.will.to_int(),
-                    check_irresistible,
-                    formula::PATHFINDING_DOSE_RESIST_LIMIT,
-                    &pathfinding::direct_cost,
-                );
-
-                let new_pos_opt = if path.len() <= resist_radius {
-                    path.next()
-                } else {
-                    None
-                };
+        }
+        GameEvent::VictoryNpcPlaced { pos, player_pos } => format!(
+            "The Victory NPC is waiting {}",
+            event_log::relative_position(*player_pos, *pos)
+        ),
+        GameEvent::VictoryNpcGone => "You got High. The Victory NPC is gone.".to_string(),
+        GameEvent::Victory => "You won!".to_string(),
+        GameEvent::MessageShown { title, message, .. } => format!("{title}: {message}"),
+        GameEvent::PlayerMoved { .. }
+        | GameEvent::MonsterMoved { .. }
+        | GameEvent::MonsterKilled { .. }
+        | GameEvent::PlayerAttacked { .. }
+        | GameEvent::CompanionMet(_) => return,
+    };
+    state.event_log.push(text);
+}
 
-                if let Some(new_pos) = new_pos_opt {
-                    action = Action::Move(new_pos);
-                } else {
-                    // NOTE: no path leading to the irresistible dose
-                }
-            }
+fn play_event_sound(audio: &mut Audio, event: &GameEvent) {
+    let effect = match event {
+        GameEvent::PlayerMoved { .. } => Effect::Walk,
+        GameEvent::MonsterMoved { visible: true, .. } => {
+            let delay = audio.random_delay();
+            audio.play_sound(Effect::MonsterMoved, delay);
+            return;
         }
+        GameEvent::MonsterAttacked { .. } => Effect::PlayerHit,
+        GameEvent::MonsterKilled { .. } => Effect::MonsterHit,
+        GameEvent::FoodEaten { .. } | GameEvent::DoseUsed { .. } => Effect::Explosion,
+        GameEvent::PlayerDied { .. } => Effect::GameOver,
+        _ => return,
+    };
+    audio.play_sound(effect, Duration::from_millis(0));
+}
 
-        // NOTE: If we have doses in the inventory that we wouldn't be
-        // able to pick up anymore, use them up one by one each turn:
-        let carried_irresistible_dose = player
-            .inventory
-            .iter()
-            .find(|i| {
-                i.is_dose()
-                    && formula::player_resist_radius(i.irresistible, player.will.to_int()) > 0
-            })
-            .map(|i| i.kind);
-        if let Some(kind) = carried_irresistible_dose {
-            action = Action::Use(kind);
+/// Add the finished game to the run history.
+fn record_event_stats(state: &mut State, event: &GameEvent) {
+    match event {
+        GameEvent::PlayerDied { .. } if state.game_session == GameSession::Ended => {
+            record_run(state);
         }
+        GameEvent::Victory => record_run(state),
+        _ => {}
+    }
+}
 
-        log::trace!("Final Action: {:?}", action);
-        match action {
-            Action::Move(dest) => {
-                let dest_walkable =
-                    world.walkable(dest, Blocker::WALL | Blocker::MONSTER, player.pos);
-                let bumping_into_monster = world.monster_on_pos(dest).is_some();
-                if bumping_into_monster {
-                    player.spend_ap(1);
-                    // info!("Player attacks {:?}", monster);
-                    player.motion_animation = animation::Move::bounce(
-                        player.pos * (tile_size / 3),
-                        dest * (tile_size / 3),
-                        formula::ANIMATION_ATTACK_DURATION,
-                    );
-                    if let Some(kind) = world.monster_on_pos(dest).map(|m| m.kind) {
-                        match kind {
-                            monster::Kind::Anxiety => {
-                                log::debug!(
-                                    "Bumped into anxiety! Current anxiety counter: {:?}",
-                                    player.anxiety_counter
-                                );
-                                let increment =
-                                    if player.bonuses.contains(&CompanionBonus::DoubleWillGrowth) {
-                                        2
-                                    } else {
-                                        1
-                                    };
-                                log::debug!("Anxiety increment: {:?}", increment);
-                                player.anxiety_counter += increment;
-                                log::debug!("New anxiety counter: {:?}", player.anxiety_counter);
-                                if player.anxiety_counter.is_max() {
-                                    log::info!("Increasing player's will");
-                                    player.will += 1;
-                                    player.anxiety_counter.set_to_min();
-                                }
-                            }
-                            monster::Kind::Hunger => {
-                                let modifier = Modifier::Attribute {
-                                    state_of_mind: 3,
-                                    will: 0,
-                                };
-                                player.take_effect(modifier);
-                            }
-                            // NOTE: NPCs don't give bonuses or accompany the player when high.
-                            monster::Kind::Npc if player.mind.is_sober() => {
-                                if let Some(monster) = world.monster_on_pos(dest) {
-                                    log::info!("Bumped into NPC: {}", monster);
-                                }
-
-                                // Clear any existing monsters accompanying the player. The player
-                                // can have only one companion at a time right now.
-                                //
-                                // TODO: it also sounds like we could just track the followers in
-                                // the Player/State struct but that needs Monster IDs.
-                                let npcs = world
-                                    .monsters_mut(simulation_area)
-                                    .filter(|m| m.kind == monster::Kind::Npc);
-                                for npc in npcs {
-                                    if npc.position == dest {
-                                        log::info!("NPC {} accompanies the player.", npc);
-                                        npc.accompanying_player = true;
-                                        assert!(npc.companion_bonus.is_some());
-                                    } else if npc.accompanying_player {
-                                        log::info!("NPC {} leaves the player.", npc);
-                                        npc.accompanying_player = false;
-                                    }
-                                }
-                            }
-
-                            monster::Kind::Signpost => {
-                                log::info!("Bumped into a signpost!");
-                                window_stack.push(
-                                    window::message_box(
-                                        "Message",
-                                        "\"I thought you were going to stay sober for good. I was wrong. Goodbye.\""));
-                            }
-
-                            _ => {}
-                        }
-                        kill_monster(dest, world, audio);
+fn animate_event(state: &mut State, event: &GameEvent) {
+    match *event {
+        GameEvent::PlayerDied { cause, mind, .. } => start_death_fade(state, cause, mind),
+        GameEvent::Victory => state.uncovered_map = true,
+        _ => {}
+    }
+}
 
-                        if kind.is_monster() {
-                            *bumped_into_a_monster = true;
-                        }
-                    }
-                } else if dest_walkable {
-                    player.spend_ap(1);
-                    player.motion_animation = animation::Move::ease(
-                        player.pos * tile_size,
-                        dest * tile_size,
-                        formula::ANIMATION_MOVE_DURATION,
-                    );
-                    player.move_to(dest);
-                    audio.play_sound(Effect::Walk, Duration::from_millis(0));
-                    while let Some(item) = world.pickup_item(dest) {
-                        use crate::item::Kind::*;
-                        match item.kind {
-                            Food => player.inventory.push(item),
-                            Dose | StrongDose | CardinalDose | DiagonalDose => {
-                                let resist_radius = formula::player_resist_radius(
-                                    item.irresistible,
-                                    player.will.to_int(),
-                                );
-                                if resist_radius == 0 {
-                                    player.inventory.push(item);
-                                } else {
-                                    use_dose(player, explosion_animation, item, palette, audio);
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    // NOTE: we bumped into a wall, don't do anything
-                }
-            }
+fn show_event_windows(state: &mut State, event: &GameEvent) {
+    match event {
+        GameEvent::MessageShown {
+            title,
+            message,
+            ttl,
+        } => {
+            state.window_stack.push(Window::Message {
+                title: title.clone(),
+                message: message.clone(),
+                ttl: *ttl,
+            });
+        }
+        GameEvent::Victory => {
+            state.selected_endgame_window_action = Some(endgame::Action::NewGame);
+            state.window_stack.push(Window::Endgame);
+        }
+        _ => {}
+    }
+}
 
-            Action::Use(item::Kind::Food) => {
-                if let Some(food_idx) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::Food)
-                {
-                    player.spend_ap(1);
-                    audio.play_sound(Effect::Explosion, Duration::from_millis(0));
-                    let food = player.inventory.remove(food_idx);
-                    player.take_effect(food.modifier);
-                    let food_explosion_radius = 2;
-                    let animation = animation::SquareExplosion::new(
-                        player.pos,
-                        food_explosion_radius,
-                        1,
-                        palette.explosion,
-                    );
-                    *explosion_animation = Some(Box::new(animation));
-                }
+fn unlock_event_achievements(state: &mut State, event: &GameEvent) {
+    match *event {
+        GameEvent::CompanionMet(_)
+            if achievements::met_all_companions(&state.player.companions_met) =>
+        {
+            unlock_achievement(state, Achievement::GoodCompany);
+        }
+        GameEvent::TurnEnded { turn }
+            if turn == achievements::SURVIVOR_TURNS && state.player.alive() =>
+        {
+            unlock_achievement(state, Achievement::Survivor);
+        }
+        GameEvent::Victory => {
+            if state.player.doses_used == 0 {
+                unlock_achievement(state, Achievement::CleanVictory);
             }
-
-            Action::Use(item::Kind::Dose) => {
-                if let Some(dose_index) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::Dose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose, palette, audio);
-                }
+            if achievements::all_challenges_on(state.challenge) {
+                unlock_achievement(state, Achievement::HardMode);
             }
+        }
+        _ => {}
+    }
+}
 
-            Action::Use(item::Kind::StrongDose) => {
-                if let Some(dose_index) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::StrongDose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose, palette, audio);
-                }
+/// Set the fadeout animation on death.
+fn start_death_fade(state: &mut State, cause_of_death: Option<CauseOfDeath>, mind: player::Mind) {
+    use crate::player::CauseOfDeath::*;
+    let fade_color = if cfg!(feature = "recording") {
+        state.palette.fade_to_black_animation
+    } else {
+        match cause_of_death {
+            Some(Exhausted) => state.palette.exhaustion_animation,
+            Some(Overdosed) => state.palette.overdose_animation,
+            Some(_) => state.palette.death_animation,
+            None => {
+                // NOTE: this shouldn't happen (there should always be
+                // a cause of death) but if it did, we won't crash
+                state.palette.death_animation
             }
+        }
+    };
 
-            Action::Use(item::Kind::CardinalDose) => {
-                if let Some(dose_index) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::CardinalDose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose, palette, audio);
-                }
-            }
+    let fade = formula::mind_fade_value(mind);
 
-            Action::Use(item::Kind::DiagonalDose) => {
-                if let Some(dose_index) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::DiagonalDose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose, palette, audio);
-                }
-            }
+    let fade_out_ms = if cfg!(feature = "recording") {
+        2500
+    } else if state.replay_full_speed {
+        500
+    } else {
+        2500
+    };
 
-            Action::Attack(destination, player_modifier) => {
-                log::error!(
-                    "Player tried to use the `Attack` action. This should not be possible (attack is done via moving). Destination: {:?}, player modifier: {:?}",
-                    destination,
-                    player_modifier
-                );
-                player.spend_ap(1);
-            }
-        }
+    let fade_in_ms = if cfg!(feature = "recording") {
+        1300
+    } else if state.replay_full_speed {
+        200
     } else {
-        log::trace!("No Command found");
-    }
+        500
+    };
+
+    let initial_fade_percentage = 1.0 - fade;
+    state.screen_fading = Some(animation::ScreenFade::new(
+        fade_color,
+        Duration::from_millis(fade_out_ms),
+        Duration::from_millis(200),
+        Duration::from_millis(fade_in_ms),
+        initial_fade_percentage,
+    ));
 }
 
-fn process_player(
+/// Handle the examine mode: toggle it and move the cursor around.
+///
+/// The cursor moves with the same keys as the player, the gamepad
+/// stick or a mouse click. The player doesn't move while examining.
+fn process_examine(
     state: &mut State,
-    display: &Display,
-    audio: &mut Audio,
-    simulation_area: Rectangle,
+    gamepad: &Gamepad,
+    gamepad_highlighted_tile: Option<Point>,
+    highlighted_tiles: &mut Vec<Point>,
 ) {
-    {
-        log::trace!("Processing player");
-
-        // appease borrowck
-        let player = &mut state.player;
+    let can_examine = state.game_session == GameSession::InProgress && state.player.alive();
+    if !can_examine {
+        state.examine_cursor = None;
+        return;
+    }
 
-        // NPCs should unfollow an intoxicated player:
-        if player.mind.is_high() {
-            let npcs = state.world.monsters_mut(simulation_area).filter(|m| {
-                m.kind == monster::Kind::Npc && m.accompanying_player && m.companion_bonus.is_some()
-            });
-            for npc in npcs {
-                log::info!("{:?} will not accompany an intoxicated player.", npc);
-                npc.accompanying_player = false;
-            }
+    let toggled = state.keys.matches_code(KeyCode::L);
+    let previous_cursor = state.examine_cursor;
+    let mut cursor = match (previous_cursor, toggled) {
+        (None, true) => state.player.pos,
+        (Some(cursor), false) => cursor,
+        (None, false) => return,
+        (Some(_), true) => {
+            state.examine_cursor = None;
+            return;
         }
-
-        let world = &state.world;
-
-        // NOTE: if the player manages to outrun the NPC (they follow
-        // the player but it still can happen), the bonus will
-        // disappear.
-        let npc_bonuses = world
-            .monsters(simulation_area)
-            .filter(|m| {
-                m.kind == monster::Kind::Npc && m.accompanying_player && m.companion_bonus.is_some()
-            })
-            .map(|m| {
-                // NOTE: this unwrap should always succeed due to the
-                // filter check above. Providing a fallback to prevent
-                // any crasches caused by future refactoring.
-                m.companion_bonus.unwrap_or_else(|| {
-                    log::error!(
-                        "Trying to get a companion bonus where one doesn't exist, but  it should."
-                    );
-                    CompanionBonus::DoubleWillGrowth
-                })
-            });
-        player.bonuses.clear();
-        player.bonuses.extend(npc_bonuses);
+    };
+    if state.keys.matches_code(KeyCode::Esc) || state.keys.matches_code(KeyCode::Menu) {
+        state.examine_cursor = None;
+        return;
     }
-
-    let visible = state.mouse_world_position().inside_circular_area(
-        state.player.pos,
-        formula::exploration_radius(state.player.mind),
-    );
-
-    log::trace!(
-        "left down: {}, visible: {}, walking timer done: {}",
-        state.mouse.left_is_down,
-        visible,
-        state.path_walking_timer.finished()
-    );
-    log::trace!("Player path: {:?}", state.player_path);
-
-    let walk_the_path_command = state.commands.front() == Some(&Command::WalkPath);
-    if walk_the_path_command {
-        state.commands.pop_front();
+    if state.keys.matches_code(KeyCode::A) {
+        state.annotation_draft = state
+            .annotation(cursor)
+            .map(|annotation| annotation.name.clone())
+            .unwrap_or_default();
+        state.window_stack.push(Window::Annotate { pos: cursor });
+        return;
+    }
+    if state.keys.matches_code(KeyCode::G) {
+        state.set_waypoint(cursor);
+        state.examine_cursor = None;
+        return;
     }
 
-    // NOTE: If the player is following a path move them one step along the path
-    if (walk_the_path_command || (state.mouse.left_is_down && visible))
-        && state.path_walking_timer.finished()
+    // NOTE: use the regular movement keys, but turn them into cursor
+    // moves instead of player commands. Everything else is ignored.
+    let mut commands = VecDeque::new();
+    process_keys(&mut state.keys, &mut commands);
+    let mut offsets = commands
+        .iter()
+        .filter_map(command_direction)
+        .collect::<Vec<_>>();
+    if gamepad.left_stick_flicked
+        && let Some(offset) = gamepad_highlighted_tile
     {
-        state.path_walking_timer.reset();
-        if let Some(destination) = state.player_path.next() {
-            let command = match destination - state.player.pos {
-                Point { x: 0, y: -1 } => Some(Command::N),
-                Point { x: 0, y: 1 } => Some(Command::S),
-                Point { x: -1, y: 0 } => Some(Command::W),
-                Point { x: 1, y: 0 } => Some(Command::E),
-
-                Point { x: -1, y: -1 } => Some(Command::NW),
-                Point { x: -1, y: 1 } => Some(Command::SW),
-                Point { x: 1, y: -1 } => Some(Command::NE),
-                Point { x: 1, y: 1 } => Some(Command::SE),
-
-                unexpected => {
-                    log::debug!("Unexpected command point: {:?}", unexpected);
-                    None
-                }
-            };
-            if let Some(command) = command {
-                log::trace!("Pushing mouse trail command: {:?}", command);
-                state.commands.push_front(command);
-            } else {
-                log::trace!("We're in the player path section, but no command is pushed.");
-            }
-        }
+        offsets.push(offset);
     }
 
-    log::trace!("Commands: {:?}", state.commands);
-    let previous_action_points = state.player.ap();
-    process_player_action(
-        &mut state.player,
-        &mut state.commands,
-        &mut state.world,
-        simulation_area,
-        &mut state.explosion_animation,
-        &mut state.rng,
-        &mut state.window_stack,
-        &mut state.player_bumped_into_a_monster,
-        display.tile_size,
-        &state.palette,
-        audio,
-    );
-    log::trace!("player action processed");
+    // NOTE: keep the cursor within the visible part of the map
+    let map_rect = Rectangle::from_size(state.map_size);
+    for offset in offsets {
+        let destination = cursor + offset;
+        if map_rect.contains_excluding_bottom_right(state.screen_pos_from_world_pos(destination)) {
+            cursor = destination;
+        }
+    }
 
-    // If the player ever picks up a dose, mark it in this variable:
-    let player_picked_up_a_dose = state.player.inventory.iter().any(item::Item::is_dose);
-    if player_picked_up_a_dose {
-        state.player_picked_up_a_dose = true;
+    if state.mouse.left_clicked && map_rect.contains_excluding_bottom_right(state.mouse.tile_pos) {
+        cursor = state.mouse_world_position();
     }
-    log::trace!("Player picked up a dose: {}", player_picked_up_a_dose);
 
-    let spent_ap_this_turn = previous_action_points > state.player.ap();
-    log::trace!("Player spent AP this turn: {}", spent_ap_this_turn);
+    state.examine_cursor = Some(cursor);
+    highlighted_tiles.push(state.screen_pos_from_world_pos(cursor));
 
-    // Place the Victory NPC if the player behaved themself.
-    if state.player.will.is_max() && !state.player.mind.is_high() && state.victory_npc_id.is_none()
-    {
-        place_victory_npc(state);
+    if previous_cursor != Some(cursor) {
+        let description = examine::describe(state, cursor).join(". ");
+        let position = event_log::relative_position(state.player.pos, cursor);
+        state
+            .event_log
+            .push(format!("Examining {position}: {description}"));
     }
+}
 
-    // Set the longest high streak
-    if spent_ap_this_turn {
-        if state.player.mind.is_high() {
-            state.player.current_high_streak += 1;
-            if state.player.current_high_streak > state.player.longest_high_streak {
-                state.player.longest_high_streak = state.player.current_high_streak;
-            }
-        } else {
-            state.player.current_high_streak = 0;
+/// Label the annotated tiles and the waypoint on the screen.
+fn show_annotations(state: &State, ui: &Ui, display: &Display) {
+    let tile_size = display.tile_size as f32;
+    let map_rect = Rectangle::from_size(state.map_size);
+    let tile_rect = |pos: Point| {
+        let screen_pos = state.screen_pos_from_world_pos(pos);
+        map_rect
+            .contains_excluding_bottom_right(screen_pos)
+            .then(|| {
+                egui::Rect::from_min_size(
+                    egui::pos2(
+                        screen_pos.x as f32 * tile_size,
+                        screen_pos.y as f32 * tile_size,
+                    ),
+                    egui::Vec2::splat(tile_size),
+                )
+            })
+    };
+    let painter = ui.painter();
+    for annotation in &state.annotations {
+        if let Some(rect) = tile_rect(annotation.pos) {
+            map::mark_tile(
+                painter,
+                rect,
+                &annotation.name,
+                state.palette.gui_text,
+                &state.palette,
+            );
         }
     }
-
-    // NOTE: The player has reached the Victory NPC. Win the game! \o/
-    if state.player.bonuses.contains(&CompanionBonus::Victory) {
-        win_the_game(state);
+    if let Some(rect) = state.waypoint.and_then(tile_rect) {
+        let color = state.palette.player(state.player.color_index);
+        map::mark_tile(painter, rect, "Waypoint", color, &state.palette);
     }
+}
 
-    state.world.explore(
-        state.player.pos,
-        formula::exploration_radius(state.player.mind),
+fn show_examine_panel(state: &State, ui: &Ui, display: &Display, cursor: Point) {
+    let tile_size = display.tile_size as f32;
+    let screen_pos = state.screen_pos_from_world_pos(cursor);
+    // NOTE: put the panel right next to the examined tile
+    let panel_pos = egui::pos2(
+        (screen_pos.x + 1) as f32 * tile_size,
+        screen_pos.y as f32 * tile_size,
     );
+    let panel_width = formula::sidebar_width_px(display.text_size) as f32;
+    egui::Area::new(egui::Id::new("Examine Panel"))
+        .fixed_pos(panel_pos)
+        .order(egui::Order::Tooltip)
+        .constrain(true)
+        .interactable(false)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_max_width(panel_width);
+                for line in examine::describe(state, cursor) {
+                    ui.label(line);
+                }
+            });
+        });
+}
+
+/// The direction a movement command goes to or `None` for the
+/// non-movement commands.
+fn command_direction(command: &Command) -> Option<Point> {
+    match command {
+        Command::N => Some(Point::new(0, -1)),
+        Command::S => Some(Point::new(0, 1)),
+        Command::W => Some(Point::new(-1, 0)),
+        Command::E => Some(Point::new(1, 0)),
+        Command::NW => Some(Point::new(-1, -1)),
+        Command::NE => Some(Point::new(1, -1)),
+        Command::SW => Some(Point::new(-1, 1)),
+        Command::SE => Some(Point::new(1, 1)),
+        _ => None,
+    }
+}
+
+/// The tile size one zoom level in or out from `tile_size`.
+///
+/// Zooming past the largest or the smallest available size keeps it.
+fn zoomed_tile_size(tile_size: i32, zoom_in: bool) -> i32 {
+    let mut sizes = crate::engine::AVAILABLE_TILE_SIZES;
+    sizes.sort_unstable();
+    let Some(index) = sizes.iter().position(|&size| size == tile_size) else {
+        return tile_size;
+    };
+    let index = if zoom_in {
+        (index + 1).min(sizes.len() - 1)
+    } else {
+        index.saturating_sub(1)
+    };
+    sizes[index]
 }
 
 fn process_keys(keys: &mut Keys, commands: &mut VecDeque<Command>) {
@@ -1839,75 +1707,6 @@ pub fn inventory_key(kind: item::Kind) -> Option<u8> {
     None
 }
 
-fn kill_monster(monster_position: Point, world: &mut World, audio: &mut Audio) {
-    let invincible = world
-        .monster_on_pos(monster_position)
-        .is_some_and(|m| m.invincible);
-    if invincible {
-        // It's invincible: no-op
-    } else {
-        if let Some(monster) = world.monster_on_pos(monster_position) {
-            log::debug!("Killing monster: {:?}", monster);
-            monster.dead = true;
-            audio.play_sound(Effect::MonsterHit, Duration::from_millis(0));
-        }
-        world.remove_monster(monster_position);
-    }
-}
-
-fn use_dose(
-    player: &mut player::Player,
-    explosion_animation: &mut Option<Box<dyn AreaOfEffect>>,
-    item: item::Item,
-    palette: &Palette,
-    audio: &mut Audio,
-) {
-    use crate::{item::Kind::*, player::Modifier::*};
-    log::debug!("Using dose");
-    audio.play_sound(Effect::Explosion, Duration::from_millis(0));
-    if let Intoxication { state_of_mind, .. } = item.modifier {
-        let radius = if state_of_mind <= 100 { 4 } else { 6 };
-        player.take_effect(item.modifier);
-        let animation: Box<dyn AreaOfEffect> = match item.kind {
-            Dose | StrongDose => Box::new(animation::SquareExplosion::new(
-                player.pos,
-                radius,
-                2,
-                palette.explosion,
-            )),
-            CardinalDose => Box::new(animation::CardinalExplosion::new(
-                player.pos,
-                radius,
-                2,
-                palette.explosion,
-                palette.shattering_explosion,
-            )),
-            DiagonalDose => Box::new(animation::DiagonalExplosion::new(
-                player.pos,
-                radius,
-                2,
-                palette.explosion,
-                palette.shattering_explosion,
-            )),
-            Food => {
-                wtf!("Tried to use `Food` in `use_dose`. This shouldn't happen.");
-                Box::new(animation::SquareExplosion::new(
-                    player.pos,
-                    0,
-                    0,
-                    palette.explosion,
-                ))
-            }
-        };
-        *explosion_animation = Some(animation);
-    } else {
-        wtf!(
-            "Tried to use a dose but `item.modifier` was not of the `Intoxication` variant: {:?}",
-            item.modifier
-        );
-    }
-}
-
 fn show_exit_stats(stats: &Stats) {
     log::info!(
         "\nSlowest update durations: {:?}\n",
@@ -1935,295 +1734,79 @@ fn show_exit_stats(stats: &Stats) {
         stats.mean_drawcalls()
     );
 
+    log::info!(
+        "\nMean frame duration: {} ms\nLongest frame duration: {} ms",
+        stats.mean_frame(),
+        stats.longest_frame().as_secs_f32() * 1000.0
+    );
+
     log::info!("Mean FPS: {}", stats.mean_fps());
     log::info!("Lowest FPS: {}", stats.lowest_fps());
 }
 
-fn verify_states(expected: &state::Verification, actual: &state::Verification) {
-    let mut valid = true;
-
-    if expected.tick_id != actual.tick_id {
-        log::error!(
-            "Expected tick_id: {}, actual: {}",
-            expected.tick_id,
-            actual.tick_id
-        );
-        valid = false;
-    }
-
-    if expected.turn != actual.turn {
-        log::error!("Expected turn: {}, actual: {}", expected.turn, actual.turn);
-        valid = false;
-    }
-
-    if expected.chunk_count != actual.chunk_count {
-        log::error!(
-            "Expected chunks: {}, actual: {}",
-            expected.chunk_count,
-            actual.chunk_count
-        );
-        valid = false;
-    }
-
-    if expected.player_pos != actual.player_pos {
-        log::error!(
-            "Expected player position: {}, actual: {}",
-            expected.player_pos,
-            actual.player_pos
-        );
-        valid = false;
-    }
-
-    if expected.monsters.len() != actual.monsters.len() {
-        log::error!(
-            "Expected monster count: {}, actual: {}",
-            expected.monsters.len(),
-            actual.monsters.len()
-        );
-        valid = false;
-    }
-
-    if expected.monsters != actual.monsters {
-        let expected_monsters: HashMap<Point, (Point, monster::Kind)> = expected
-            .monsters
-            .iter()
-            .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster)))
-            .collect();
-        let actual_monsters: HashMap<Point, (Point, monster::Kind)> = actual
-            .monsters
-            .iter()
-            .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster)))
-            .collect();
-
-        for (pos, expected) in &expected_monsters {
-            match actual_monsters.get(pos) {
-                Some(actual) => {
-                    if expected != actual {
-                        log::error!(
-                            "Monster at {} differ. Expected: {:?}, \
-                             actual: {:?}",
-                            pos,
-                            expected,
-                            actual
-                        );
-                        valid = false;
-                    }
-                }
-                None => {
-                    log::error!(
-                        "Monster expected at {}: {:?}, but it's not \
-                         there.",
-                        pos,
-                        expected
-                    );
-                    valid = false;
-                }
-            }
-        }
-
-        for (pos, actual) in &actual_monsters {
-            if !expected_monsters.contains_key(pos) {
-                log::error!("There is an unexpected monster at: {}: {:?}.", pos, actual);
-                valid = false;
-            }
-        }
-    }
+pub fn create_new_game_state(state: &State, new_challenge: Challenge) -> State {
+    create_new_game_state_with_seed(state, util::random_seed(), new_challenge)
+}
 
-    // Use if+panic rather than `assert_eq`. The latter prints out both objects and they can be MASSIVE.
-    // We've got a more targeted expected/actual printout above so all we need here is to crash.
-    #[allow(clippy::panic)]
-    if !valid {
-        panic!("Validation failed!");
-    }
+/// Start today's daily challenge: a fixed seed and challenge for everyone.
+pub fn create_daily_game_state(state: &State, date: &str) -> State {
+    let mut new_state =
+        create_new_game_state_with_seed(state, daily::seed(date), daily::challenge());
+    new_state.daily = Some(date.to_string());
+    new_state
 }
 
-pub fn create_new_game_state(state: &State, new_challenge: Challenge) -> State {
+fn create_new_game_state_with_seed(state: &State, seed: u32, new_challenge: Challenge) -> State {
+    let event_log = state.event_log.for_new_game();
     let mut state = State::new_game(
         state.world_size,
         state.map_size,
         state.panel_width,
         state.exit_after,
         state.debug,
+        seed,
         state::generate_replay_path(),
         new_challenge,
         state.palette,
     );
+    state.event_log = event_log;
     state.generate_world();
     state
 }
 
-fn place_victory_npc(state: &mut State) -> Point {
-    log::info!("Generating the Victory NPC!");
-    let mut distance_range = formula::VICTORY_NPC_DISTANCE;
-    // NOTE: Compute path to Victory NPC that is reachable by the
-    // player. This may take several attempts. Leave the position
-    // immutable at the end.
-    let mut vnpc_pos;
-    let mut attempts = 250;
-    let blockers = Blocker::WALL | Blocker::MONSTER;
-    loop {
-        if attempts <= 0 {
-            // TODO: generate VNPC at a shorter distance instead of crashing?
-            log::warn!("Could not find a viable Victory NPC position in 250 tries.");
-            let min = distance_range.0 - 20;
-            let max = distance_range.1 - 20;
-            if min > 5 && max > 5 {
-                distance_range = InclusiveRange(min, max);
-                attempts = 20;
-                log::info!("Reduced VNPC spawn range to: {:?}.", distance_range);
-            } else {
-                log::warn!(
-                    "Could not find a viable Victory NPC position anywhere! Winning game instead."
-                );
-                state.player.bonuses.push(monster::CompanionBonus::Victory);
-                win_the_game(state);
-                return state.player.pos;
-            }
-        } else {
-            attempts -= 1;
-        }
-
-        // NOTE: this is a little convoluted. We test if the Victory
-        // NPC position is walkable. And if it's not, we try other
-        // positions in its immediate vicinity instead of generating a
-        // new candidate position via `formula::victory_npc_position`.
-        //
-        // We do this, because the walkability test requires we have a
-        // World Chunk in place and generating these can be expensive.
-        // So if we picked a completely random position every time, we
-        // could end up generating a lot of chunks for no immediate
-        // reason.
-        //
-        // What we do instead is generate one Chunk for the given
-        // position and then try nearby areas (which will be
-        // overwhelmingly likely in the same Chunk).
-        vnpc_pos = formula::victory_npc_position(&mut state.rng, state.player.pos, distance_range);
-        log::info!("Trying to find test NPC position {:?}", vnpc_pos);
-        state.world.ensure_chunk_at_pos(vnpc_pos);
-        if let Some(pos) = walkable_place_nearby(&state.world, vnpc_pos, blockers, state.player.pos)
-        {
-            log::info!("Position {:?} is walkable!", pos);
-            vnpc_pos = pos;
-            for cell_pos in point::Line::new(state.player.pos, vnpc_pos) {
-                state.world.ensure_chunk_at_pos(cell_pos);
-            }
-        } else {
-            log::warn!(
-                "Failed to find empty place around the candidate VNPC position {:?}",
-                vnpc_pos
-            );
-            continue;
-        }
-
-        log::info!(
-            "player pos: {:?}, vnpc pos: {:?}",
-            state.player.pos,
-            vnpc_pos
-        );
-        // TODO: make sure the world chunks exist before trying to find path
-        let check_irresistible = false;
-        let path_to_vnpc = pathfinding::Path::find(
-            state.player.pos,
-            vnpc_pos,
-            &state.world,
-            blockers,
-            state.player.pos,
-            state.player.will.to_int(),
-            check_irresistible,
-            formula::PATHFINDING_VNPC_REACHABILITY_LIMIT,
-            &pathfinding::direct_cost,
-        );
-        if path_to_vnpc.is_empty() {
-            log::warn!("Failed to find path from player to Victory NPC!")
-        } else {
-            log::info!("Path to Victory NPC takes {} steps", path_to_vnpc.len());
-            break;
-        }
-    }
-    let vnpc_pos = vnpc_pos;
-
-    if let Some(prev_npc_id) = state.victory_npc_id.take() {
-        log::warn!("Replacing an existing NPC! {:?}", prev_npc_id);
-        state.world.remove_monster_by_id(prev_npc_id);
+/// Add the just finished game to the run history.
+fn record_run(state: &State) {
+    // NOTE: replays and cheating would skew the statistics
+    if state.replay || state.cheating {
+        return;
     }
-
-    // NOTE: Uncover the map leading to the Victory NPC position
-    let positions = point::Line::new(state.player.pos, vnpc_pos);
-    for cell_pos in positions {
-        state.world.ensure_chunk_at_pos(cell_pos);
-        let display_half_size = state.map_size / 2;
-        // NOTE: make sure every cell that will be shown has a chunk.
-        //
-        // If we didn't do this, we would get blank places when the line would cross a boundary
-        // of two chunks, but the surrounding chunks were not brought in.
-        state
-            .world
-            .ensure_chunk_at_pos(cell_pos + (display_half_size.x, display_half_size.y));
-        state
-            .world
-            .ensure_chunk_at_pos(cell_pos + (-display_half_size.x, display_half_size.y));
-        state
-            .world
-            .ensure_chunk_at_pos(cell_pos + (display_half_size.x, -display_half_size.y));
-        state
-            .world
-            .ensure_chunk_at_pos(cell_pos + (-display_half_size.x, -display_half_size.y));
-        state.world.always_visible(cell_pos, 2);
-        state.world.explore(cell_pos, 4);
-    }
-    state.world.explore(vnpc_pos, 5);
-    state.world.always_visible(vnpc_pos, 2);
-
-    if let Some(chunk) = state.world.chunk_mut(vnpc_pos) {
-        let mut monster = monster::Monster::new(monster::Kind::Npc, vnpc_pos, state.challenge);
-        monster.companion_bonus = Some(CompanionBonus::Victory);
-        // NOTE: The NPCs have the same colour range as the player,
-        // but let's always pick a colour that's different from the
-        // current player's one.
-        monster.npc_color_index = {
-            let mut index: usize = 0;
-            for _ in 0..5 {
-                let pick = state
-                    .rng
-                    .range_inclusive(0, state.palette.player.len() as i32 - 1)
-                    as usize;
-                if pick != state.player.color_index {
-                    index = pick;
-                    break;
-                }
-            }
-            index
-        };
-
-        monster.ai_state = ai::AIState::NoOp;
-        let id = chunk.add_monster(monster);
-        state.victory_npc_id = Some(id);
+    let record = history::RunRecord::from_state(state);
+    if let Err(err) = history::append(&record) {
+        log::error!("Could not record the game in the run history: {}", err);
     }
-
-    // NOTE: Scroll to the Victory NPC position
-    let ms = if state.replay_full_speed { 1000 } else { 2000 };
-    state.pos_timer = Timer::new(Duration::from_millis(ms));
-    state.old_screen_pos = state.screen_position_in_world;
-    state.new_screen_pos = vnpc_pos;
-
-    vnpc_pos
-}
-
-fn win_the_game(state: &mut State) {
-    state.side = Side::Victory;
-    state.game_session = GameSession::Ended;
-    state.uncovered_map = true;
-    state.selected_endgame_window_action = Some(endgame::Action::NewGame);
-    state.window_stack.push(Window::Endgame);
+    daily::record_result(state);
 }
 
-/// Return a point close to the given one that is walkable.
-fn walkable_place_nearby(
-    world: &World,
-    pos: Point,
-    blockers: Blocker,
-    player_pos: Point,
-) -> Option<Point> {
-    // Radius `2` means the central point and the eight surrounding ones.
-    point::SquareArea::new(pos, 2).find(|&point| world.walkable(point, blockers, player_pos))
+/// Unlock the `achievement` and let the player know unless they've
+/// unlocked it before.
+fn unlock_achievement(state: &mut State, achievement: Achievement) {
+    if state.replay || state.cheating || state.challenge.practice {
+        return;
+    }
+    let mut unlocked = achievements::Unlocked::load();
+    if !unlocked.unlock(achievement, util::unix_timestamp()) {
+        return;
+    }
+    log::info!("Unlocked achievement: {:?}", achievement);
+    if let Err(err) = unlocked.save() {
+        log::error!("Could not save the achievements: {}", err);
+    }
+    state
+        .event_log
+        .push(format!("Achievement unlocked: {}", achievement.title()));
+    state.window_stack.push(window::timed_message_box(
+        "Achievement Unlocked",
+        &format!("{}\n\n{}", achievement.title(), achievement.description()),
+        Duration::from_secs(5),
+    ));
 }
@@ -30,6 +30,12 @@ pub struct Gamepad {
     /// B or Circle
     pub east: bool,
 
+    /// The left trigger (L2 or LT)
+    pub left_trigger: bool,
+
+    /// The right trigger (R2 or RT)
+    pub right_trigger: bool,
+
     /// The button next to the D-pad cluster on the left (Share)
     pub select: bool,
 
@@ -104,6 +110,9 @@ pub fn process_gamepad_events(gilrs: &mut Gilrs, gamepad: &mut Gamepad, dt: Dura
                 Button::North => gamepad.north = true,
                 Button::West => gamepad.west = true,
 
+                Button::LeftTrigger2 => gamepad.left_trigger = true,
+                Button::RightTrigger2 => gamepad.right_trigger = true,
+
                 Button::Start => gamepad.start = true,
                 Button::Select => gamepad.select = true,
 
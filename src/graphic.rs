@@ -1,8 +1,18 @@
-use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error};
+
+use serde::{
+    Deserialize, Serialize,
+    de::{
+        IntoDeserializer,
+        value::{Error as DeError, StrDeserializer},
+    },
+};
+
+use toml_edit::Document as TomlDocument;
 
 pub const TILE_SIZE: i32 = 10;
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Graphic {
     #[default]
     Empty,
@@ -97,9 +107,111 @@ pub enum Graphic {
     Signpost,
 }
 
-pub fn tilemap_coords_px(_tilesize: u32, graphic: Graphic) -> Option<(i32, i32)> {
+/// Layout of the graphics tilemap texture.
+///
+/// The default matches the tilemap embedded in the binary
+/// (`assets/tiles.png`). An asset pack can provide its own tilemap
+/// along with an index that moves any of the graphics elsewhere or
+/// uses a different tile size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tileset {
+    /// Size (in pixels) of a single tile in the tilemap texture.
+    pub tile_size: i32,
+    /// Width and height of the whole tilemap texture in pixels.
+    pub texture_size_px: (i32, i32),
+    /// Graphics placed somewhere else than in the default tilemap.
+    /// The positions are in tiles, not pixels.
+    overrides: HashMap<Graphic, (i32, i32)>,
+}
+
+impl Default for Tileset {
+    fn default() -> Self {
+        Self {
+            tile_size: TILE_SIZE,
+            texture_size_px: (
+                crate::engine::TILEMAP_TEXTURE_WIDTH as i32,
+                crate::engine::TILEMAP_TEXTURE_HEIGHT as i32,
+            ),
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+impl Tileset {
+    /// Build the tileset from a TOML index that accompanies an
+    /// external tilemap image. The index looks like this:
+    ///
+    /// ```toml
+    /// tile_size = 16
+    ///
+    /// [graphics]
+    /// Tree1 = [3, 1]
+    /// Dose = [0, 11]
+    /// ```
+    ///
+    /// Positions are in tiles. Any graphic that's not listed keeps its
+    /// default position.
+    pub fn from_index(index: &str, texture_size_px: (i32, i32)) -> Result<Self, Box<dyn Error>> {
+        let toml = index.parse::<TomlDocument>()?;
+        let mut tileset = Self {
+            texture_size_px,
+            ..Self::default()
+        };
+
+        if let Some(tile_size) = toml["tile_size"].as_integer() {
+            if tile_size <= 0 {
+                throw!("The tileset `tile_size` must be a positive number.");
+            }
+            tileset.tile_size = i32::try_from(tile_size)?;
+        }
+
+        if let Some(graphics) = toml["graphics"].as_table() {
+            for (name, value) in graphics.iter() {
+                let deserializer: StrDeserializer<'_, DeError> = name.into_deserializer();
+                let graphic = match Graphic::deserialize(deserializer) {
+                    Ok(graphic) => graphic,
+                    Err(err) => {
+                        log::warn!("Tileset: unknown graphic `{}`: {}", name, err);
+                        continue;
+                    }
+                };
+                let coords = value.as_array().and_then(|array| {
+                    let x = array.get(0)?.as_integer()?;
+                    let y = array.get(1)?.as_integer()?;
+                    Some((i32::try_from(x).ok()?, i32::try_from(y).ok()?))
+                });
+                match coords {
+                    Some(coords) => {
+                        tileset.overrides.insert(graphic, coords);
+                    }
+                    None => log::warn!(
+                        "Tileset: `{}` must be a `[x, y]` pair of tile positions.",
+                        name
+                    ),
+                }
+            }
+        }
+
+        Ok(tileset)
+    }
+
+    /// Position of the graphic in the tilemap texture in pixels.
+    ///
+    /// Returns `None` if the graphic is not in the tilemap. The
+    /// callers should fall back to the glyph rendering then.
+    pub fn coords_px(&self, graphic: Graphic) -> Option<(i32, i32)> {
+        let coords = match self.overrides.get(&graphic) {
+            Some(&coords) => Some(coords),
+            None => default_tile_coords(graphic),
+        };
+        coords.map(|(tile_x, tile_y)| (tile_x * self.tile_size, tile_y * self.tile_size))
+    }
+}
+
+/// Position (in tiles) of the graphic in the embedded tilemap.
+fn default_tile_coords(graphic: Graphic) -> Option<(i32, i32)> {
     use Graphic::*;
-    let coords = match graphic {
+    match graphic {
         Empty => None,
 
         Tree1 => Some((3, 1)),
@@ -189,8 +301,7 @@ pub fn tilemap_coords_px(_tilesize: u32, graphic: Graphic) -> Option<(i32, i32)>
         Bat => Some((3 + 8, 15 - 4)),
 
         Signpost => Some((12 - 1, 8)),
-    };
-    coords.map(|(tile_x, tile_y)| (tile_x * TILE_SIZE, tile_y * TILE_SIZE))
+    }
 }
 
 impl From<Graphic> for char {
@@ -289,3 +400,46 @@ impl From<Graphic> for char {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Graphic, TILE_SIZE, Tileset};
+
+    use std::error::Error;
+
+    #[test]
+    fn test_index_overrides_coords() -> Result<(), Box<dyn Error>> {
+        let index = "
+tile_size = 16
+
+[graphics]
+Dose = [2, 3]
+";
+        let tileset = Tileset::from_index(index, (160, 160))?;
+        assert_eq!(16, tileset.tile_size);
+        assert_eq!((160, 160), tileset.texture_size_px);
+        assert_eq!(Some((32, 48)), tileset.coords_px(Graphic::Dose));
+        // Graphics missing from the index keep their default position
+        assert_eq!(Some((16, 16)), tileset.coords_px(Graphic::Ground1));
+        assert_eq!(None, tileset.coords_px(Graphic::Empty));
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_unknown_graphic_is_skipped() -> Result<(), Box<dyn Error>> {
+        let index = "
+[graphics]
+NotAGraphic = [1, 1]
+Tree1 = [0, 0]
+";
+        let tileset = Tileset::from_index(index, (100, 100))?;
+        assert_eq!(TILE_SIZE, tileset.tile_size);
+        assert_eq!(Some((0, 0)), tileset.coords_px(Graphic::Tree1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_invalid_tile_size() {
+        assert!(Tileset::from_index("tile_size = 0", (100, 100)).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    formula, monster,
+    player::CauseOfDeath,
+    state::{Challenge, Side, State},
+    util,
+};
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A summary of a single finished game.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// When the game ended, in seconds since the Unix epoch.
+    pub finished_at: u64,
+    pub seed: u32,
+    pub turns: i32,
+    pub victory: bool,
+    pub cause_of_death: Option<CauseOfDeath>,
+    pub perpetrator: Option<monster::Kind>,
+    pub longest_high_streak: i32,
+    pub longest_sober_streak: i32,
+    pub challenge: Challenge,
+    pub replay_path: Option<PathBuf>,
+    /// How many turns the player took back in the practice mode.
+    #[serde(default)]
+    pub undos: u32,
+}
+
+impl RunRecord {
+    pub fn from_state(state: &State) -> Self {
+        let victory = state.side == Side::Victory;
+        Self {
+            finished_at: util::unix_timestamp(),
+            seed: state.seed,
+            turns: state.turn,
+            victory,
+            cause_of_death: if victory {
+                None
+            } else {
+                formula::cause_of_death(&state.player)
+            },
+            perpetrator: state
+                .player
+                .perpetrator
+                .as_ref()
+                .map(|monster| monster.kind),
+            longest_high_streak: state.player.longest_high_streak,
+            longest_sober_streak: state.player.longest_sober_streak,
+            challenge: state.challenge,
+            replay_path: state.replay_path.clone(),
+            undos: state.undos,
+        }
+    }
+
+    /// The date the game ended on in the `YYYY-MM-DD` format (UTC).
+    pub fn date(&self) -> String {
+        util::format_date(self.finished_at)
+    }
+
+    /// A short description of how the game ended.
+    pub fn outcome(&self) -> String {
+        use CauseOfDeath::*;
+        if self.victory {
+            return "Won".to_string();
+        }
+        let perpetrator = self.perpetrator.map(monster::Kind::name);
+        match (self.cause_of_death, perpetrator) {
+            (Some(Exhausted), Some(name)) => format!("Exhausted by {name}"),
+            (Some(Exhausted), None) => "Exhausted".to_string(),
+            (Some(Overdosed), _) => "Overdosed".to_string(),
+            (Some(LostWill), Some(name)) => format!("Lost Will to {name}"),
+            (Some(LostWill), None) => "Lost Will".to_string(),
+            (Some(Killed), Some(name)) => format!("Defeated by {name}"),
+            (Some(Killed), None) | (None, _) => "Lost".to_string(),
+        }
+    }
+}
+
+/// Aggregated statistics over all the recorded games.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub games_played: usize,
+    pub victories: usize,
+    pub average_turns: f32,
+    /// The monster that ended the most games and how many.
+    pub most_common_perpetrator: Option<(monster::Kind, usize)>,
+    pub longest_sober_streak: i32,
+}
+
+impl Summary {
+    /// NOTE: the practice games are left out. The player could undo
+    /// their way through them.
+    pub fn new(records: &[RunRecord]) -> Self {
+        let records: Vec<&RunRecord> = records
+            .iter()
+            .filter(|record| !record.challenge.practice)
+            .collect();
+        if records.is_empty() {
+            return Self::default();
+        }
+
+        let total_turns: i64 = records.iter().map(|record| i64::from(record.turns)).sum();
+
+        let mut perpetrators = BTreeMap::new();
+        for kind in records
+            .iter()
+            .filter(|record| !record.victory)
+            .filter_map(|record| record.perpetrator)
+        {
+            *perpetrators.entry(kind).or_insert(0) += 1;
+        }
+        // NOTE: `max_by_key` returns the last maximum element so
+        // ties go to the last `Kind`. That's arbitrary, but stable.
+        let most_common_perpetrator = perpetrators.into_iter().max_by_key(|&(_, count)| count);
+
+        Self {
+            games_played: records.len(),
+            victories: records.iter().filter(|record| record.victory).count(),
+            average_turns: total_turns as f32 / records.len() as f32,
+            most_common_perpetrator,
+            longest_sober_streak: records
+                .iter()
+                .map(|record| record.longest_sober_streak)
+                .max()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Percentage of the games won.
+    pub fn win_rate(&self) -> f32 {
+        if self.games_played == 0 {
+            0.0
+        } else {
+            self.victories as f32 * 100.0 / self.games_played as f32
+        }
+    }
+}
+
+/// Read all the recorded games.
+///
+/// Missing file means no games were played yet. Lines we can't parse
+/// (e.g. written by an incompatible version) are skipped.
+pub fn load() -> Vec<RunRecord> {
+    let path = crate::paths::history_file();
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                log::error!(
+                    "Could not open the run history at '{}': {}",
+                    path.display(),
+                    err
+                );
+            }
+            return vec![];
+        }
+    };
+
+    let mut records = vec![];
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                log::error!("Could not read the run history: {}", err);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => log::warn!("Skipping run history line {}: {}", index + 1, err),
+        }
+    }
+    records
+}
+
+/// Add the `record` at the end of the run history file.
+pub fn append(record: &RunRecord) -> Result<(), Box<dyn Error>> {
+    crate::paths::ensure_dir(&crate::paths::data_dir());
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(crate::paths::history_file())?;
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{line}")?;
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(turns: i32, victory: bool, perpetrator: Option<monster::Kind>) -> RunRecord {
+        RunRecord {
+            finished_at: 0,
+            seed: 1,
+            turns,
+            victory,
+            cause_of_death: if victory {
+                None
+            } else {
+                Some(CauseOfDeath::Killed)
+            },
+            perpetrator,
+            longest_high_streak: 0,
+            longest_sober_streak: turns / 2,
+            challenge: Challenge::default(),
+            replay_path: None,
+            undos: 0,
+        }
+    }
+
+    #[test]
+    fn test_empty_summary() {
+        let summary = Summary::new(&[]);
+        assert_eq!(summary.games_played, 0);
+        assert_eq!(summary.win_rate(), 0.0);
+        assert_eq!(summary.most_common_perpetrator, None);
+    }
+
+    #[test]
+    fn test_summary() {
+        use monster::Kind::*;
+        let records = [
+            record(100, false, Some(Depression)),
+            record(200, false, Some(Anxiety)),
+            record(300, true, None),
+            record(400, false, Some(Depression)),
+        ];
+        let summary = Summary::new(&records);
+        assert_eq!(summary.games_played, 4);
+        assert_eq!(summary.victories, 1);
+        assert_eq!(summary.win_rate(), 25.0);
+        assert_eq!(summary.average_turns, 250.0);
+        assert_eq!(summary.most_common_perpetrator, Some((Depression, 2)));
+        assert_eq!(summary.longest_sober_streak, 200);
+    }
+
+    #[test]
+    fn test_summary_skips_practice_games() {
+        let mut practice = record(100, true, None);
+        practice.challenge.practice = true;
+        practice.undos = 3;
+        let records = [record(300, false, None), practice];
+        let summary = Summary::new(&records);
+        assert_eq!(summary.games_played, 1);
+        assert_eq!(summary.victories, 0);
+        assert_eq!(summary.average_turns, 300.0);
+    }
+
+    #[test]
+    fn test_date() {
+        let mut record = record(1, true, None);
+        record.finished_at = 1_700_000_000;
+        assert_eq!(record.date(), "2023-11-14");
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let record = record(42, false, Some(monster::Kind::Hunger));
+        let json = serde_json::to_string(&record).unwrap_or_default();
+        let parsed: Option<RunRecord> = serde_json::from_str(&json).ok();
+        assert_eq!(parsed, Some(record));
+    }
+}
@@ -47,6 +47,13 @@ impl Kind {
             StrongDose => "Strong Dose",
         }
     }
+
+    pub fn is_dose(self) -> bool {
+        match self {
+            Dose | StrongDose | CardinalDose | DiagonalDose => true,
+            Food => false,
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -81,10 +88,7 @@ pub struct Item {
 
 impl Item {
     pub fn is_dose(&self) -> bool {
-        match self.kind {
-            Dose | StrongDose | CardinalDose | DiagonalDose => true,
-            Food => false,
-        }
+        self.kind.is_dose()
     }
 
     pub fn graphic(&self) -> Graphic {
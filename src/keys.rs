@@ -90,6 +90,9 @@ pub enum KeyCode {
     Enter,
     Space,
     Esc,
+    Backspace,
+    Plus,
+    Minus,
     QuestionMark,
     Menu,
 }
@@ -26,19 +26,25 @@ macro_rules! wtf {
 
 }
 
+pub mod achievements;
 pub mod ai;
 pub mod animation;
+pub mod assets;
 pub mod audio;
 pub mod blocker;
 pub mod color;
+pub mod daily;
 pub mod engine;
 pub mod error;
+pub mod event_log;
+pub mod examine;
 pub mod formula;
 pub mod game;
 pub mod gamepad;
 pub mod generators;
 pub mod graphic;
 pub mod graphics;
+pub mod history;
 pub mod item;
 pub mod keys;
 pub mod level;
@@ -46,16 +52,22 @@ pub mod metadata;
 pub mod monster;
 pub mod palette;
 pub mod pathfinding;
+pub mod paths;
 pub mod player;
 pub mod point;
 pub mod random;
 pub mod ranged_int;
 pub mod rect;
 pub mod render;
+#[cfg(feature = "replay")]
+pub mod replay;
 pub mod settings;
+pub mod simulation;
 pub mod state;
 pub mod stats;
+pub mod timeline;
 pub mod timer;
+pub mod trace;
 pub mod ui;
 pub mod util;
 pub mod window;
@@ -78,6 +90,10 @@ use simplelog::Config;
 // So instead, we've narrowed the sidebar to 17 tiles (just enough to
 // make every withdrawal step show up). That means we don't maintain
 // the perfect aspect ratio, but it seems to be good enough.
+//
+// NOTE: this is only the initial map size. The game fills the window
+// with the map once it's running and the tile size (zoom) decides how
+// many tiles that is.
 pub const DISPLAYED_MAP_SIZE: i32 = 30;
 
 pub const PANEL_WIDTH: i32 = 17;
@@ -88,6 +104,7 @@ fn run_glutin(
     window_title: &str,
     settings_store: settings::FileSystemStore,
     state: state::State,
+    assets: assets::AssetPack,
 ) {
     log::info!("Using the glutin backend");
 
@@ -105,6 +122,7 @@ fn run_glutin(
             window_title,
             settings_store,
             Box::new(state),
+            assets,
         );
         if let Err(err) = result {
             log::error!("Error occured in the glutin main_loop: {err:?}");
@@ -121,6 +139,7 @@ fn run_sdl3(
     window_title: &str,
     settings_store: settings::FileSystemStore,
     state: state::State,
+    assets: assets::AssetPack,
 ) {
     log::info!("Using the SDL3 backend");
 
@@ -131,6 +150,7 @@ fn run_sdl3(
             window_title,
             settings_store,
             Box::new(state),
+            assets,
         );
         if let Err(err) = result {
             log::error!("Error occured in the SDL3 main_loop: {err:?}");
@@ -147,6 +167,7 @@ fn run_sdl2(
     window_title: &str,
     settings_store: settings::FileSystemStore,
     state: state::State,
+    assets: assets::AssetPack,
 ) {
     log::info!("Using the SDL2 backend");
 
@@ -157,6 +178,7 @@ fn run_sdl2(
             window_title,
             settings_store,
             Box::new(state),
+            assets,
         );
         dbg!(&result);
         if let Err(err) = result {
@@ -168,12 +190,126 @@ fn run_sdl2(
     log::error!("The \"sdl2-backend\" feature was not compiled in.");
 }
 
+/// Replay every finished daily challenge and check it matches the
+/// leaderboard.
+#[cfg(feature = "replay")]
+fn verify_daily_leaderboard() -> Result<(), Box<dyn std::error::Error>> {
+    let leaderboard = daily::Leaderboard::load();
+    let mut failures = 0;
+    for entry in leaderboard.ranked() {
+        match daily::verify(entry) {
+            Ok(()) => log::info!("Daily challenge {}: verified", entry.date),
+            Err(err) => {
+                log::error!("Daily challenge {}: {}", entry.date, err);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        throw!(&format!(
+            "{failures} daily challenge result(s) could not be verified."
+        ));
+    }
+    Ok(())
+}
+
+/// Replay the files (or every replay in the directories) headlessly
+/// and print a report for each.
+///
+/// NOTE: the replays that don't store their challenge are played
+/// with the default one rather than the player's settings. The result
+/// mustn't depend on who runs this.
+#[cfg(feature = "replay")]
+fn verify_replays(
+    paths: impl Iterator<Item = std::path::PathBuf>,
+    on_divergence: state::OnDivergence,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(&path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.retain(|entry| entry.is_file());
+            entries.sort();
+            for entry in entries {
+                if paths::is_replay_file(&entry) {
+                    files.push(entry);
+                } else {
+                    println!("{}\n  skipped: not a replay", entry.display());
+                }
+            }
+        } else {
+            files.push(path);
+        }
+    }
+
+    let mut failures = 0;
+    for file in &files {
+        match replay::verify(file, state::Challenge::default(), on_divergence) {
+            Ok(report) => {
+                println!("{report}");
+                if !report.passed() {
+                    failures += 1;
+                }
+            }
+            Err(err) => {
+                println!("{}\n  result: error\n    {}", file.display(), err);
+                failures += 1;
+            }
+        }
+    }
+    println!(
+        "Verified {} replay(s): {} passed, {} failed.",
+        files.len(),
+        files.len() - failures,
+        failures
+    );
+
+    if failures > 0 {
+        throw!(&format!("{failures} replay(s) could not be verified."));
+    }
+    Ok(())
+}
+
+/// Play the replay under the current rules and write it out again
+/// with fresh verifications. Like `verify_replays`, this ignores the
+/// player's challenge settings.
+#[cfg(feature = "replay")]
+fn rerecord_replay(
+    path: &std::path::Path,
+    output: &std::path::Path,
+    debug: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if output.exists() {
+        throw!(&format!(
+            "The output file '{}' exists already. Not going to overwrite it, aborting.",
+            output.display()
+        ));
+    }
+    let report = replay::rerecord(path, output, state::Challenge::default(), debug)?;
+    println!("{report}");
+    if let replay::Outcome::Crashed(_) = report.outcome {
+        throw!(&format!(
+            "Could not re-record '{}', the new replay is incomplete.",
+            path.display()
+        ));
+    }
+    println!("Re-recorded into: {}", output.display());
+    Ok(())
+}
+
 pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     use crate::settings::Store;
-    use clap::{App, Arg};
+    use clap::{App, Arg, SubCommand};
     use simplelog::{CombinedLogger, LevelFilter, SharedLogger, SimpleLogger, WriteLogger};
     use std::fs::File;
 
+    let override_help = settings::OVERRIDES
+        .iter()
+        .map(|(key, _flag)| format!("Override the `{key}` setting for this run only."))
+        .collect::<Vec<_>>();
+
     let mut app = App::new(metadata::TITLE)
         .version(metadata::VERSION)
         .author(metadata::AUTHORS)
@@ -193,7 +329,41 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             Arg::with_name("debug")
                 .long("debug")
                 .help("Debug mode. Output detailed messages and replay logs. This can be really verbose and take up massive amounts of space."),
+        )
+        .arg(
+            Arg::with_name("assets")
+                .long("assets")
+                .value_name("DIR")
+                .help("Load tiles, fonts and sounds from this directory. Anything missing there falls back to the built-in assets.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Read and store the settings in this file instead of the default one.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("event-log")
+                .long("event-log")
+                .value_name("FILE")
+                .help("Write the description of what's happening in the game into this file (or stdout if it's `-`). Useful for screen readers and text-to-speech tools. Pass `--quiet` too when using stdout.")
+                .takes_value(true),
+        );
+
+    // NOTE: every setting can be overridden with a flag for a single
+    // run. The same goes for the `DOSE_RESPONSE_*` environment
+    // variables, but the flags take precedence.
+    for ((key, flag), help) in settings::OVERRIDES.iter().zip(&override_help) {
+        app = app.arg(
+            Arg::with_name(key)
+                .long(flag)
+                .value_name("VALUE")
+                .help(help)
+                .takes_value(true),
         );
+    }
 
     if cfg!(feature = "cheating") {
         app = app
@@ -242,6 +412,48 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
                     .long("replay-file")
                     .value_name("FILE")
                     .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("verify-daily")
+                    .help("Verify every daily challenge result on the leaderboard by replaying it headlessly and exit.")
+                    .long("verify-daily"),
+            )
+            .arg(
+                Arg::with_name("soft-replay")
+                    .help("Keep playing the replay when it stops matching the recorded game. The differences are logged as warnings. Useful for replays recorded before a gameplay change.")
+                    .long("soft-replay"),
+            )
+            .subcommand(
+                SubCommand::with_name("verify-replay")
+                    .about("Replay the files headlessly and report whether they still play out the way they were recorded. Exits with a non-zero status if any of them doesn't.")
+                    .arg(
+                        Arg::with_name("files")
+                            .value_name("FILE")
+                            .help("The replay files to verify. Every replay in a directory is verified (the `replay-*` and `*.gz` files), the other files are skipped.")
+                            .multiple(true)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("soft")
+                            .long("soft")
+                            .help("Don't stop at the first divergence, play the replays through to see how they end."),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("rerecord-replay")
+                    .about("Play the replay's inputs under the current rules and record them into a new replay with fresh verifications. Use this to update replays after a deliberate gameplay change.")
+                    .arg(
+                        Arg::with_name("replay")
+                            .value_name("REPLAY")
+                            .help("The replay to re-record.")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("output")
+                            .value_name("OUTPUT")
+                            .help("Where to write the new replay. It's compressed if the name ends with `.gz`.")
+                            .required(true),
+                    ),
             );
     }
 
@@ -274,7 +486,8 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         // 	.set_time_format_custom(time::macros::format_description!(""))
         .build();
 
-    if let Ok(logfile) = File::create("dose-response.log") {
+    paths::ensure_dir(&paths::log_dir());
+    if let Ok(logfile) = File::create(paths::log_file()) {
         loggers.push(WriteLogger::new(
             LevelFilter::Trace,
             file_log_config,
@@ -322,13 +535,54 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         crate::engine::AVAILABLE_BACKENDS
     );
 
-    let mut settings_store = settings::FileSystemStore::new();
+    paths::migrate_legacy_files();
+
+    #[cfg(feature = "replay")]
+    if matches.is_present("verify-daily") {
+        return verify_daily_leaderboard();
+    }
+
+    let mut settings_store = match matches.value_of("config") {
+        Some(path) => settings::FileSystemStore::with_path(path.into()),
+        None => settings::FileSystemStore::new(),
+    };
+    settings_store.override_from_env();
+    for (key, _flag) in settings::OVERRIDES {
+        if let Some(value) = matches.value_of(key) {
+            settings_store.override_value(key, value);
+        }
+    }
     let mut settings = settings_store.load();
     let backend = settings.backend.clone();
     let challenge = settings.challenge();
     let palette = settings.palette();
 
-    let state = if let Some(replay) = matches.value_of("replay") {
+    #[cfg(feature = "replay")]
+    if let Some(verify_matches) = matches.subcommand_matches("verify-replay") {
+        let files = verify_matches.values_of("files").into_iter().flatten();
+        let on_divergence = if verify_matches.is_present("soft") {
+            state::OnDivergence::Continue
+        } else {
+            state::OnDivergence::Stop
+        };
+        return verify_replays(files.map(std::path::PathBuf::from), on_divergence);
+    }
+
+    #[cfg(feature = "replay")]
+    if let Some(rerecord_matches) = matches.subcommand_matches("rerecord-replay")
+        && let (Some(replay), Some(output)) = (
+            rerecord_matches.value_of("replay"),
+            rerecord_matches.value_of("output"),
+        )
+    {
+        return rerecord_replay(
+            std::path::Path::new(replay),
+            std::path::Path::new(output),
+            matches.is_present("debug"),
+        );
+    }
+
+    let mut state = if let Some(replay) = matches.value_of("replay") {
         if matches.is_present("replay-file") {
             throw!(
                 "The `replay-file` option can only be used during regular \
@@ -347,7 +601,7 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         };
 
         let replay_path = std::path::Path::new(replay);
-        state::State::replay_game(
+        let mut state = state::State::replay_game(
             WORLD_SIZE,
             point::Point::from_i32(DISPLAYED_MAP_SIZE),
             PANEL_WIDTH,
@@ -359,7 +613,11 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             matches.is_present("debug"),
             challenge,
             palette,
-        )?
+        )?;
+        if matches.is_present("soft-replay") {
+            state.on_divergence = state::OnDivergence::Continue;
+        }
+        state
     } else {
         if matches.is_present("replay-full-speed") {
             throw!(
@@ -385,6 +643,7 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             PANEL_WIDTH,
             matches.is_present("exit-after"),
             matches.is_present("debug"),
+            util::random_seed(),
             replay_file,
             challenge,
             palette,
@@ -409,6 +668,20 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         state
     };
 
+    match matches.value_of("event-log") {
+        Some("-") => state.event_log = event_log::EventLog::with_output(std::io::stdout()),
+        Some(path) => match File::create(path) {
+            Ok(file) => state.event_log = event_log::EventLog::with_output(file),
+            Err(err) => throw!(&format!("Could not create the event log '{path}': {err}")),
+        },
+        None => {}
+    }
+
+    let assets = match matches.value_of("assets") {
+        Some(dir) => assets::AssetPack::new(std::path::Path::new(dir)),
+        None => assets::AssetPack::embedded(),
+    };
+
     let background = palette.unexplored_background;
     let game_title = metadata::TITLE;
 
@@ -421,9 +694,9 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     match backend.as_str() {
-        "glutin" => run_glutin(background, game_title, settings_store, state),
-        "sdl3" => run_sdl3(background, game_title, settings_store, state),
-        "sdl2" => run_sdl2(background, game_title, settings_store, state),
+        "glutin" => run_glutin(background, game_title, settings_store, state, assets),
+        "sdl3" => run_sdl3(background, game_title, settings_store, state, assets),
+        "sdl2" => run_sdl2(background, game_title, settings_store, state, assets),
         _ => {
             log::error!("Unknown backend: {}", backend);
         }
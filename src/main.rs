@@ -7,6 +7,7 @@ fn main() {
         }
         Err(err) => {
             log::error!("Reached a top-level error: {}", err);
+            std::process::exit(1);
         }
     };
 }
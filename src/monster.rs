@@ -68,6 +68,18 @@ impl Kind {
             Signpost => false,
         }
     }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Anxiety => "Anxiety",
+            Depression => "Depression",
+            Hunger => "Hunger",
+            Shadows => "Shadows",
+            Voices => "Voices",
+            Npc => "NPC",
+            Signpost => "signpost",
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -261,15 +273,7 @@ impl Monster {
     }
 
     pub fn name(&self) -> &str {
-        match self.kind {
-            Anxiety => "Anxiety",
-            Depression => "Depression",
-            Hunger => "Hunger",
-            Shadows => "Shadows",
-            Voices => "Voices",
-            Npc => "NPC",
-            Signpost => "signpost",
-        }
+        self.kind.name()
     }
 }
 
@@ -1,6 +1,8 @@
 use crate::{
-    blocker,
+    blocker::{self, Blocker},
     point::Point,
+    rect::Rectangle,
+    trace,
     world::{TileContents, World},
 };
 
@@ -11,27 +13,172 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
+mod hierarchy;
+
+pub use self::hierarchy::ChunkGraph;
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Path {
     path: Vec<Point>,
 }
 
 impl Path {
+    /// Find the path using a one-off `PathfindingContext`.
+    ///
+    /// Anything that searches repeatedly (e.g. every turn) should
+    /// keep a context around and call `PathfindingContext::find`
+    /// instead.
     pub fn find(
         from: Point,
         to: Point,
         world: &World,
         blockers: blocker::Blocker,
         player_position: Point,
-        player_will: i32,
-        // Are we interested in knowing about irresistible doses in the path?
-        check_irresistible: bool,
         calculation_limit: i32,
-        cost: &dyn Fn(Point, Point, TileContents) -> f32,
+        cost: &dyn PathCost,
     ) -> Self {
-        // TODO: if the cost was a struct/trait rather than a `fn`, we
-        // could express the "are we interested in knowing about
-        // irresistible doses?" there.
+        PathfindingContext::new().find(
+            from,
+            to,
+            world,
+            blockers,
+            player_position,
+            calculation_limit,
+            cost,
+        )
+    }
+
+    /// The number of steps to necessary to reach the destination. If
+    /// no path was found, it is `0`.
+    pub fn len(&self) -> usize {
+        self.path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.path.clear();
+    }
+}
+
+impl Iterator for Path {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.path.pop()
+    }
+}
+
+/// The pathfinding cost of moving to the next Point.
+///
+/// The higher the cost, the harder to move to the tile. The
+/// `tile_contents` variable can help determine the underlying cost.
+///
+/// The destination is expected to be walkable (`cost` always returns
+/// a finite value).
+pub trait PathCost {
+    fn cost(&self, current: Point, next: Point, tile_contents: TileContents) -> f32;
+
+    /// The player's will if we're interested in knowing about
+    /// irresistible doses in the path. Looking for them is expensive
+    /// so nobody does by default.
+    fn player_will(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// Every walkable tile costs the same.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DirectCost;
+
+impl PathCost for DirectCost {
+    fn cost(&self, _current: Point, _next: Point, tile_contents: TileContents) -> f32 {
+        match tile_contents {
+            TileContents::Monster => 1.0,
+            TileContents::Item => 1.0,
+            TileContents::Irresistible => 1.0,
+            TileContents::Empty => 1.0,
+        }
+    }
+}
+
+/// Monsters walk around each other if they can.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MonsterCost;
+
+impl PathCost for MonsterCost {
+    fn cost(&self, _current: Point, _next: Point, tile_contents: TileContents) -> f32 {
+        match tile_contents {
+            TileContents::Monster => 2.1,
+            TileContents::Item => 1.0,
+            TileContents::Irresistible => 1.0,
+            TileContents::Empty => 1.0,
+        }
+    }
+}
+
+/// The player avoids walking past the doses they couldn't resist.
+#[derive(Copy, Clone, Debug)]
+pub struct PlayerCost {
+    pub will: i32,
+}
+
+impl PathCost for PlayerCost {
+    fn cost(&self, _current: Point, _next: Point, tile_contents: TileContents) -> f32 {
+        match tile_contents {
+            TileContents::Monster => 1.0,
+            TileContents::Item => 1.0,
+            TileContents::Irresistible => 4.0,
+            TileContents::Empty => 1.0,
+        }
+    }
+
+    fn player_will(&self) -> Option<i32> {
+        Some(self.will)
+    }
+}
+
+/// Buffers shared by the path searches so they don't allocate.
+///
+/// The search state lives in dense arrays with one block for every
+/// world chunk a search has visited. The blocks are allocated the
+/// first time a chunk is visited and reused after that. Each search
+/// gets a new `generation` so the entries left over from the
+/// previous ones don't need clearing.
+#[derive(Debug, Default)]
+pub struct PathfindingContext {
+    chunk_size: i32,
+    generation: u32,
+    /// Where each chunk's block starts in the arrays below.
+    chunk_offsets: HashMap<Point, usize>,
+    /// The most recently looked up chunk and its offset. The
+    /// neighbouring tiles are almost always in the same chunk.
+    last_chunk: Option<(Point, usize)>,
+    /// The `generation` that last reached the tile.
+    reached: Vec<u32>,
+    cost_so_far: Vec<f32>,
+    came_from: Vec<Point>,
+    frontier: BinaryHeap<State>,
+}
+
+impl PathfindingContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn find(
+        &mut self,
+        from: Point,
+        to: Point,
+        world: &World,
+        blockers: blocker::Blocker,
+        player_position: Point,
+        calculation_limit: i32,
+        cost: &dyn PathCost,
+    ) -> Path {
+        let _span = trace::span("pathfinding");
         if from == to {
             return Path { path: vec![] };
         }
@@ -44,47 +191,20 @@ impl Path {
             return Path { path: vec![to] };
         }
 
-        let neighbors = |current: Point| {
-            assert!(world.within_bounds(current));
-            let dp: [Point; 9] = [
-                (-1, -1).into(),
-                (-1, 0).into(),
-                (-1, 1).into(),
-                (0, -1).into(),
-                (0, 0).into(),
-                (0, 1).into(),
-                (1, -1).into(),
-                (1, 0).into(),
-                (1, 1).into(),
-            ];
-            dp.iter()
-                .map(|&d| current + d)
-                .filter(|&point| {
-                    world.within_bounds(point) && world.walkable(point, blockers, player_position)
-                })
-                .map(|point| {
-                    (
-                        point,
-                        world.tile_contents(point, player_will, check_irresistible),
-                    )
-                })
-                .collect::<Vec<_>>()
-        };
+        let player_will = cost.player_will();
+        let check_irresistible = player_will.is_some();
+        let player_will = player_will.unwrap_or_default();
 
-        let mut frontier = BinaryHeap::new();
-        frontier.push(State {
+        self.start_search(world.chunk_size());
+        self.frontier.push(State {
             position: from,
             cost: 0.0,
         });
-        let mut came_from = HashMap::new();
-        let mut cost_so_far = HashMap::new();
-
-        came_from.insert(from, None);
-        cost_so_far.insert(from, 0.0);
+        self.reach(from, 0.0, from);
 
         let mut calculation_steps = 0;
 
-        while let Some(current) = frontier.pop() {
+        while let Some(current) = self.frontier.pop() {
             if current.position == to {
                 break;
             }
@@ -92,21 +212,22 @@ impl Path {
                 break;
             }
             calculation_steps += 1;
-            let neigh = neighbors(current.position);
-            for &(next, tile_contents) in &neigh {
-                assert!((current.position.x - next.x).abs() <= 1);
-                assert!((current.position.y - next.y).abs() <= 1);
-                let new_cost =
-                    cost_so_far[&current.position] + cost(current.position, next, tile_contents);
-                let val = cost_so_far.entry(next).or_insert(f32::MAX);
-                if new_cost < *val {
-                    *val = new_cost;
+            assert!(world.within_bounds(current.position));
+            let current_cost = self.cost_so_far(current.position);
+            for d in NEIGHBOURS {
+                let next = current.position + d;
+                if !(world.within_bounds(next) && world.walkable(next, blockers, player_position)) {
+                    continue;
+                }
+                let tile_contents = world.tile_contents(next, player_will, check_irresistible);
+                let new_cost = current_cost + cost.cost(current.position, next, tile_contents);
+                if new_cost < self.cost_so_far(next) {
+                    self.reach(next, new_cost, current.position);
                     let priority = new_cost + heuristic(to, next);
-                    frontier.push(State {
+                    self.frontier.push(State {
                         position: next,
                         cost: priority,
                     });
-                    came_from.insert(next, Some(current.position));
                 }
             }
         }
@@ -115,17 +236,13 @@ impl Path {
             let mut current = to;
             let mut path_buffer = vec![current];
             while current != from {
-                match came_from.get(&current) {
-                    Some(&Some(new_current)) => {
+                match self.came_from(current) {
+                    Some(new_current) => {
                         current = new_current;
                         if current != from {
                             path_buffer.push(current);
                         }
                     }
-                    Some(&None) => log::error!(
-                        "Pathfinding: Every point except for the initial \
-                         one (`from`) one should be some."
-                    ),
                     None => {
                         path_buffer = vec![];
                         break;
@@ -139,63 +256,268 @@ impl Path {
         Path { path }
     }
 
-    /// The number of steps to necessary to reach the destination. If
-    /// no path was found, it is `0`.
-    pub fn len(&self) -> usize {
-        self.path.len()
+    fn start_search(&mut self, chunk_size: i32) {
+        // Drop the blocks of the chunks the player has long left
+        // behind rather than growing forever.
+        if chunk_size != self.chunk_size || self.chunk_offsets.len() > MAX_CONTEXT_CHUNKS {
+            *self = Self {
+                chunk_size,
+                ..Self::default()
+            };
+        }
+        self.frontier.clear();
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            self.reached.fill(0);
+            self.generation = 1;
+        }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.path.is_empty()
+    /// The position of `pos` in the dense arrays. Allocates the
+    /// chunk's block if it's not there yet.
+    fn index(&mut self, pos: Point) -> usize {
+        let size = self.chunk_size;
+        let chunk = Point::new(pos.x.div_euclid(size), pos.y.div_euclid(size));
+        let offset = match self.last_chunk {
+            Some((last_chunk, offset)) if last_chunk == chunk => offset,
+            _ => {
+                let len = self.reached.len();
+                let offset = *self.chunk_offsets.entry(chunk).or_insert(len);
+                if offset == len {
+                    let new_len = len + (size * size) as usize;
+                    self.reached.resize(new_len, 0);
+                    self.cost_so_far.resize(new_len, f32::MAX);
+                    self.came_from.resize(new_len, Point::zero());
+                }
+                self.last_chunk = Some((chunk, offset));
+                offset
+            }
+        };
+        offset + (pos.y.rem_euclid(size) * size + pos.x.rem_euclid(size)) as usize
     }
 
-    pub fn clear(&mut self) {
-        self.path.clear();
+    fn reach(&mut self, pos: Point, cost: f32, came_from: Point) {
+        let index = self.index(pos);
+        self.reached[index] = self.generation;
+        self.cost_so_far[index] = cost;
+        self.came_from[index] = came_from;
     }
-}
 
-impl Iterator for Path {
-    type Item = Point;
+    fn cost_so_far(&mut self, pos: Point) -> f32 {
+        let index = self.index(pos);
+        if self.reached[index] == self.generation {
+            self.cost_so_far[index]
+        } else {
+            f32::MAX
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.path.pop()
+    fn came_from(&mut self, pos: Point) -> Option<Point> {
+        let index = self.index(pos);
+        (self.reached[index] == self.generation).then(|| self.came_from[index])
     }
 }
 
-/// Calculate the pathfinding cost of moving to the next Point.
+/// How many chunks' worth of buffers a `PathfindingContext` keeps
+/// before starting over.
+const MAX_CONTEXT_CHUNKS: usize = 64;
+
+/// The tiles we can step to, in the order they're explored. The
+/// order decides the ties between equally good paths so it must not
+/// change.
+const NEIGHBOURS: [Point; 8] = [
+    Point { x: -1, y: -1 },
+    Point { x: -1, y: 0 },
+    Point { x: -1, y: 1 },
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+    Point { x: 1, y: -1 },
+    Point { x: 1, y: 0 },
+    Point { x: 1, y: 1 },
+];
+
+/// The cost of walking from every tile of an area to the nearest of
+/// its goals.
 ///
-/// The higher the cost, the harder to move to the tile. The
-/// `tile_contents` variable can help determine the underlying cost.
+/// Everyone walking towards the same goals shares the one map and
+/// just keeps stepping to the neighbour with the lowest value
+/// (`next_step`). Building the map once is much cheaper than running
+/// a separate search for every monster.
 ///
-/// The destination is expected to be walkable (this function always
-/// returns a finite cost).
-pub fn direct_cost(_current: Point, _next: Point, tile_contents: TileContents) -> f32 {
-    match tile_contents {
-        TileContents::Monster => 1.0,
-        TileContents::Item => 1.0,
-        TileContents::Irresistible => 1.0,
-        TileContents::Empty => 1.0,
-    }
+/// A map with multiple goals works as a desire map (go for the
+/// closest dose, food or NPC) and `make_flee_map` turns it into one
+/// that leads away from the goals instead.
+#[derive(Clone, Debug)]
+pub struct DijkstraMap {
+    area: Rectangle,
+    /// The map values, row by row. Unreachable tiles are `f32::MAX`.
+    values: Vec<f32>,
+    frontier: BinaryHeap<State>,
 }
 
-pub fn monster_cost(_current: Point, _next: Point, tile_contents: TileContents) -> f32 {
-    match tile_contents {
-        TileContents::Monster => 2.1,
-        TileContents::Item => 1.0,
-        TileContents::Irresistible => 1.0,
-        TileContents::Empty => 1.0,
+impl Default for DijkstraMap {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub fn player_cost(_current: Point, _next: Point, tile_contents: TileContents) -> f32 {
-    match tile_contents {
-        TileContents::Monster => 1.0,
-        TileContents::Item => 1.0,
-        TileContents::Irresistible => 4.0,
-        TileContents::Empty => 1.0,
+impl DijkstraMap {
+    pub fn new() -> Self {
+        Self {
+            area: Rectangle::from_size(Point::zero()),
+            values: vec![],
+            frontier: BinaryHeap::new(),
+        }
+    }
+
+    /// Fill the map with the cost of walking from every tile in
+    /// `area` to the nearest of the `goals`. Goals outside the area
+    /// are ignored and so are the paths that would leave it.
+    pub fn build(
+        &mut self,
+        area: Rectangle,
+        goals: &[Point],
+        world: &World,
+        blockers: Blocker,
+        player_position: Point,
+        cost: &dyn PathCost,
+    ) {
+        let _span = trace::span("pathfinding: dijkstra map");
+        self.area = area;
+        self.values.clear();
+        self.values
+            .resize((area.width() * area.height()) as usize, f32::MAX);
+        for &goal in goals {
+            if let Some(index) = self.index(goal) {
+                self.values[index] = 0.0;
+            }
+        }
+        self.relax(world, blockers, player_position, cost);
+    }
+
+    /// Turn the map into one that leads away from its goals.
+    ///
+    /// Rather than just running to the furthest tile, the result
+    /// prefers the escape routes that get the furthest overall so
+    /// whoever follows it doesn't get cornered.
+    pub fn make_flee_map(
+        &mut self,
+        world: &World,
+        blockers: Blocker,
+        player_position: Point,
+        cost: &dyn PathCost,
+    ) {
+        for value in &mut self.values {
+            if *value != f32::MAX {
+                *value *= FLEE_COEFFICIENT;
+            }
+        }
+        self.relax(world, blockers, player_position, cost);
+    }
+
+    /// The map value at `pos`. `None` if it's outside of the map or
+    /// there's no way to get to any goal from there.
+    pub fn value(&self, pos: Point) -> Option<f32> {
+        self.index(pos)
+            .map(|index| self.values[index])
+            .filter(|&value| value != f32::MAX)
+    }
+
+    /// The neighbour of `from` with the lowest value that is lower
+    /// than `from`'s own and that isn't blocked.
+    ///
+    /// `None` means there's no way forward. Either `from` is already
+    /// at a goal, it's not on the map or all the better tiles are
+    /// blocked (e.g. by other monsters).
+    pub fn next_step(
+        &self,
+        from: Point,
+        world: &World,
+        blockers: Blocker,
+        player_position: Point,
+    ) -> Option<Point> {
+        let mut best = (from, self.value(from)?);
+        for d in NEIGHBOURS {
+            let next = from + d;
+            if let Some(value) = self.value(next)
+                && value < best.1
+                && world.walkable(next, blockers, player_position)
+            {
+                best = (next, value);
+            }
+        }
+        (best.0 != from).then_some(best.0)
+    }
+
+    fn index(&self, pos: Point) -> Option<usize> {
+        if self.area.contains_inclusive(pos) {
+            let offset = pos - self.area.top_left();
+            Some((offset.y * self.area.width() + offset.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Lower every tile's value to the cheapest way of reaching a
+    /// tile whose value is already set.
+    fn relax(
+        &mut self,
+        world: &World,
+        blockers: Blocker,
+        player_position: Point,
+        cost: &dyn PathCost,
+    ) {
+        let player_will = cost.player_will();
+        let check_irresistible = player_will.is_some();
+        let player_will = player_will.unwrap_or_default();
+
+        self.frontier.clear();
+        for (pos, &value) in self.area.points().zip(&self.values) {
+            if value != f32::MAX {
+                self.frontier.push(State {
+                    position: pos,
+                    cost: value,
+                });
+            }
+        }
+
+        while let Some(current) = self.frontier.pop() {
+            let Some(current_index) = self.index(current.position) else {
+                continue;
+            };
+            if current.cost > self.values[current_index] {
+                // We've found a cheaper way here since this was queued
+                continue;
+            }
+            let tile_contents =
+                world.tile_contents(current.position, player_will, check_irresistible);
+            for d in NEIGHBOURS {
+                let next = current.position + d;
+                let Some(next_index) = self.index(next) else {
+                    continue;
+                };
+                if !world.walkable(next, blockers, player_position) {
+                    continue;
+                }
+                // NOTE: the map is walked from `next` towards
+                // `current`, i.e. the opposite of how we've got here.
+                let new_cost = current.cost + cost.cost(next, current.position, tile_contents);
+                if new_cost < self.values[next_index] {
+                    self.values[next_index] = new_cost;
+                    self.frontier.push(State {
+                        position: next,
+                        cost: new_cost,
+                    });
+                }
+            }
+        }
     }
 }
 
+/// How much a flee map prefers the long escape routes to simply
+/// getting away from the goals. See `DijkstraMap::make_flee_map`.
+const FLEE_COEFFICIENT: f32 = -1.2;
+
 fn heuristic(destination: Point, next: Point) -> f32 {
     ((destination.x - next.x).abs() + (destination.y - next.y).abs()) as f32
 }
@@ -233,11 +555,12 @@ mod test {
     // Panics in test code are fine. They're just a different kind of assert.
     #![allow(clippy::panic)]
 
-    use super::Path;
+    use super::{DijkstraMap, DirectCost, Path, PathfindingContext};
     use crate::{
         blocker::Blocker,
         player::{Mind, PlayerInfo},
         point::Point,
+        rect::Rectangle,
         world::World,
     };
 
@@ -334,21 +657,158 @@ mod test {
 
     fn find_path(board: &Board, blockers: Blocker, calculation_limit: i32) -> Path {
         let player_position = Point::new(0, 0);
-        let will = 2;
-        let check_irresistible = false;
         Path::find(
             board.start,
             board.destination,
             &board.world,
             blockers,
             player_position,
-            will,
-            check_irresistible,
             calculation_limit,
-            &super::direct_cost,
+            &DirectCost,
         )
     }
 
+    #[test]
+    fn test_reused_context() {
+        let board = make_board(
+            "
+....x......
+.s..x......
+....x......
+........d..
+",
+        );
+        let mut context = PathfindingContext::new();
+        let search = |context: &mut PathfindingContext, from: Point, to: Point| {
+            context
+                .find(
+                    from,
+                    to,
+                    &board.world,
+                    Blocker::WALL,
+                    Point::new(0, 0),
+                    50,
+                    &DirectCost,
+                )
+                .collect::<Vec<_>>()
+        };
+        let expected = find_path(&board, Blocker::WALL, 50).collect::<Vec<_>>();
+        assert_eq!(expected.len(), 7);
+        assert_eq!(
+            search(&mut context, board.start, board.destination),
+            expected
+        );
+        // A search from elsewhere must not see the previous one's leftovers:
+        assert_eq!(
+            search(&mut context, board.destination, Point::new(5, 0)),
+            vec![Point::new(7, 2), Point::new(6, 1), Point::new(5, 0)]
+        );
+        assert_eq!(
+            search(&mut context, board.start, board.destination),
+            expected
+        );
+    }
+
+    fn walk_down(board: &Board, map: &DijkstraMap, from: Point) -> Vec<Point> {
+        let mut walked = vec![];
+        let mut current = from;
+        while let Some(next) = map.next_step(current, &board.world, Blocker::WALL, Point::new(0, 0))
+        {
+            walked.push(next);
+            current = next;
+        }
+        walked
+    }
+
+    #[test]
+    fn test_dijkstra_map_leads_to_the_goal() {
+        let board = make_board(
+            "
+....x......
+.s..x......
+....x......
+........d..
+",
+        );
+        let mut map = DijkstraMap::new();
+        map.build(
+            Rectangle::from_size(Point::new(11, 4)),
+            &[board.destination],
+            &board.world,
+            Blocker::WALL,
+            Point::new(0, 0),
+            &DirectCost,
+        );
+        assert_eq!(map.value(board.destination), Some(0.0));
+        assert_eq!(map.value(board.start), Some(7.0));
+        assert_eq!(map.value(Point::new(4, 0)), None);
+        assert_eq!(map.value(Point::new(20, 20)), None);
+
+        let walked = walk_down(&board, &map, board.start);
+        assert_eq!(walked.len(), 7);
+        assert_eq!(walked.last(), Some(&board.destination));
+    }
+
+    #[test]
+    fn test_dijkstra_map_nearest_goal() {
+        let board = make_board(
+            "
+d.........
+..........
+..........
+..........
+......s...
+",
+        );
+        let other_goal = Point::new(9, 4);
+        let mut map = DijkstraMap::new();
+        map.build(
+            Rectangle::from_size(Point::new(10, 5)),
+            &[board.destination, other_goal],
+            &board.world,
+            Blocker::WALL,
+            Point::new(0, 0),
+            &DirectCost,
+        );
+        assert_eq!(map.value(board.start), Some(3.0));
+        assert_eq!(
+            walk_down(&board, &map, board.start).last(),
+            Some(&other_goal)
+        );
+    }
+
+    #[test]
+    fn test_flee_map() {
+        let board = make_board(
+            "
+..........
+..........
+....sd....
+..........
+..........
+",
+        );
+        let mut map = DijkstraMap::new();
+        map.build(
+            Rectangle::from_size(Point::new(10, 5)),
+            &[board.destination],
+            &board.world,
+            Blocker::WALL,
+            Point::new(0, 0),
+            &DirectCost,
+        );
+        map.make_flee_map(&board.world, Blocker::WALL, Point::new(0, 0), &DirectCost);
+        let walked = walk_down(&board, &map, board.start);
+        assert!(!walked.is_empty());
+        let mut distance = board.start.tile_distance(board.destination);
+        for pos in walked {
+            let new_distance = pos.tile_distance(board.destination);
+            assert!(new_distance >= distance);
+            distance = new_distance;
+        }
+        assert_eq!(distance, 5);
+    }
+
     #[test]
     fn test_neighbor() {
         let board = make_board(
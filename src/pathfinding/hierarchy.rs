@@ -0,0 +1,486 @@
+use super::{DijkstraMap, DirectCost, Path, PathCost, PathfindingContext, State};
+
+use crate::{blocker::Blocker, point::Point, rect::Rectangle, trace, world::World};
+
+use std::collections::{BinaryHeap, HashMap};
+
+/// A map of the ways between the world chunks for finding long paths
+/// (Hierarchical Pathfinding, HPA*).
+///
+/// `PathfindingContext::find` goes tile by tile so it has to give up
+/// after a few hundred steps. Here, the tiles where one chunk leads
+/// into another are the nodes of a much smaller graph. Each chunk
+/// knows how far apart its nodes are. We find the path through this
+/// graph first and then walk it tile by tile only between the
+/// consecutive nodes.
+///
+/// The graph only knows about the terrain (`Blocker::WALL`) and it's
+/// built lazily as the searches reach new chunks.
+#[derive(Debug, Default)]
+pub struct ChunkGraph {
+    chunk_size: i32,
+    chunks: HashMap<Point, ChunkNodes>,
+    map: DijkstraMap,
+}
+
+/// The nodes of a single chunk.
+#[derive(Debug)]
+struct ChunkNodes {
+    /// Which of the neighbouring chunks (in `SIDES` order) existed
+    /// when the nodes were calculated. A chunk that appears later
+    /// adds new entrances.
+    neighbours: [bool; 4],
+    /// The nodes of this chunk, the nodes they can reach and how far
+    /// away they are. That's the other nodes of the same chunk and
+    /// their twin across the chunk border.
+    edges: HashMap<Point, Vec<(Point, f32)>>,
+}
+
+/// The directions of the chunk's neighbours.
+const SIDES: [Point; 4] = [
+    Point { x: 0, y: -1 },
+    Point { x: 1, y: 0 },
+    Point { x: 0, y: 1 },
+    Point { x: -1, y: 0 },
+];
+
+/// An entrance at least this wide gets a node at both of its ends
+/// rather than just one in the middle.
+const WIDE_ENTRANCE: usize = 6;
+
+impl ChunkGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Find the path from `from` to `to`, no matter how far apart
+    /// they are.
+    ///
+    /// `calculation_limit` is the number of graph nodes to try
+    /// before giving up, not tiles. The `blockers` and `cost` only
+    /// apply to the tile by tile parts of the search.
+    pub fn find(
+        &mut self,
+        from: Point,
+        to: Point,
+        world: &World,
+        blockers: Blocker,
+        player_position: Point,
+        calculation_limit: i32,
+        context: &mut PathfindingContext,
+        cost: &dyn PathCost,
+    ) -> Path {
+        let _span = trace::span("pathfinding: chunk graph");
+        if from == to || !world.walkable(to, blockers, player_position) {
+            return Path { path: vec![] };
+        }
+        if world.chunk_size() != self.chunk_size {
+            *self = Self {
+                chunk_size: world.chunk_size(),
+                ..Self::default()
+            };
+        }
+
+        let waypoints = self.waypoints(from, to, world, calculation_limit);
+        if waypoints.is_empty() {
+            return Path { path: vec![] };
+        }
+
+        // NOTE: the waypoints within a chunk are never more than a
+        // chunk away but a path between them can still wind about.
+        let segment_limit = self.chunk_size * self.chunk_size;
+        let mut steps = vec![];
+        let mut current = from;
+        for waypoint in waypoints {
+            if current.tile_distance(waypoint) == 1 {
+                if !world.walkable(waypoint, blockers, player_position) {
+                    return Path { path: vec![] };
+                }
+                steps.push(waypoint);
+            } else {
+                let segment = context.find(
+                    current,
+                    waypoint,
+                    world,
+                    blockers,
+                    player_position,
+                    segment_limit,
+                    cost,
+                );
+                if segment.is_empty() {
+                    return Path { path: vec![] };
+                }
+                steps.extend(segment);
+            }
+            current = waypoint;
+        }
+
+        steps.reverse();
+        Path { path: steps }
+    }
+
+    /// No chunk has been added to the graph yet.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Forget everything known about the chunk with `pos` (and its
+    /// neighbours who share its borders). Call this when the terrain
+    /// there changes.
+    pub fn invalidate(&mut self, pos: Point) {
+        if self.chunk_size == 0 {
+            return;
+        }
+        let chunk = self.chunk_position(pos);
+        self.chunks.remove(&chunk);
+        for side in SIDES {
+            self.chunks.remove(&(chunk + side * self.chunk_size));
+        }
+    }
+
+    /// The graph nodes on the way from `from` to `to`, ending with
+    /// `to`. Empty if there's no way through.
+    fn waypoints(
+        &mut self,
+        from: Point,
+        to: Point,
+        world: &World,
+        calculation_limit: i32,
+    ) -> Vec<Point> {
+        let from_chunk = self.chunk_position(from);
+        let to_chunk = self.chunk_position(to);
+        let mut start_edges = self.edges_within_chunk(from, from_chunk, world);
+        if from_chunk == to_chunk
+            && let Some(direct_cost) = self.map.value(to)
+        {
+            start_edges.push((to, direct_cost));
+        }
+        // NOTE: `from` can be a graph node itself.
+        start_edges.extend(self.edges(from, world));
+        let goal_edges = self.edges_within_chunk(to, to_chunk, world);
+        let goal_costs: HashMap<Point, f32> = goal_edges.iter().copied().collect();
+
+        let mut frontier = BinaryHeap::new();
+        let mut came_from = HashMap::new();
+        let mut cost_so_far = HashMap::new();
+        frontier.push(State {
+            position: from,
+            cost: 0.0,
+        });
+        cost_so_far.insert(from, 0.0);
+
+        let mut calculation_steps = 0;
+        let mut found = false;
+        while let Some(current) = frontier.pop() {
+            if current.position == to {
+                found = true;
+                break;
+            }
+            if calculation_steps >= calculation_limit {
+                break;
+            }
+            calculation_steps += 1;
+
+            let current_cost = cost_so_far[&current.position];
+            let mut neighbours = if current.position == from {
+                start_edges.clone()
+            } else {
+                self.edges(current.position, world)
+            };
+            if let Some(&goal_cost) = goal_costs.get(&current.position) {
+                neighbours.push((to, goal_cost));
+            }
+            for (next, edge_cost) in neighbours {
+                let new_cost = current_cost + edge_cost;
+                if new_cost < cost_so_far.get(&next).copied().unwrap_or(f32::MAX) {
+                    cost_so_far.insert(next, new_cost);
+                    came_from.insert(next, current.position);
+                    frontier.push(State {
+                        position: next,
+                        cost: new_cost + next.tile_distance(to) as f32,
+                    });
+                }
+            }
+        }
+
+        let mut waypoints = vec![];
+        if found {
+            let mut current = to;
+            while current != from {
+                waypoints.push(current);
+                current = came_from[&current];
+            }
+            waypoints.reverse();
+        }
+        waypoints
+    }
+
+    /// The nodes of `chunk` reachable from the `pos` tile inside it
+    /// and how far they are. If `pos` is a node itself, it's left
+    /// out.
+    ///
+    /// This leaves the distances from `pos` in `self.map`.
+    fn edges_within_chunk(&mut self, pos: Point, chunk: Point, world: &World) -> Vec<(Point, f32)> {
+        let nodes = self.nodes(chunk, world);
+        let area = self.chunk_area(chunk);
+        self.map
+            .build(area, &[pos], world, Blocker::WALL, pos, &DirectCost);
+        let mut edges = nodes
+            .iter()
+            .filter(|&&node| node != pos)
+            .filter_map(|&node| self.map.value(node).map(|value| (node, value)))
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|&(node, _)| (node.x, node.y));
+        edges
+    }
+
+    /// The edges leading from the graph node at `pos`.
+    fn edges(&mut self, pos: Point, world: &World) -> Vec<(Point, f32)> {
+        let chunk = self.chunk_position(pos);
+        self.ensure_chunk(chunk, world);
+        self.chunks
+            .get(&chunk)
+            .and_then(|chunk| chunk.edges.get(&pos))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn nodes(&mut self, chunk: Point, world: &World) -> Vec<Point> {
+        self.ensure_chunk(chunk, world);
+        let mut nodes = self
+            .chunks
+            .get(&chunk)
+            .map(|chunk| chunk.edges.keys().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        nodes.sort_by_key(|node| (node.x, node.y));
+        nodes
+    }
+
+    /// Calculate the chunk's nodes unless they're already known.
+    fn ensure_chunk(&mut self, chunk: Point, world: &World) {
+        let neighbours = SIDES.map(|side| world.chunk(chunk + side * self.chunk_size).is_some());
+        if self
+            .chunks
+            .get(&chunk)
+            .is_some_and(|nodes| nodes.neighbours == neighbours)
+        {
+            return;
+        }
+
+        let mut entrances = vec![];
+        for side in SIDES {
+            entrances.extend(self.entrances(chunk, side, world));
+        }
+        entrances.sort_by_key(|&(node, _)| (node.x, node.y));
+        entrances.dedup_by_key(|&mut (node, _)| node);
+
+        let area = self.chunk_area(chunk);
+        let mut edges = HashMap::new();
+        for &(node, twin) in &entrances {
+            self.map
+                .build(area, &[node], world, Blocker::WALL, node, &DirectCost);
+            let mut node_edges = vec![(twin, 1.0)];
+            node_edges.extend(
+                entrances
+                    .iter()
+                    .filter(|&&(other, _)| other != node)
+                    .filter_map(|&(other, _)| self.map.value(other).map(|value| (other, value))),
+            );
+            edges.insert(node, node_edges);
+        }
+
+        self.chunks.insert(chunk, ChunkNodes { neighbours, edges });
+    }
+
+    /// The pairs of tiles where `chunk` leads into its neighbour in
+    /// the `side` direction. The first tile of the pair is in
+    /// `chunk`, the second in the neighbour.
+    ///
+    /// The neighbour gets the same pairs (just swapped) when it looks
+    /// back at `chunk` so the nodes on both sides match up.
+    fn entrances(&self, chunk: Point, side: Point, world: &World) -> Vec<(Point, Point)> {
+        let size = self.chunk_size;
+        // NOTE: the tiles along the border and how to step across.
+        let (first, along) = match side {
+            Point { x: 0, y: -1 } => (chunk, Point::new(1, 0)),
+            Point { x: 1, y: 0 } => (chunk + (size - 1, 0), Point::new(0, 1)),
+            Point { x: 0, y: 1 } => (chunk + (0, size - 1), Point::new(1, 0)),
+            _ => (chunk, Point::new(0, 1)),
+        };
+        let open = |index: i32| {
+            let inside = first + along * index;
+            let outside = inside + side;
+            world.walkable(inside, Blocker::WALL, inside)
+                && world.walkable(outside, Blocker::WALL, inside)
+        };
+
+        let mut entrances = vec![];
+        let mut index = 0;
+        while index < size {
+            if !open(index) {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < size && open(index) {
+                index += 1;
+            }
+            let end = index - 1;
+            let picks = if (end - start + 1) as usize >= WIDE_ENTRANCE {
+                vec![start, end]
+            } else {
+                vec![(start + end) / 2]
+            };
+            for pick in picks {
+                let inside = first + along * pick;
+                entrances.push((inside, inside + side));
+            }
+        }
+        entrances
+    }
+
+    fn chunk_position(&self, pos: Point) -> Point {
+        let size = self.chunk_size;
+        Point::new(pos.x.div_euclid(size) * size, pos.y.div_euclid(size) * size)
+    }
+
+    fn chunk_area(&self, chunk: Point) -> Rectangle {
+        Rectangle::from_point_and_size(chunk, Point::from_i32(self.chunk_size))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChunkGraph;
+    use crate::{
+        blocker::Blocker,
+        level::{Tile, TileKind},
+        pathfinding::{DirectCost, PathfindingContext},
+        player::{Mind, PlayerInfo},
+        point::Point,
+        rect::Rectangle,
+        world::World,
+    };
+
+    /// A world of 4x4 chunks 8 tiles wide (from `(-15, -15)` to
+    /// `(15, 15)`) with no monsters and the given walls.
+    fn make_world(walls: &[Point]) -> World {
+        let player_info = PlayerInfo {
+            pos: Point::new(0, 0),
+            mind: Mind::Sober(crate::ranged_int::Ranged::new_max(crate::formula::SOBER)),
+            max_ap: 1,
+            will: 3,
+        };
+        let mut world = World::new(0, 32, 8, player_info, Default::default());
+        for pos in Rectangle::new(Point::new(-15, -15), Point::new(15, 15)).points() {
+            world.ensure_chunk_at_pos(pos);
+            world.remove_monster(pos);
+            if let Some(cell) = world.cell_mut(pos) {
+                let kind = if walls.contains(&pos) {
+                    TileKind::Tree
+                } else {
+                    TileKind::Empty
+                };
+                cell.tile = Tile::new(kind);
+            }
+        }
+        world
+    }
+
+    /// A wall through the middle of the world with a gap at `gap`.
+    fn wall_with_gap(gap: Option<Point>) -> Vec<Point> {
+        (-15..=15)
+            .map(|y| Point::new(0, y))
+            .filter(|&pos| Some(pos) != gap)
+            .collect()
+    }
+
+    fn find(graph: &mut ChunkGraph, world: &World, from: Point, to: Point) -> Vec<Point> {
+        let mut context = PathfindingContext::new();
+        graph
+            .find(
+                from,
+                to,
+                world,
+                Blocker::WALL,
+                from,
+                100,
+                &mut context,
+                &DirectCost,
+            )
+            .collect()
+    }
+
+    fn assert_walkable_path(world: &World, from: Point, to: Point, path: &[Point]) {
+        assert_eq!(path.last(), Some(&to));
+        let mut current = from;
+        for &step in path {
+            assert_eq!(current.tile_distance(step), 1);
+            assert!(world.walkable(step, Blocker::WALL, from));
+            current = step;
+        }
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let world = make_world(&[]);
+        let mut graph = ChunkGraph::new();
+        let from = Point::new(-14, -14);
+        let to = Point::new(14, 13);
+        let path = find(&mut graph, &world, from, to);
+        assert_walkable_path(&world, from, to, &path);
+        // NOTE: the path goes through the chunk entrances so it's
+        // close to the shortest one, but not always the same.
+        assert!(path.len() >= 28);
+        assert!(path.len() <= 32);
+    }
+
+    #[test]
+    fn test_same_chunk() {
+        let world = make_world(&[]);
+        let mut graph = ChunkGraph::new();
+        let from = Point::new(1, 1);
+        let to = Point::new(5, 2);
+        let path = find(&mut graph, &world, from, to);
+        assert_walkable_path(&world, from, to, &path);
+        assert_eq!(path.len(), 4);
+    }
+
+    #[test]
+    fn test_around_a_wall() {
+        let gap = Point::new(0, 14);
+        let world = make_world(&wall_with_gap(Some(gap)));
+        let mut graph = ChunkGraph::new();
+        let from = Point::new(-3, -13);
+        let to = Point::new(3, -13);
+        let path = find(&mut graph, &world, from, to);
+        assert_walkable_path(&world, from, to, &path);
+        assert!(path.contains(&gap));
+    }
+
+    #[test]
+    fn test_no_path() {
+        let world = make_world(&wall_with_gap(None));
+        let mut graph = ChunkGraph::new();
+        assert!(find(&mut graph, &world, Point::new(-3, -13), Point::new(3, -13)).is_empty());
+        assert!(find(&mut graph, &world, Point::new(-3, -13), Point::new(0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let mut world = make_world(&wall_with_gap(None));
+        let mut graph = ChunkGraph::new();
+        let from = Point::new(-3, -13);
+        let to = Point::new(3, -13);
+        assert!(find(&mut graph, &world, from, to).is_empty());
+
+        let gap = Point::new(0, 2);
+        if let Some(cell) = world.cell_mut(gap) {
+            cell.tile = Tile::new(TileKind::Empty);
+        }
+        graph.invalidate(gap);
+        let path = find(&mut graph, &world, from, to);
+        assert_walkable_path(&world, from, to, &path);
+        assert!(path.contains(&gap));
+    }
+}
@@ -0,0 +1,395 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Name of the game's directory within the platform directories.
+const APP_DIR: &str = "dose-response";
+
+const SETTINGS_FILE: &str = "settings.toml";
+const SAVEGAME_FILE: &str = "SAVEDGAME.sav";
+const LOG_FILE: &str = "dose-response.log";
+const REPLAY_DIR: &str = "replays";
+const HISTORY_FILE: &str = "run-history.jsonl";
+const ACHIEVEMENTS_FILE: &str = "achievements.json";
+const DAILY_LEADERBOARD_FILE: &str = "daily-leaderboard.json";
+const DAILY_DIR: &str = "daily";
+const TRACE_DIR: &str = "traces";
+
+/// Where the log from before the migration ends up. The log file is
+/// recreated on every start so we don't want to overwrite it with the
+/// old one.
+const LEGACY_LOG_FILE: &str = "dose-response.old.log";
+
+/// Directory with the settings file.
+///
+/// `$XDG_CONFIG_HOME/dose-response` on Linux, `%APPDATA%` on Windows
+/// and `Application Support` on macOS.
+pub fn config_dir() -> PathBuf {
+    app_dir(platform_config_dir())
+}
+
+/// Directory with the saved game and the replays.
+///
+/// `$XDG_DATA_HOME/dose-response` on Linux, `%APPDATA%` on Windows
+/// and `Application Support` on macOS.
+pub fn data_dir() -> PathBuf {
+    app_dir(platform_data_dir())
+}
+
+/// Directory with the log files.
+///
+/// `$XDG_STATE_HOME/dose-response` on Linux, `%LOCALAPPDATA%` on
+/// Windows and `~/Library/Logs` on macOS.
+pub fn log_dir() -> PathBuf {
+    app_dir(platform_log_dir())
+}
+
+pub fn settings_file() -> PathBuf {
+    config_dir().join(SETTINGS_FILE)
+}
+
+pub fn savegame_file() -> PathBuf {
+    data_dir().join(SAVEGAME_FILE)
+}
+
+pub fn replay_dir() -> PathBuf {
+    data_dir().join(REPLAY_DIR)
+}
+
+/// The record of every finished game, one JSON object per line.
+pub fn history_file() -> PathBuf {
+    data_dir().join(HISTORY_FILE)
+}
+
+pub fn achievements_file() -> PathBuf {
+    data_dir().join(ACHIEVEMENTS_FILE)
+}
+
+pub fn daily_leaderboard_file() -> PathBuf {
+    data_dir().join(DAILY_LEADERBOARD_FILE)
+}
+
+/// Directory with the replays of the daily challenges.
+pub fn daily_dir() -> PathBuf {
+    data_dir().join(DAILY_DIR)
+}
+
+pub fn log_file() -> PathBuf {
+    log_dir().join(LOG_FILE)
+}
+
+/// Directory with the exported performance traces.
+pub fn trace_dir() -> PathBuf {
+    log_dir().join(TRACE_DIR)
+}
+
+/// The directory the game used to store everything in before we
+/// switched to the platform directories. It's also the fallback when
+/// the platform directories are not available.
+fn legacy_dir() -> PathBuf {
+    exe_dir()
+        .or_else(|| env::current_dir().ok())
+        .unwrap_or_default()
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+fn app_dir(platform_dir: Option<PathBuf>) -> PathBuf {
+    match platform_dir {
+        Some(dir) => dir.join(APP_DIR),
+        None => legacy_dir(),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Read the XDG base directory from the `variable` or fall back to
+/// `default` under the home directory.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn xdg_dir(variable: &str, default: &str) -> Option<PathBuf> {
+    resolve_xdg_dir(env::var_os(variable), home_dir(), default)
+}
+
+/// Pick the XDG base directory from the `value` of its environment
+/// variable and the `home` directory.
+#[cfg(any(test, not(any(target_os = "windows", target_os = "macos"))))]
+fn resolve_xdg_dir(
+    value: Option<std::ffi::OsString>,
+    home: Option<PathBuf>,
+    default: &str,
+) -> Option<PathBuf> {
+    // NOTE: the XDG spec says relative paths are invalid and should
+    // be ignored.
+    value
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| home.join(default)))
+}
+
+#[cfg(target_os = "windows")]
+fn platform_config_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "windows")]
+fn platform_data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "windows")]
+fn platform_log_dir() -> Option<PathBuf> {
+    env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn platform_config_dir() -> Option<PathBuf> {
+    home_dir().map(|home| home.join("Library/Application Support"))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_data_dir() -> Option<PathBuf> {
+    home_dir().map(|home| home.join("Library/Application Support"))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_log_dir() -> Option<PathBuf> {
+    home_dir().map(|home| home.join("Library/Logs"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn platform_config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn platform_data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn platform_log_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Whether the file at `path` looks like one of our replays. They're
+/// called `replay-<timestamp>`, but the shared ones are often
+/// compressed and renamed.
+pub fn is_replay_file(path: &Path) -> bool {
+    let recorded = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("replay-"));
+    let compressed = path.extension().is_some_and(|ext| ext == "gz");
+    recorded || compressed
+}
+
+/// Create the directory (and all its parents) unless it exists already.
+pub fn ensure_dir(dir: &Path) {
+    if !dir.exists()
+        && let Err(err) = fs::create_dir_all(dir)
+    {
+        log::error!("Could not create directory '{}': {}", dir.display(), err);
+    }
+}
+
+/// A fresh temporary directory for a test. It's removed again when
+/// this goes out of scope.
+#[cfg(test)]
+pub struct TestDir {
+    pub path: PathBuf,
+}
+
+#[cfg(test)]
+impl TestDir {
+    pub fn new(test_name: &str) -> Self {
+        let path = env::temp_dir().join(format!(
+            "dose-response-{}-{}",
+            test_name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        ensure_dir(&path);
+        Self { path }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Move the files the older versions of the game created next to
+/// the executable to the platform directories.
+///
+/// Nothing is overwritten: if the destination exists already, the
+/// old file stays where it is. That also means this is safe to call
+/// on every start.
+///
+/// NOTE: the older versions wrote into the current directory which
+/// is usually, but not always, where the executable is. We only look
+/// next to the executable though: the current directory can be
+/// anything and its `settings.toml` may not be ours.
+pub fn migrate_legacy_files() {
+    let Some(legacy_dir) = exe_dir() else {
+        return;
+    };
+
+    migrate_file(&legacy_dir.join(SETTINGS_FILE), &settings_file());
+    migrate_file(&legacy_dir.join(SAVEGAME_FILE), &savegame_file());
+    // NOTE: without the platform directories, the log is written
+    // next to the executable. That's the file of this run and it's
+    // open already so it has to stay where it is.
+    if log_dir() != legacy_dir {
+        migrate_file(&legacy_dir.join(LOG_FILE), &log_dir().join(LEGACY_LOG_FILE));
+    }
+
+    let legacy_replay_dir = legacy_dir.join(REPLAY_DIR);
+    if legacy_replay_dir.is_dir() && legacy_replay_dir != replay_dir() {
+        match fs::read_dir(&legacy_replay_dir) {
+            Ok(entries) => {
+                // NOTE: only touch the files that look like our
+                // replays.
+                let replays = entries
+                    .flatten()
+                    .filter(|entry| is_replay_file(&entry.path()));
+                for entry in replays {
+                    let destination = replay_dir().join(entry.file_name());
+                    migrate_file(&entry.path(), &destination);
+                }
+                // NOTE: this only succeeds if the directory is empty
+                // (i.e. we've moved every replay over).
+                let _ = fs::remove_dir(&legacy_replay_dir);
+            }
+            Err(err) => log::error!(
+                "Could not read the replay directory '{}': {}",
+                legacy_replay_dir.display(),
+                err
+            ),
+        }
+    }
+}
+
+fn migrate_file(source: &Path, destination: &Path) {
+    if source == destination || !source.is_file() {
+        return;
+    }
+    if destination.exists() {
+        log::warn!(
+            "Not migrating '{}': '{}' exists already.",
+            source.display(),
+            destination.display()
+        );
+        return;
+    }
+    if let Some(parent) = destination.parent() {
+        ensure_dir(parent);
+    }
+
+    log::info!(
+        "Migrating '{}' to '{}'",
+        source.display(),
+        destination.display()
+    );
+    // NOTE: `rename` doesn't work across filesystems so fall back to
+    // copying the file over.
+    let result = fs::rename(source, destination).or_else(|_| {
+        fs::copy(source, destination)?;
+        fs::remove_file(source)
+    });
+    if let Err(err) = result {
+        log::error!("Could not migrate '{}': {}", source.display(), err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_xdg_variable_wins_over_home() {
+        let dir = resolve_xdg_dir(
+            Some(OsString::from("/xdg/config")),
+            Some(PathBuf::from("/home/player")),
+            ".config",
+        );
+        assert_eq!(Some(PathBuf::from("/xdg/config")), dir);
+    }
+
+    #[test]
+    fn test_xdg_falls_back_to_home() {
+        let home = Some(PathBuf::from("/home/player"));
+        let expected = Some(PathBuf::from("/home/player/.config"));
+        assert_eq!(expected, resolve_xdg_dir(None, home.clone(), ".config"));
+        // Relative paths are invalid according to the spec
+        assert_eq!(
+            expected,
+            resolve_xdg_dir(Some(OsString::from("xdg/config")), home, ".config")
+        );
+    }
+
+    #[test]
+    fn test_xdg_without_variable_or_home() {
+        assert_eq!(None, resolve_xdg_dir(None, None, ".config"));
+    }
+
+    #[test]
+    fn test_migrate_file_moves_the_file() {
+        let dir = TestDir::new("paths-move");
+        let source = dir.path.join("settings.toml");
+        let destination = dir.path.join("config").join("settings.toml");
+        assert!(fs::write(&source, "old").is_ok());
+
+        migrate_file(&source, &destination);
+
+        assert!(!source.exists());
+        assert_eq!("old", fs::read_to_string(&destination).unwrap_or_default());
+    }
+
+    #[test]
+    fn test_migrate_file_keeps_existing_destination() {
+        let dir = TestDir::new("paths-keep");
+        let source = dir.path.join("old.toml");
+        let destination = dir.path.join("new.toml");
+        assert!(fs::write(&source, "old").is_ok());
+        assert!(fs::write(&destination, "new").is_ok());
+
+        migrate_file(&source, &destination);
+
+        assert_eq!("old", fs::read_to_string(&source).unwrap_or_default());
+        assert_eq!("new", fs::read_to_string(&destination).unwrap_or_default());
+    }
+
+    #[test]
+    fn test_migrate_missing_file_is_a_noop() {
+        let dir = TestDir::new("paths-missing");
+        let destination = dir.path.join("new.toml");
+
+        migrate_file(&dir.path.join("old.toml"), &destination);
+
+        assert!(!destination.exists());
+    }
+
+    #[test]
+    fn test_is_replay_file() {
+        assert!(is_replay_file(Path::new(
+            "replays/replay-2024-09-25T20-04-39.123"
+        )));
+        assert!(is_replay_file(Path::new(
+            "e2e-tests/overdose-2024-09-25.gz"
+        )));
+        assert!(!is_replay_file(Path::new("e2e-tests/README.md")));
+        assert!(!is_replay_file(Path::new("e2e-tests/notes.txt")));
+    }
+}
@@ -77,7 +77,7 @@ pub enum Bonus {
     UncoverMap,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum CauseOfDeath {
     Exhausted,
     Overdosed,
@@ -112,8 +112,15 @@ pub struct Player {
     // TODO: merge this with the other bonuses
     pub bonus: Bonus,
     pub bonuses: Vec<CompanionBonus>,
+    /// Every kind of companion that accompanied the player this game.
+    pub companions_met: Vec<CompanionBonus>,
     pub current_high_streak: i32,
     pub longest_high_streak: i32,
+    pub current_sober_streak: i32,
+    pub longest_sober_streak: i32,
+    pub doses_used: i32,
+    pub monsters_killed: i32,
+    pub npcs_met: i32,
 
     /// How many times has the player been reset.
     pub reset_count: i32,
@@ -138,6 +145,12 @@ impl Player {
         player.graphic = Graphic::CharacterSkirt;
         player.current_high_streak = 0;
         player.longest_high_streak = 0;
+        player.current_sober_streak = 0;
+        player.longest_sober_streak = 0;
+        player.doses_used = 0;
+        player.monsters_killed = 0;
+        player.npcs_met = 0;
+        player.companions_met = vec![];
         player.reset_count = 0;
 
         player
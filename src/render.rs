@@ -77,10 +77,7 @@ pub fn render_game(
         display.set_fade(animation.color, fade);
     }
 
-    let uncovered_map = (cfg!(feature = "cheating") && state.cheating)
-        || state.player.bonus == Bonus::UncoverMap  // player bonus
-        || state.uncovered_map  // map uncovered after the endgame fade
-        || !state.challenge.hide_unseen_tiles; // challenge Settings option
+    let uncovered_map = state.map_uncovered();
 
     let radius = formula::exploration_radius(state.player.mind);
 
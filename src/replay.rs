@@ -0,0 +1,346 @@
+use crate::{
+    engine,
+    palette::Palette,
+    point::Point,
+    settings::NoOpStore,
+    state::{self, Challenge, Divergence, OnDivergence, Side, State},
+};
+
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    fs::File,
+    io::Write,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, write::GzEncoder};
+
+/// How did the replayed game end.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Victory,
+    Death,
+    /// The replay stopped while the player was still alive.
+    Unfinished,
+    /// The replay couldn't be played through, e.g. it's truncated or
+    /// it ran out of inputs too early.
+    Crashed(String),
+}
+
+/// The result of re-running a single replay file.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub path: PathBuf,
+    pub seed: u32,
+    pub outcome: Outcome,
+    /// The first place the replayed game stopped matching the recorded one.
+    pub divergence: Option<Divergence>,
+    /// The turn the game reached. `None` if the replay crashed.
+    pub turn: Option<i32>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.divergence.is_none() && !matches!(self.outcome, Outcome::Crashed(_))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = match (&self.outcome, &self.divergence) {
+            (Outcome::Crashed(_), _) => "crashed",
+            (_, Some(_)) => "diverged",
+            _ => "ok",
+        };
+        writeln!(f, "{}", self.path.display())?;
+        writeln!(f, "  seed: {}", self.seed)?;
+        writeln!(f, "  result: {result}")?;
+        if let Some(divergence) = &self.divergence {
+            writeln!(
+                f,
+                "  first divergent tick: {} (turn {})",
+                divergence.tick_id, divergence.turn
+            )?;
+            for difference in &divergence.differences {
+                writeln!(f, "    {difference}")?;
+            }
+        }
+        let outcome = match &self.outcome {
+            Outcome::Victory => "victory",
+            Outcome::Death => "death",
+            Outcome::Unfinished => "unfinished",
+            Outcome::Crashed(message) => {
+                writeln!(f, "    {message}")?;
+                "none"
+            }
+        };
+        writeln!(f, "  outcome: {outcome}")?;
+        match self.turn {
+            Some(turn) => write!(f, "  turns: {turn}"),
+            None => write!(f, "  turns: unknown"),
+        }
+    }
+}
+
+/// Play the replay at `path` through without a window and report how
+/// it went. The older replays don't store their challenge so they're
+/// played with `challenge` instead.
+///
+/// `OnDivergence::Stop` ends the replay at the first divergence,
+/// `OnDivergence::Continue` plays it through to see where it ends up.
+///
+/// This only returns an error if the replay can't be loaded at all.
+/// A replay that doesn't match the recording is a `Report` with a
+/// `divergence`.
+pub fn verify(
+    path: &Path,
+    challenge: Challenge,
+    on_divergence: OnDivergence,
+) -> Result<Report, Box<dyn Error>> {
+    let debug = false;
+    let mut state = load(path, challenge, debug)?;
+    state.on_divergence = on_divergence;
+    simulate(path, state)
+}
+
+/// Play the replay at `path` under the current rules and record it
+/// again into `output`, with fresh verifications. The inputs stay the
+/// same so if the rules changed, the game may end differently.
+///
+/// If `output` ends with `.gz`, it's compressed. `challenge` is
+/// only used by the older replays, same as in `verify`.
+pub fn rerecord(
+    path: &Path,
+    output: &Path,
+    challenge: Challenge,
+    debug: bool,
+) -> Result<Report, Box<dyn Error>> {
+    let mut state = load(path, challenge, debug)?;
+    state.on_divergence = OnDivergence::Continue;
+    state.rules_version = state::RULES_VERSION;
+
+    let file = File::create_new(output)?;
+    let mut writer: Box<dyn Write> = if output.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(GzEncoder::new(file, Compression::default()))
+    } else {
+        Box::new(file)
+    };
+    state::log_header(&mut writer, state.seed, state.challenge);
+    state.input_logger = writer;
+
+    // NOTE: the new replay is finished when the state (and with it
+    // the `input_logger`) is dropped at the end of the simulation.
+    simulate(path, state)
+}
+
+fn load(path: &Path, challenge: Challenge, debug: bool) -> Result<State, Box<dyn Error>> {
+    let cheating = false;
+    let invincible = false;
+    let replay_full_speed = true;
+    let exit_after = true;
+    State::replay_game(
+        crate::WORLD_SIZE,
+        Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+        crate::PANEL_WIDTH,
+        path,
+        cheating,
+        invincible,
+        replay_full_speed,
+        exit_after,
+        debug,
+        challenge,
+        Palette::classic(),
+    )
+}
+
+fn simulate(path: &Path, state: State) -> Result<Report, Box<dyn Error>> {
+    let seed = state.seed;
+
+    // NOTE: divergences are handled by `state.on_divergence`, but a
+    // broken replay can still trip one of the asserts in
+    // `game::update`. We want to report that too rather than bring
+    // the whole verification down.
+    let simulation = panic::catch_unwind(AssertUnwindSafe(|| {
+        engine::headless::simulate(NoOpStore, Box::new(state))
+    }));
+    let report = match simulation {
+        Ok(result) => {
+            let state = result?;
+            let outcome = if state.side == Side::Victory {
+                Outcome::Victory
+            } else if state.player.alive() {
+                Outcome::Unfinished
+            } else {
+                Outcome::Death
+            };
+            Report {
+                path: path.to_path_buf(),
+                seed,
+                outcome,
+                divergence: state.divergence.clone(),
+                turn: Some(state.turn),
+            }
+        }
+        Err(payload) => Report {
+            path: path.to_path_buf(),
+            seed,
+            outcome: Outcome::Crashed(panic_message(payload.as_ref())),
+            divergence: None,
+            turn: None,
+        },
+    };
+
+    Ok(report)
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "The replay panicked.".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        blocker::Blocker,
+        engine::Mouse,
+        keys::KeyCode,
+        paths::TestDir,
+        state::{Input, VerificationWrapper},
+    };
+
+    fn new_game(seed: u32, challenge: Challenge) -> State {
+        State::new_game(
+            crate::WORLD_SIZE,
+            Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+            crate::PANEL_WIDTH,
+            false,
+            false,
+            seed,
+            None,
+            challenge,
+            Palette::classic(),
+        )
+    }
+
+    /// An input for `tick_id` with nothing going on.
+    fn idle_input(tick_id: i32) -> Input {
+        Input {
+            keys: vec![],
+            mouse: Mouse::new(),
+            tick_id,
+            verification: VerificationWrapper::None,
+            waypoint: None,
+            undo: false,
+        }
+    }
+
+    /// Write a replay with the `inputs` and play it through.
+    fn play_replay(
+        test_name: &str,
+        seed: u32,
+        challenge: Challenge,
+        inputs: impl IntoIterator<Item = Input>,
+    ) -> Result<Box<State>, Box<dyn Error>> {
+        let dir = TestDir::new(test_name);
+        let path = dir.path.join("replay-test");
+        let mut file = File::create(&path)?;
+        state::log_header(&mut file, seed, challenge);
+        for input in inputs {
+            state::log_input(&mut file, input);
+        }
+        drop(file);
+
+        let mut state = load(&path, Challenge::default(), false)?;
+        state.on_divergence = OnDivergence::Continue;
+        engine::headless::simulate(NoOpStore, Box::new(state))
+    }
+
+    #[test]
+    fn test_replay_walks_to_the_map_waypoint() -> Result<(), Box<dyn Error>> {
+        let seed = 1;
+        let challenge = Challenge::default();
+        let state = new_game(seed, challenge);
+        let start = state.player.pos;
+        let waypoint = [
+            Point::new(3, 0),
+            Point::new(-3, 0),
+            Point::new(0, 3),
+            Point::new(0, -3),
+        ]
+        .into_iter()
+        .map(|offset| start + offset)
+        .find(|&pos| state.world.walkable(pos, Blocker::WALL, start));
+        assert!(waypoint.is_some());
+
+        // NOTE: the map window is never replayed. The waypoint picked
+        // there must come from the game window's input.
+        let inputs = (1..=200).map(|tick_id| Input {
+            waypoint: waypoint.filter(|_| tick_id == 1),
+            ..idle_input(tick_id)
+        });
+        let state = play_replay("replay-waypoint", seed, challenge, inputs)?;
+        assert_eq!(Some(state.player.pos), waypoint);
+        assert_eq!(state.waypoint, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_goes_back_a_turn() -> Result<(), Box<dyn Error>> {
+        let seed = 1;
+        let challenge = Challenge {
+            practice: true,
+            ..Challenge::default()
+        };
+        let state = new_game(seed, challenge);
+        let start = state.player.pos;
+        let step = [
+            (Point::new(1, 0), KeyCode::Right),
+            (Point::new(-1, 0), KeyCode::Left),
+            (Point::new(0, 1), KeyCode::Down),
+            (Point::new(0, -1), KeyCode::Up),
+        ]
+        .into_iter()
+        .find(|&(offset, _)| state.world.walkable(start + offset, Blocker::WALL, start));
+        assert!(step.is_some());
+        let keys = step.map(|(_, code)| vec![code.into()]).unwrap_or_default();
+
+        let inputs = (1..=100).map(|tick_id| Input {
+            keys: if tick_id == 1 { keys.clone() } else { vec![] },
+            undo: tick_id == 50,
+            ..idle_input(tick_id)
+        });
+        let state = play_replay("replay-undo", seed, challenge, inputs)?;
+        assert_eq!(1, state.undos);
+        assert_eq!(0, state.turn);
+        assert_eq!(start, state.player.pos);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_is_played_with_its_own_challenge() -> Result<(), Box<dyn Error>> {
+        let dir = TestDir::new("replay-challenge");
+        let path = dir.path.join("replay-challenge");
+        let challenge = Challenge {
+            hide_unseen_tiles: false,
+            fast_depression: false,
+            one_chance: false,
+            practice: true,
+        };
+        let mut file = File::create(&path)?;
+        state::log_header(&mut file, 1, challenge);
+        drop(file);
+
+        let state = load(&path, Challenge::default(), false)?;
+        assert_eq!(challenge, state.challenge);
+        Ok(())
+    }
+}
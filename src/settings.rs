@@ -1,4 +1,4 @@
-use crate::{engine, palette, state};
+use crate::{engine, formula, palette, state};
 
 use serde::{Deserialize, Serialize};
 
@@ -11,7 +11,7 @@ use std::{
     string::ToString,
 };
 
-use toml_edit::Document as TomlDocument;
+use toml_edit::{Document as TomlDocument, Value as TomlValue};
 
 pub const MIN_WINDOW_WIDTH: u32 = 480;
 pub const MAX_WINDOW_WIDTH: u32 = 5000;
@@ -22,6 +22,11 @@ pub const MAX_WINDOW_HEIGHT: u32 = 5000;
 pub const DEFAULT_WINDOW_WIDTH: u32 = 1280;
 pub const DEFAULT_WINDOW_HEIGHT: u32 = 800;
 
+/// The game always updates at `formula::FPS`, the cap only limits how
+/// often it gets redrawn. So there's no point going above that.
+pub const MIN_FPS_CAP: u32 = 10;
+pub const MAX_FPS_CAP: u32 = formula::FPS as u32;
+
 pub const DISPLAY: &str = "display";
 pub const FULLSCREEN: &str = "fullscreen";
 pub const WINDOW: &str = "window";
@@ -36,11 +41,47 @@ pub const BACKEND: &str = "backend";
 pub const HIDE_UNSEEN_TILES: &str = "hide_unseen_tiles";
 pub const FAST_DEPRESSION: &str = "fast_depression";
 pub const PERMADEATH: &str = "permadeath";
+pub const PRACTICE: &str = "practice";
 pub const BACKGROUND_VOLUME: &str = "background_volume";
 pub const SOUND_VOLUME: &str = "sound_volume";
+pub const FPS_CAP: &str = "fps_cap";
+pub const VSYNC: &str = "vsync";
+pub const POWER_SAVING: &str = "power_saving";
 
 pub const FIRST_EVER_STARTUP: &str = "first_ever_startup";
 
+/// Prefix of the environment variables that override the settings
+/// for a single run. E.g. `DOSE_RESPONSE_TILE_SIZE=30`.
+pub const ENV_PREFIX: &str = "DOSE_RESPONSE_";
+
+/// Every settings key along with the command-line flag that
+/// overrides it for a single run.
+pub const OVERRIDES: [(&str, &str); 18] = [
+    (DISPLAY, "display"),
+    (VISUAL_STYLE, "visual-style"),
+    (PALETTE, "palette"),
+    (TILE_SIZE, "tile-size"),
+    (TEXT_SIZE, "text-size"),
+    (WINDOW_WIDTH, "window-width"),
+    (WINDOW_HEIGHT, "window-height"),
+    (BACKEND, "backend"),
+    (HIDE_UNSEEN_TILES, "hide-unseen-tiles"),
+    (FAST_DEPRESSION, "fast-depression"),
+    (PERMADEATH, "permadeath"),
+    (PRACTICE, "practice"),
+    (BACKGROUND_VOLUME, "background-volume"),
+    (SOUND_VOLUME, "sound-volume"),
+    (FPS_CAP, "fps-cap"),
+    (VSYNC, "vsync"),
+    (POWER_SAVING, "power-saving"),
+    (FIRST_EVER_STARTUP, "first-ever-startup"),
+];
+
+/// Whether `key` is one of the `OVERRIDES`.
+fn is_overridable(key: &str) -> bool {
+    OVERRIDES.iter().any(|&(known_key, _flag)| known_key == key)
+}
+
 /// The colour palette that the user can select
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Palette {
@@ -82,8 +123,15 @@ pub struct Settings {
     pub hide_unseen_tiles: bool,
     pub fast_depression: bool,
     pub permadeath: bool,
+    pub practice: bool,
     pub background_volume: f32,
     pub sound_volume: f32,
+    /// The maximum number of frames rendered per second.
+    pub fps_cap: u32,
+    pub vsync: bool,
+    /// Stop redrawing at the full frame rate when nothing's happening
+    /// and wait for the player's input instead.
+    pub power_saving: bool,
     pub first_ever_startup: bool,
 }
 
@@ -108,8 +156,12 @@ impl Default for Settings {
             hide_unseen_tiles: true,
             fast_depression: true,
             permadeath: true,
+            practice: false,
             background_volume: 1.0,
             sound_volume: 1.0,
+            fps_cap: MAX_FPS_CAP,
+            vsync: true,
+            power_saving: true,
             first_ever_startup: true,
         };
 
@@ -137,7 +189,10 @@ impl Settings {
         state::Challenge {
             hide_unseen_tiles: self.hide_unseen_tiles,
             fast_depression: self.fast_depression,
-            one_chance: self.permadeath,
+            // NOTE: the practice mode lets you undo your turns so
+            // it can't be a permadeath run.
+            one_chance: self.permadeath && !self.practice,
+            practice: self.practice,
         }
     }
 
@@ -209,6 +264,8 @@ impl Settings {
 
         let _ = writeln!(out, "{} = \"{}\"", PERMADEATH, self.permadeath);
 
+        let _ = writeln!(out, "{} = \"{}\"", PRACTICE, self.practice);
+
         out.push_str("# Options: <0.0, 1.0>\n");
         let _ = writeln!(
             out,
@@ -218,6 +275,16 @@ impl Settings {
         out.push_str("# Options: <0.0, 1.0>\n");
         let _ = writeln!(out, "{} = \"{}\"", SOUND_VOLUME, self.sound_volume);
 
+        let _ = writeln!(
+            out,
+            "# Options: <{MIN_FPS_CAP}, {MAX_FPS_CAP}>. Limits how often the game is redrawn, not how fast it runs."
+        );
+        let _ = writeln!(out, "{FPS_CAP} = {}", self.fps_cap);
+
+        let _ = writeln!(out, "{VSYNC} = {}", self.vsync);
+
+        let _ = writeln!(out, "{POWER_SAVING} = {}", self.power_saving);
+
         let _ = writeln!(
             out,
             "{} = \"{}\"",
@@ -237,9 +304,17 @@ pub trait Store {
     fn save(&mut self, settings: &Settings);
 }
 
+/// Settings stored in a TOML file.
+///
+/// The values are layered: the embedded defaults are overwritten by
+/// the settings file which is in turn overwritten by the environment
+/// variables and command-line flags (see `override_value`).
 pub struct FileSystemStore {
     path: PathBuf,
     toml: TomlDocument,
+    /// Values that apply to the current run only. They're never
+    /// written back to the settings file.
+    overrides: Vec<(String, TomlValue)>,
 }
 
 #[allow(dead_code)]
@@ -247,11 +322,13 @@ impl FileSystemStore {
     /// Create a new `Settings` store backed by a TOML document on the
     /// filesystem. If the file does not exist, it will be created.
     pub fn new() -> Self {
-        let filename = "settings.toml";
-        let mut path = std::env::current_exe()
-            .or_else(|_| std::env::current_dir())
-            .unwrap_or_default();
-        path.set_file_name(filename);
+        crate::paths::ensure_dir(&crate::paths::config_dir());
+        Self::with_path(crate::paths::settings_file())
+    }
+
+    /// Create a new `Settings` store backed by the TOML file at
+    /// `path`. If the file does not exist, it will be created.
+    pub fn with_path(path: PathBuf) -> Self {
         log::info!("Settings will be stored at: '{}'", path.display());
 
         let toml = Self::read_settings_toml(&path).unwrap_or_else(|err| {
@@ -271,7 +348,62 @@ impl FileSystemStore {
             toml
         });
 
-        Self { path, toml }
+        Self {
+            path,
+            toml,
+            overrides: vec![],
+        }
+    }
+
+    /// Override the settings `key` with `value` for this run.
+    ///
+    /// The value is parsed the same way as in the settings file, so
+    /// `30` is a number and `true` a boolean. Anything that doesn't
+    /// parse is treated as a string (so `classic` works without the
+    /// quotes).
+    pub fn override_value(&mut self, key: &str, value: &str) {
+        if !is_overridable(key) {
+            log::error!("Settings: cannot override unknown key `{}`.", key);
+            return;
+        }
+        let value = value
+            .parse::<TomlValue>()
+            .unwrap_or_else(|_| TomlValue::from(value));
+        log::info!("Settings: overriding `{}` with: {}", key, value);
+        self.overrides.retain(|(overridden_key, _)| overridden_key != key);
+        self.overrides.push((key.to_string(), value));
+    }
+
+    /// Override the settings using the `DOSE_RESPONSE_*` environment
+    /// variables. E.g. `DOSE_RESPONSE_PALETTE=greyscale`.
+    pub fn override_from_env(&mut self) {
+        self.override_from_vars(std::env::vars());
+    }
+
+    /// Apply the overrides from the `(name, value)` environment
+    /// variable pairs. Variables without the `ENV_PREFIX` are ignored
+    /// and so are the ones that don't name a setting (they may belong
+    /// to a launcher or a CI script rather than to us).
+    fn override_from_vars(&mut self, vars: impl IntoIterator<Item = (String, String)>) {
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let key = key.to_lowercase();
+            if is_overridable(&key) {
+                self.override_value(&key, &value);
+            } else {
+                log::debug!("Settings: ignoring the environment variable `{}`.", name);
+            }
+        }
+    }
+
+    fn toml_with_overrides(&self) -> TomlDocument {
+        let mut toml = self.toml.clone();
+        for (key, value) in &self.overrides {
+            toml[key.as_str()] = toml_edit::value(value.clone());
+        }
+        toml
     }
 
     fn read_settings_toml(path: &Path) -> Result<TomlDocument, Box<dyn Error>> {
@@ -287,6 +419,57 @@ impl FileSystemStore {
         std::fs::write(path, toml.to_string_in_original_order())?;
         Ok(())
     }
+
+    /// Write all the `settings` values into the TOML document.
+    fn write_values(toml: &mut TomlDocument, settings: &Settings) {
+        let display = match settings.fullscreen {
+            true => FULLSCREEN,
+            false => WINDOW,
+        };
+        toml[DISPLAY] = toml_edit::value(display);
+
+        toml[VISUAL_STYLE] = toml_edit::value(settings.visual_style.to_string());
+
+        toml[PALETTE] = toml_edit::value(settings.palette.to_string());
+
+        toml[TILE_SIZE] = toml_edit::value(i64::from(settings.tile_size));
+
+        toml[TEXT_SIZE] = toml_edit::value(i64::from(settings.text_size));
+
+        if settings.fullscreen {
+            // NOTE: don't save the window width/height when we're in
+            // the full screen mode. Because that means once we
+            // switched to windowed, the window would keep the fullscreen size.
+            //
+            // Instead, we want to return to the size the window had
+            // before going full screen.
+        } else {
+            toml[WINDOW_WIDTH] = toml_edit::value(i64::from(settings.window_width));
+            toml[WINDOW_HEIGHT] = toml_edit::value(i64::from(settings.window_height));
+        }
+
+        toml[BACKEND] = toml_edit::value(settings.backend.clone());
+
+        toml[HIDE_UNSEEN_TILES] = toml_edit::value(settings.hide_unseen_tiles);
+
+        toml[FAST_DEPRESSION] = toml_edit::value(settings.fast_depression);
+
+        toml[PERMADEATH] = toml_edit::value(settings.permadeath);
+
+        toml[PRACTICE] = toml_edit::value(settings.practice);
+
+        toml[BACKGROUND_VOLUME] = toml_edit::value(f64::from(settings.background_volume));
+
+        toml[SOUND_VOLUME] = toml_edit::value(f64::from(settings.sound_volume));
+
+        toml[FPS_CAP] = toml_edit::value(i64::from(settings.fps_cap));
+
+        toml[VSYNC] = toml_edit::value(settings.vsync);
+
+        toml[POWER_SAVING] = toml_edit::value(settings.power_saving);
+
+        toml[FIRST_EVER_STARTUP] = toml_edit::value(settings.first_ever_startup);
+    }
 }
 
 impl Default for FileSystemStore {
@@ -297,9 +480,10 @@ impl Default for FileSystemStore {
 
 impl Store for FileSystemStore {
     fn load(&self) -> Settings {
+        let toml = self.toml_with_overrides();
         let mut settings = Settings::default();
 
-        match self.toml[DISPLAY].as_str() {
+        match toml[DISPLAY].as_str() {
             Some(FULLSCREEN) => settings.fullscreen = true,
             Some(WINDOW) => settings.fullscreen = false,
             Some(unexpected) => {
@@ -314,7 +498,7 @@ impl Store for FileSystemStore {
             None => log::error!("Settings: missing `{}` entry.", DISPLAY),
         }
 
-        match self.toml[VISUAL_STYLE].as_str() {
+        match toml[VISUAL_STYLE].as_str() {
             Some(engine::VISUAL_STYLE_GRAPHICAL_STR) => {
                 settings.visual_style = engine::VisualStyle::Graphical
             }
@@ -341,7 +525,7 @@ impl Store for FileSystemStore {
             ),
         }
 
-        match self.toml[PALETTE].as_str() {
+        match toml[PALETTE].as_str() {
             Some(PALETTE_CLASSIC_STR) => {
                 settings.palette = Palette::Classic;
             }
@@ -375,7 +559,7 @@ impl Store for FileSystemStore {
                 .copied()
                 .unwrap_or_else(|| Settings::default().tile_size);
         } else {
-            match self.toml[TILE_SIZE].as_integer() {
+            match toml[TILE_SIZE].as_integer() {
                 Some(tile_size) => {
                     let tile_size = tile_size as i32;
                     if crate::engine::AVAILABLE_TILE_SIZES.contains(&tile_size) {
@@ -392,7 +576,7 @@ impl Store for FileSystemStore {
             }
         }
 
-        match self.toml[TEXT_SIZE].as_integer() {
+        match toml[TEXT_SIZE].as_integer() {
             Some(text_size) => {
                 let text_size = text_size as i32;
                 if crate::engine::AVAILABLE_TEXT_SIZES.contains(&text_size) {
@@ -408,7 +592,7 @@ impl Store for FileSystemStore {
             None => log::error!("Settings: missing `{}` entry.", TEXT_SIZE),
         }
 
-        match self.toml[WINDOW_WIDTH].as_integer() {
+        match toml[WINDOW_WIDTH].as_integer() {
             Some(window_width) => {
                 if window_width < i64::from(MIN_WINDOW_WIDTH) {
                     log::error!(
@@ -429,7 +613,7 @@ impl Store for FileSystemStore {
             None => log::error!("Settings: missing `{}` entry.", WINDOW_WIDTH),
         }
 
-        match self.toml[WINDOW_HEIGHT].as_integer() {
+        match toml[WINDOW_HEIGHT].as_integer() {
             Some(window_height) => {
                 if window_height < i64::from(MIN_WINDOW_HEIGHT) {
                     log::error!(
@@ -450,7 +634,7 @@ impl Store for FileSystemStore {
             None => log::error!("Settings: missing `{}` entry.", WINDOW_HEIGHT),
         }
 
-        match self.toml[BACKEND].as_str() {
+        match toml[BACKEND].as_str() {
             Some(backend) => {
                 if crate::engine::AVAILABLE_BACKENDS.contains(&backend) {
                     settings.backend = backend.into();
@@ -465,35 +649,42 @@ impl Store for FileSystemStore {
             None => log::error!("Settings: missing `{}` entry.", BACKEND),
         }
 
-        match self.toml[HIDE_UNSEEN_TILES].as_bool() {
+        match toml[HIDE_UNSEEN_TILES].as_bool() {
             Some(hide_unseen_tiles) => {
                 settings.hide_unseen_tiles = hide_unseen_tiles;
             }
             None => log::error!("Settings: missing `{}` entry.", HIDE_UNSEEN_TILES),
         }
 
-        match self.toml[FAST_DEPRESSION].as_bool() {
+        match toml[FAST_DEPRESSION].as_bool() {
             Some(fast_depression) => {
                 settings.fast_depression = fast_depression;
             }
             None => log::error!("Settings: missing `{}` entry.", FAST_DEPRESSION),
         }
 
-        match self.toml[PERMADEATH].as_bool() {
+        match toml[PERMADEATH].as_bool() {
             Some(permadeath) => {
                 settings.permadeath = permadeath;
             }
             None => log::error!("Settings: missing `{}` entry.", PERMADEATH),
         }
 
-        match self.toml[BACKGROUND_VOLUME].as_float() {
+        match toml[PRACTICE].as_bool() {
+            Some(practice) => {
+                settings.practice = practice;
+            }
+            None => log::error!("Settings: missing `{}` entry.", PRACTICE),
+        }
+
+        match toml[BACKGROUND_VOLUME].as_float() {
             Some(volume) => {
                 settings.background_volume = volume.clamp(0.0, 1.0) as f32;
             }
             // toml_edit uses `f64.as_string()` which outputs e.g.
             // `1.0` as `1`. This then gets parsed here as integer not
             // float so we need to handle that case separately.
-            None => match self.toml[BACKGROUND_VOLUME].as_integer() {
+            None => match toml[BACKGROUND_VOLUME].as_integer() {
                 Some(volume) => {
                     settings.background_volume = volume.clamp(0, 1) as f32;
                 }
@@ -501,11 +692,11 @@ impl Store for FileSystemStore {
             },
         }
 
-        match self.toml[SOUND_VOLUME].as_float() {
+        match toml[SOUND_VOLUME].as_float() {
             Some(volume) => {
                 settings.sound_volume = volume.clamp(0.0, 1.0) as f32;
             }
-            None => match self.toml[SOUND_VOLUME].as_integer() {
+            None => match toml[SOUND_VOLUME].as_integer() {
                 Some(volume) => {
                     settings.sound_volume = volume.clamp(0, 1) as f32;
                 }
@@ -513,7 +704,49 @@ impl Store for FileSystemStore {
             },
         }
 
-        match self.toml[FIRST_EVER_STARTUP].as_bool() {
+        match toml[FPS_CAP].as_integer() {
+            Some(fps_cap) => {
+                if fps_cap < i64::from(MIN_FPS_CAP) || fps_cap > i64::from(MAX_FPS_CAP) {
+                    log::error!(
+                        "Settings error: `{}` must be between {} and {}.",
+                        FPS_CAP,
+                        MIN_FPS_CAP,
+                        MAX_FPS_CAP
+                    );
+                } else {
+                    settings.fps_cap = fps_cap as u32;
+                }
+            }
+            None => log::info!(
+                "Settings: missing `{}`, falling back to: {}",
+                FPS_CAP,
+                settings.fps_cap
+            ),
+        }
+
+        match toml[VSYNC].as_bool() {
+            Some(vsync) => {
+                settings.vsync = vsync;
+            }
+            None => log::info!(
+                "Settings: missing `{}`, falling back to: {}",
+                VSYNC,
+                settings.vsync
+            ),
+        }
+
+        match toml[POWER_SAVING].as_bool() {
+            Some(power_saving) => {
+                settings.power_saving = power_saving;
+            }
+            None => log::info!(
+                "Settings: missing `{}`, falling back to: {}",
+                POWER_SAVING,
+                settings.power_saving
+            ),
+        }
+
+        match toml[FIRST_EVER_STARTUP].as_bool() {
             Some(first_ever_startup) => {
                 settings.first_ever_startup = first_ever_startup;
             }
@@ -529,45 +762,25 @@ impl Store for FileSystemStore {
 
     fn save(&mut self, settings: &Settings) {
         log::info!("Saving new settings to file {}", self.path.display());
-        let display = match settings.fullscreen {
-            true => FULLSCREEN,
-            false => WINDOW,
-        };
-        self.toml[DISPLAY] = toml_edit::value(display);
-
-        self.toml[VISUAL_STYLE] = toml_edit::value(settings.visual_style.to_string());
-
-        self.toml[PALETTE] = toml_edit::value(settings.palette.to_string());
-
-        self.toml[TILE_SIZE] = toml_edit::value(i64::from(settings.tile_size));
-
-        self.toml[TEXT_SIZE] = toml_edit::value(i64::from(settings.text_size));
-
-        if settings.fullscreen {
-            // NOTE: don't save the window width/height when we're in
-            // the full screen mode. Because that means once we
-            // switched to windowed, the window would keep the fullscreen size.
-            //
-            // Instead, we want to return to the size the window had
-            // before going full screen.
-        } else {
-            self.toml[WINDOW_WIDTH] = toml_edit::value(i64::from(settings.window_width));
-            self.toml[WINDOW_HEIGHT] = toml_edit::value(i64::from(settings.window_height));
+        let mut toml = self.toml.clone();
+        Self::write_values(&mut toml, settings);
+
+        if !self.overrides.is_empty() {
+            // NOTE: the overrides only apply to the current run. Keep
+            // whatever value the file had unless the player changed
+            // the overridden setting in the game.
+            let mut overridden = TomlDocument::new();
+            Self::write_values(&mut overridden, &self.load());
+            for (key, _value) in &self.overrides {
+                let key = key.as_str();
+                let current = toml[key].as_value().map(ToString::to_string);
+                let from_override = overridden[key].as_value().map(ToString::to_string);
+                if current == from_override {
+                    toml[key] = self.toml[key].clone();
+                }
+            }
         }
-
-        self.toml[BACKEND] = toml_edit::value(settings.backend.clone());
-
-        self.toml[HIDE_UNSEEN_TILES] = toml_edit::value(settings.hide_unseen_tiles);
-
-        self.toml[FAST_DEPRESSION] = toml_edit::value(settings.fast_depression);
-
-        self.toml[PERMADEATH] = toml_edit::value(settings.permadeath);
-
-        self.toml[BACKGROUND_VOLUME] = toml_edit::value(f64::from(settings.background_volume));
-
-        self.toml[SOUND_VOLUME] = toml_edit::value(f64::from(settings.sound_volume));
-
-        self.toml[FIRST_EVER_STARTUP] = toml_edit::value(settings.first_ever_startup);
+        self.toml = toml;
 
         if let Err(err) = Self::write_settings_toml(&self.path, &self.toml) {
             log::error!("Could not write settings to the storage: {:?}", err);
@@ -585,3 +798,104 @@ impl Store for NoOpStore {
 
     fn save(&mut self, _settings: &Settings) {}
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::paths::TestDir;
+
+    /// A settings file with the given contents in a fresh temporary
+    /// directory. The directory goes away with the returned `TestDir`.
+    fn store_with_file(test_name: &str, contents: &str) -> (TestDir, FileSystemStore) {
+        let dir = TestDir::new(&format!("settings-{test_name}"));
+        let path = dir.path.join("settings.toml");
+        assert!(std::fs::write(&path, contents).is_ok());
+        (dir, FileSystemStore::with_path(path))
+    }
+
+    fn file_contents(store: &FileSystemStore) -> String {
+        std::fs::read_to_string(&store.path).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_file_value_is_loaded() {
+        let (_dir, store) = store_with_file("file", "palette = \"accessible\"\ntile_size = 40\n");
+        let settings = store.load();
+        assert_eq!(Palette::Accessible, settings.palette);
+        assert_eq!(40, settings.tile_size);
+    }
+
+    #[test]
+    fn test_cli_override_wins_over_the_file() {
+        let (_dir, mut store) =
+            store_with_file("cli", "palette = \"accessible\"\ntile_size = 40\n");
+        store.override_value(PALETTE, "greyscale");
+        store.override_value(TILE_SIZE, "30");
+        let settings = store.load();
+        assert_eq!(Palette::Greyscale, settings.palette);
+        assert_eq!(30, settings.tile_size);
+    }
+
+    #[test]
+    fn test_env_override_wins_over_the_file() {
+        let (_dir, mut store) =
+            store_with_file("env", "palette = \"accessible\"\nfast_depression = false\n");
+        store.override_from_vars([
+            ("DOSE_RESPONSE_PALETTE".to_string(), "classic".to_string()),
+            (
+                "DOSE_RESPONSE_FAST_DEPRESSION".to_string(),
+                "true".to_string(),
+            ),
+            ("PALETTE".to_string(), "greyscale".to_string()),
+            ("DOSE_RESPONSE_BUILD_ID".to_string(), "1234".to_string()),
+        ]);
+        assert_eq!(2, store.overrides.len());
+        let settings = store.load();
+        assert_eq!(Palette::Classic, settings.palette);
+        assert!(settings.fast_depression);
+    }
+
+    #[test]
+    fn test_unknown_override_is_ignored() {
+        let (_dir, mut store) = store_with_file("unknown", "tile_size = 40\n");
+        store.override_value("not_a_setting", "30");
+        assert!(store.overrides.is_empty());
+        assert_eq!(40, store.load().tile_size);
+    }
+
+    #[test]
+    fn test_save_keeps_the_file_values_of_overrides() {
+        let (_dir, mut store) =
+            store_with_file("save", "palette = \"accessible\"\ntile_size = 40\n");
+        store.override_value(PALETTE, "greyscale");
+        store.override_value(TILE_SIZE, "30");
+
+        // The player changed the text size but not the overridden
+        // settings.
+        let mut settings = store.load();
+        settings.text_size = 22;
+        store.save(&settings);
+
+        let saved = FileSystemStore::with_path(store.path.clone()).load();
+        assert_eq!(Palette::Accessible, saved.palette);
+        assert_eq!(40, saved.tile_size);
+        assert_eq!(22, saved.text_size);
+        assert!(!file_contents(&store).contains("greyscale"));
+
+        // The overrides still apply for the rest of the run
+        assert_eq!(Palette::Greyscale, store.load().palette);
+    }
+
+    #[test]
+    fn test_save_writes_overridden_setting_the_player_changed() {
+        let (_dir, mut store) = store_with_file("save-changed", "palette = \"accessible\"\n");
+        store.override_value(PALETTE, "greyscale");
+
+        let mut settings = store.load();
+        settings.palette = Palette::Classic;
+        store.save(&settings);
+
+        let saved = FileSystemStore::with_path(store.path.clone()).load();
+        assert_eq!(Palette::Classic, saved.palette);
+    }
+}
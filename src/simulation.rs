@@ -0,0 +1,1665 @@
+use crate::{
+    ai,
+    animation::{self, AreaOfEffect},
+    blocker::Blocker,
+    formula,
+    game::Action,
+    graphic::Graphic,
+    item,
+    level::TileKind,
+    monster::{self, CompanionBonus},
+    palette::Palette,
+    pathfinding::{DijkstraMap, DirectCost, MonsterCost, PathfindingContext},
+    player::{self, CauseOfDeath, Mind, Modifier},
+    point::{self, Point},
+    random::Random,
+    ranged_int::{InclusiveRange, Ranged},
+    rect::Rectangle,
+    state::{Command, GameSession, MotionAnimation, Side, State},
+    timer::Timer,
+    trace,
+    world::World,
+};
+
+use std::{collections::VecDeque, time::Duration};
+
+/// How much time passes in a single `tick` of `step`.
+const STEP_DT: Duration = Duration::from_millis(16);
+
+/// Guards against a command that never finishes playing out.
+const MAX_TICKS_PER_STEP: usize = 1000;
+
+/// Something that happened in the game world.
+///
+/// The simulation only says what happened and queues the event in
+/// `State::events`. It's up to the frontend to play a sound, show a
+/// message, unlock an achievement etc.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    PlayerMoved {
+        from: Point,
+        to: Point,
+    },
+    MonsterMoved {
+        kind: monster::Kind,
+        from: Point,
+        to: Point,
+        /// Whether the player can see the monster at its new position.
+        visible: bool,
+    },
+    /// A monster came into the player's view, either because it or
+    /// the player moved.
+    MonsterSpotted {
+        kind: monster::Kind,
+        pos: Point,
+        player_pos: Point,
+    },
+    MonsterAttacked {
+        kind: monster::Kind,
+        effect: Modifier,
+    },
+    /// The player bumped into a monster, NPC or a signpost.
+    PlayerAttacked {
+        kind: monster::Kind,
+        pos: Point,
+    },
+    /// A monster, NPC or a signpost was removed from the world.
+    MonsterKilled {
+        kind: monster::Kind,
+        pos: Point,
+    },
+    PlayerStunned,
+    PlayerPanicked,
+    /// The player faced Anxiety. Once the `counter` reaches `max`,
+    /// their Will grows.
+    AnxietyFaced {
+        counter: i32,
+        max: i32,
+    },
+    WillGrew {
+        will: i32,
+    },
+    HungerFought {
+        effect: Modifier,
+    },
+    /// The item went into the player's inventory.
+    ItemPickedUp(item::Kind),
+    /// The player stepped on a dose they couldn't resist. They use it
+    /// right away.
+    DoseIrresistible(item::Kind),
+    FoodEaten {
+        effect: Modifier,
+    },
+    DoseUsed {
+        kind: item::Kind,
+        /// The state of mind the dose put the player in.
+        mind: Mind,
+    },
+    /// An NPC started accompanying the player.
+    NpcMet(CompanionBonus),
+    /// The NPC accompanying the player left them for another one.
+    CompanionLeft,
+    /// The player spent time with a companion they haven't met before.
+    CompanionMet(CompanionBonus),
+    TurnEnded {
+        turn: i32,
+    },
+    PlayerDied {
+        cause: Option<CauseOfDeath>,
+        /// The state of mind the player died in. Without `one_chance`
+        /// the player is reset right away so it's not in the `State`.
+        mind: Mind,
+        /// The monster that dealt the final blow (if any).
+        perpetrator: Option<monster::Kind>,
+    },
+    VictoryNpcPlaced {
+        pos: Point,
+        player_pos: Point,
+    },
+    /// The player got High and the Victory NPC turned into a signpost.
+    VictoryNpcGone,
+    Victory,
+    /// Show a message box to the player. Without `ttl` it stays up
+    /// until they close it.
+    MessageShown {
+        title: String,
+        message: String,
+        ttl: Option<Duration>,
+    },
+}
+
+/// Play a single player `command` out: the player acts and the
+/// monsters take their turns until the player can act again (or the
+/// game is over).
+///
+/// This doesn't need a window, audio or any UI so it can drive bots
+/// and tests. Given the same `State` and commands, it always produces
+/// the same result.
+pub fn step(state: &mut State, command: Command) -> Vec<GameEvent> {
+    if command == Command::WalkPath {
+        state.path_walking_timer.finish();
+    }
+    state.commands.push_back(command);
+
+    for _ in 0..MAX_TICKS_PER_STEP {
+        let player_was_alive = state.player.alive();
+        let advance = state.side != Side::Victory;
+        tick(state, STEP_DT, crate::engine::DEFAULT_TILE_SIZE, advance);
+        if player_was_alive && !state.player.alive() {
+            player_died(state);
+        }
+
+        let command_played_out = state.commands.is_empty()
+            && state.explosion_animation.is_none()
+            && state.player.has_ap(1);
+        if command_played_out || state.game_session == GameSession::Ended {
+            break;
+        }
+    }
+
+    std::mem::take(&mut state.events)
+}
+
+/// Advance the game by `dt`. If `advance` is `false` (e.g. the game
+/// is paused), only the dose explosions play out.
+///
+/// Processes one action point of the player and then one of every
+/// monster, ends the turn once everyone's out of action points and
+/// updates the animations.
+///
+/// This doesn't handle the player's death, call `player_died` for that.
+pub(crate) fn tick(state: &mut State, dt: Duration, tile_size: i32, advance: bool) {
+    let mut entire_turn_ended = false;
+
+    let simulation_area = formula::simulation_area(state.player.pos);
+
+    if advance {
+        let monster_count = state.world.monsters(simulation_area).count();
+        let monster_with_ap_count = state
+            .world
+            .monsters(simulation_area)
+            .filter(|m| m.has_ap(1))
+            .count();
+        let monster_cumulative_ap: i32 = state
+            .world
+            .monsters(simulation_area)
+            .map(|m| m.ap.to_int())
+            .sum();
+        log::trace!(
+            "Player AP: {}, monsters: {}, active mon: {}, total mon AP: {}",
+            state.player.ap(),
+            monster_count,
+            monster_with_ap_count,
+            monster_cumulative_ap
+        );
+
+        state.player.motion_animation.update(dt);
+        for monster in state.world.monsters_mut(simulation_area) {
+            monster.motion_animation.update(dt);
+        }
+        for motion_animation in &mut state.extra_animations {
+            motion_animation.animation.update(dt);
+        }
+
+        // NOTE: Process 1 action point of the player and then 1 AP of
+        // all monsters. This means that their turns will alternate.
+        // E.g. if the player has 2 APs and they're close to a
+        // Depression, the player will move 1 turn first, then
+        // Depression 1 etc.
+
+        let player_ap = state.player.ap();
+        log::trace!("Player AP before processing: {player_ap}");
+        if state.player.ap() >= 1 {
+            process_player(state, tile_size, simulation_area);
+        }
+        let player_took_action = player_ap > state.player.ap();
+        let monsters_can_move = state.player.ap() == 0 || player_took_action;
+        log::trace!(
+            "Player AP: {player_ap}, Player took action: {player_took_action}, Monsters can move: {monsters_can_move}"
+        );
+
+        if state.explosion_animation.is_none() {
+            if monsters_can_move {
+                process_monsters(
+                    &mut state.world,
+                    &mut state.player,
+                    simulation_area,
+                    tile_size,
+                    &mut state.rng,
+                    &state.palette,
+                    &mut state.extra_animations,
+                    &mut state.events,
+                    &mut state.pathfinding,
+                    (state.rules_version >= 2).then_some(&mut state.chase_map),
+                    (state.rules_version >= 5).then_some(&mut state.flee_map),
+                );
+            } else {
+                log::trace!("Monsters waiting for player.");
+            }
+        } else {
+            log::trace!("Monster's waiting for the explosion to end.");
+        }
+
+        // NOTE: the anxiety counter bar is hidden at the start, but
+        // we want to show it as soon as it increases.
+        if player_took_action && !state.player.anxiety_counter.is_min() {
+            state.show_anxiety_counter = true;
+        }
+
+        if player_took_action
+            && state.player.mind.is_high()
+            && let Some(victory_npc_id) = state.victory_npc_id.take()
+        {
+            log::info!("Player got High, the Victory NPC disappears!");
+            state.events.push(GameEvent::VictoryNpcGone);
+            if let Some(vnpc) = state.world.monster_mut(victory_npc_id) {
+                // TODO: move this (and other init stuff from
+                // Monster::new) to custom functions?
+                vnpc.kind = monster::Kind::Signpost;
+                vnpc.behavior = ai::Behavior::Immobile;
+                vnpc.ai_state = ai::AIState::NoOp
+            }
+        }
+
+        // Reset all action points only after everyone is at zero:
+        let player_turn_ended = !state.player.has_ap(1);
+        let monster_turn_ended = state
+            .world
+            .monsters(simulation_area)
+            .filter(|m| m.has_ap(1))
+            .count()
+            == 0;
+
+        entire_turn_ended = player_turn_ended && monster_turn_ended;
+        log::trace!(
+            "Entire turn ended: {}, player turn ended: {}, monster turn ended: {}",
+            entire_turn_ended,
+            player_turn_ended,
+            monster_turn_ended
+        );
+    }
+
+    // Reset the player & monster action points
+    // NOTE: doing this only after we've logged the validations. Actually maybe we want to do this
+    // before we start turn processing??
+    if entire_turn_ended {
+        log::trace!("Starting new turn for player and monsters.");
+        state.player.new_turn();
+        for monster in state.world.monsters_mut(simulation_area) {
+            monster.new_turn();
+        }
+    }
+
+    if entire_turn_ended {
+        log::trace!("Turn {} has ended.", state.turn);
+        state.timeline.record(state.turn, &state.player);
+        state.turn += 1;
+        state.events.push(GameEvent::TurnEnded { turn: state.turn });
+    }
+
+    // NOTE: Load up new chunks if necessary
+    if entire_turn_ended {
+        for pos in simulation_area.points() {
+            state.world.ensure_chunk_at_pos(pos);
+        }
+    }
+
+    // Run the dose explosion effect here:
+    if let Some(ref anim) = state.explosion_animation {
+        for (pos, _, effect) in anim.tiles() {
+            if effect.contains(animation::TileEffect::KILL)
+                && kill_monster(pos, &mut state.world, &mut state.events)
+            {
+                state.player.monsters_killed += 1;
+            }
+            if effect.contains(animation::TileEffect::SHATTER)
+                && let Some(cell) = state.world.cell_mut(pos)
+            {
+                cell.tile.kind = TileKind::Empty;
+                cell.tile.graphic = Graphic::Empty;
+                cell.items.clear();
+                state.chunk_graph.invalidate(pos);
+            }
+        }
+    }
+
+    // NOTE: update the dose/food explosion animations
+    state.explosion_animation = state.explosion_animation.take().and_then(|mut animation| {
+        animation.update(dt);
+        if animation.finished() {
+            None
+        } else {
+            Some(animation)
+        }
+    });
+
+    // NOTE: Remove any animations that are already finished.
+    state.extra_animations.retain(|a| a.animation.in_progress());
+}
+
+/// The player has just died. Either end the game or give them
+/// another go, depending on the challenge.
+pub(crate) fn player_died(state: &mut State) {
+    log::info!("Player died.");
+    state.events.push(GameEvent::PlayerDied {
+        cause: formula::cause_of_death(&state.player),
+        mind: state.player.mind,
+        perpetrator: state
+            .player
+            .perpetrator
+            .as_ref()
+            .map(|monster| monster.kind),
+    });
+    if state.challenge.one_chance {
+        state.game_session = GameSession::Ended;
+        state.timeline.record(state.turn, &state.player);
+        state.show_endscreen_and_uncover_map_during_fadein = true;
+        log::trace!("Game real time: {:?}", state.clock);
+    } else {
+        // NOTE: Don't die, reset the player to the initial state instead:
+        state.player.reset();
+    }
+}
+
+fn process_monsters(
+    world: &mut World,
+    player: &mut player::Player,
+    area: Rectangle,
+    tile_size: i32,
+    rng: &mut Random,
+    palette: &Palette,
+    extra_animations: &mut Vec<MotionAnimation>,
+    events: &mut Vec<GameEvent>,
+    pathfinding: &mut PathfindingContext,
+    // NOTE: `None` under the older rules where each chasing monster
+    // finds its own path.
+    mut chase_map: Option<&mut DijkstraMap>,
+    // NOTE: `None` under the older rules where the NPCs don't keep
+    // away from the player.
+    mut flee_map: Option<&mut DijkstraMap>,
+) {
+    if !player.alive() {
+        return;
+    }
+    let _span = trace::span("monster AI");
+    log::trace!("Processing monsters");
+
+    // NOTE: the player doesn't move during the monsters' turn so
+    // everyone chasing them can share the one map.
+    if let Some(chase_map) = chase_map.as_deref_mut() {
+        chase_map.build(
+            area,
+            &[player.pos],
+            world,
+            Blocker::WALL,
+            player.pos,
+            &MonsterCost,
+        );
+    }
+
+    // NOTE: the flee map is only built once an NPC needs it. Most
+    // turns, none does.
+    let mut flee_map_built = false;
+
+    // NOTE: one quarter of the map area should be a decent overestimate
+    let monster_count_estimate = area.size().x * area.size().y / 4;
+    assert!(monster_count_estimate > 0);
+    let mut monster_positions_vec = world
+        .monsters(area)
+        .filter(|m| m.has_ap(1))
+        .map(|m| (m.ap.to_int(), m.position))
+        .collect::<Vec<_>>();
+    // NOTE: `world.monsters` does not give a stable result so we need to sort
+    // it here to ensure correct replays.
+    // NOTE: there's always at most one monster at a given position so this should always produce
+    // the same ordering.
+    //
+    // We sort by action points (so depression always goes first), by
+    // distance to player (so a closer monster can move first and make
+    // space for another one near by) and then by coordinates just to
+    // have some awy to always produce a stable ordering.
+    monster_positions_vec
+        .sort_by_key(|&(ap, pos)| (ap, player.pos.distance(pos) as i32, pos.x, pos.y));
+    let mut monster_positions_to_process: VecDeque<_> = monster_positions_vec.into();
+    log::trace!(
+        "Monsters to process: {}",
+        monster_positions_to_process.len()
+    );
+
+    while let Some((_, monster_position)) = monster_positions_to_process.pop_front() {
+        if !player.alive() {
+            // Don't process any new monsters if the player's dead
+            // because we want the game to stop and freeze at that
+            // time.
+            //
+            // But we still want all the effects (e.g. attack
+            // animatino) that started to finish so we don't want to
+            // quit the loop as soon as the player dies.
+            continue;
+        }
+        if let Some(monster_readonly) = world.monster_on_pos(monster_position).cloned() {
+            let action = {
+                let (update, action) = monster_readonly.act(player.info(), world, rng);
+                if let Some(monster) = world.monster_on_pos(monster_position) {
+                    monster.ai_state = update.ai_state;
+                    monster.ap = Ranged::new(
+                        monster.ap.to_int(),
+                        InclusiveRange(monster.ap.min(), update.max_ap),
+                    );
+
+                    monster.spend_ap(1);
+                }
+                action
+            };
+
+            let (animated_monster_position, animation) = match action {
+                Action::Move(destination) => {
+                    assert_eq!(monster_position, monster_readonly.position);
+
+                    let pos = monster_readonly.position;
+
+                    let path_changed = monster_readonly
+                        .path
+                        .last()
+                        .is_none_or(|&cached_destination| cached_destination != destination);
+
+                    // NOTE: we keep a cache of any previously calculated
+                    // path in `monster.path`. If the precalculated path
+                    // is blocked or there is none, calculate a new one
+                    // and cache it. Otherwise, just walk it.
+                    let fleeing =
+                        flee_map.is_some() && ai::avoids_player(&monster_readonly, player.info());
+                    if fleeing
+                        && !flee_map_built
+                        && let Some(flee_map) = flee_map.as_deref_mut()
+                    {
+                        flee_map.build(
+                            area,
+                            &[player.pos],
+                            world,
+                            Blocker::WALL,
+                            player.pos,
+                            &MonsterCost,
+                        );
+                        flee_map.make_flee_map(world, Blocker::WALL, player.pos, &MonsterCost);
+                        flee_map_built = true;
+                    }
+                    let flee_map = flee_map
+                        .as_deref()
+                        .filter(|flee_map| fleeing && flee_map.value(pos).is_some());
+                    let chase_map = chase_map
+                        .as_deref()
+                        .filter(|chase_map| chase_map.value(pos).is_some());
+                    let chasing = destination == player.pos && monster_readonly.kind.is_monster();
+
+                    let (newpos, newpath) = if let Some(flee_map) = flee_map {
+                        // NOTE: the NPC ignores where it wanted to go
+                        // and just gets away from the player.
+                        let blockers =
+                            monster_readonly.blockers | Blocker::MONSTER | Blocker::PLAYER;
+                        let newpos = flee_map
+                            .next_step(pos, world, blockers, player.pos)
+                            .unwrap_or(pos);
+                        (newpos, vec![])
+                    } else if let Some(chase_map) = chase_map
+                        && chasing
+                    {
+                        // NOTE: chasers don't need a path of their
+                        // own, they just walk down the shared map.
+                        // If all the better tiles are taken by the
+                        // other monsters, they wait for them to move.
+                        let blockers =
+                            monster_readonly.blockers | Blocker::MONSTER | Blocker::PLAYER;
+                        let newpos = chase_map
+                            .next_step(pos, world, blockers, player.pos)
+                            .unwrap_or(pos);
+                        (newpos, vec![])
+                    } else if monster_readonly.path.is_empty()
+                        || path_changed
+                        || !world.walkable(
+                            monster_readonly.path[0],
+                            monster_readonly.blockers,
+                            player.pos,
+                        )
+                    {
+                        // Calculate a new path or recalculate the existing one.
+                        let mut path = pathfinding.find(
+                            pos,
+                            destination,
+                            world,
+                            monster_readonly.blockers,
+                            player.pos,
+                            formula::PATHFINDING_MONSTER_LIMIT,
+                            &MonsterCost,
+                        );
+                        let newpos = path.next().unwrap_or(pos);
+                        // Cache the path-finding result
+                        let newpath = path.collect();
+                        (newpos, newpath)
+                    } else {
+                        (monster_readonly.path[0], monster_readonly.path[1..].into())
+                    };
+
+                    world.move_monster(pos, newpos, player.pos);
+                    let exploration_radius = formula::exploration_radius(player.mind);
+                    let monster_visible =
+                        newpos.inside_circular_area(player.pos, exploration_radius);
+                    events.push(GameEvent::MonsterMoved {
+                        kind: monster_readonly.kind,
+                        from: pos,
+                        to: newpos,
+                        visible: monster_visible,
+                    });
+                    let monster_was_visible =
+                        pos.inside_circular_area(player.pos, exploration_radius);
+                    if monster_visible && !monster_was_visible && monster_readonly.kind.is_monster()
+                    {
+                        events.push(GameEvent::MonsterSpotted {
+                            kind: monster_readonly.kind,
+                            pos: newpos,
+                            player_pos: player.pos,
+                        });
+                    }
+                    if let Some(monster) = world.monster_on_pos(newpos) {
+                        monster.path = newpath;
+                        if monster.has_ap(1) {
+                            monster.trail = Some(newpos);
+                        }
+                    }
+
+                    let anim = animation::Move::ease(
+                        pos * tile_size,
+                        newpos * tile_size,
+                        formula::ANIMATION_MOVE_DURATION,
+                    );
+                    assert_eq!(anim.finished(), false);
+                    (newpos, anim)
+                }
+
+                Action::Attack(target_pos, damage) => {
+                    assert_eq!(target_pos, player.pos);
+                    player.take_effect(damage);
+                    events.push(GameEvent::MonsterAttacked {
+                        kind: monster_readonly.kind,
+                        effect: damage,
+                    });
+
+                    let anim = animation::Move::bounce(
+                        monster_readonly.position * (tile_size / 3),
+                        target_pos * (tile_size / 3),
+                        formula::ANIMATION_ATTACK_DURATION,
+                    );
+
+                    if monster_readonly.die_after_attack {
+                        kill_monster(monster_readonly.position, world, events);
+                        extra_animations.push(MotionAnimation {
+                            pos: monster_readonly.position,
+                            graphic: monster_readonly.graphic(),
+                            color: monster_readonly.color(palette),
+                            animation: anim.clone(),
+                        });
+                    }
+                    if !player.alive() {
+                        // NOTE: this monster killed the player, set the perpetrator.
+                        player.perpetrator = Some(monster_readonly.clone());
+                    }
+
+                    (monster_readonly.position, anim)
+                }
+
+                Action::Use(_) => {
+                    log::error!("Trying to run the Use action on a monster. That's not defined!");
+                    (monster_readonly.position, animation::Move::none())
+                }
+            };
+
+            if let Some(monster) = world.monster_on_pos(animated_monster_position) {
+                monster.motion_animation = animation;
+            }
+        }
+    }
+}
+
+fn process_player_action(
+    player: &mut player::Player,
+    commands: &mut VecDeque<Command>,
+    world: &mut World,
+    simulation_area: Rectangle,
+    explosion_animation: &mut Option<Box<dyn AreaOfEffect>>,
+    rng: &mut Random,
+    tile_size: i32,
+    palette: &Palette,
+    events: &mut Vec<GameEvent>,
+    pathfinding: &mut PathfindingContext,
+) {
+    log::trace!("Processing player action");
+    if !player.alive() {
+        log::trace!("Processing player action, but the player is dead.");
+        return;
+    }
+    if !player.has_ap(1) {
+        log::trace!(
+            "Processing player action, but the player has no AP: {}",
+            player.ap()
+        );
+        return;
+    }
+
+    if let Some(command) = commands.pop_front() {
+        log::trace!("Player Command: {:?}", command);
+        let mut action = match command {
+            Command::N => Action::Move(player.pos + (0, -1)),
+            Command::S => Action::Move(player.pos + (0, 1)),
+            Command::W => Action::Move(player.pos + (-1, 0)),
+            Command::E => Action::Move(player.pos + (1, 0)),
+
+            Command::NW => Action::Move(player.pos + (-1, -1)),
+            Command::NE => Action::Move(player.pos + (1, -1)),
+            Command::SW => Action::Move(player.pos + (-1, 1)),
+            Command::SE => Action::Move(player.pos + (1, 1)),
+
+            Command::UseFood => Action::Use(item::Kind::Food),
+            Command::UseDose => Action::Use(item::Kind::Dose),
+            Command::UseCardinalDose => Action::Use(item::Kind::CardinalDose),
+            Command::UseDiagonalDose => Action::Use(item::Kind::DiagonalDose),
+            Command::UseStrongDose => Action::Use(item::Kind::StrongDose),
+
+            // NOTE: ignore, this has been processed elsewhere
+            Command::WalkPath => return,
+
+            Command::ShowMessageBox {
+                ttl,
+                title,
+                message,
+            } => {
+                events.push(GameEvent::MessageShown {
+                    title,
+                    message,
+                    ttl: Some(ttl),
+                });
+                return;
+            }
+        };
+        log::trace!("Action from Command: {:?}", action);
+
+        if player.stun.to_int() > 0 {
+            events.push(GameEvent::PlayerStunned);
+            action = Action::Move(player.pos);
+        } else if player.panic.to_int() > 0 {
+            events.push(GameEvent::PlayerPanicked);
+            let new_pos =
+                world.random_neighbour_position(rng, player.pos, Blocker::WALL, player.pos);
+            action = Action::Move(new_pos);
+        } else if let Some((dose_pos, dose)) = world.nearest_dose(player.pos, 5) {
+            let resist_radius =
+                formula::player_resist_radius(dose.irresistible, player.will.to_int()) as usize;
+            if player.pos.tile_distance(dose_pos) < resist_radius as i32 {
+                // We're already in the resist radius so we don't care about the cost.
+                let mut path = pathfinding.find(
+                    player.pos,
+                    dose_pos,
+                    world,
+                    Blocker::WALL,
+                    player.pos,
+                    formula::PATHFINDING_DOSE_RESIST_LIMIT,
+                    &DirectCost,
+                );
+
+                let new_pos_opt = if path.len() <= resist_radius {
+                    path.next()
+                } else {
+                    None
+                };
+
+                if let Some(new_pos) = new_pos_opt {
+                    action = Action::Move(new_pos);
+                } else {
+                    // NOTE: no path leading to the irresistible dose
+                }
+            }
+        }
+
+        // NOTE: If we have doses in the inventory that we wouldn't be
+        // able to pick up anymore, use them up one by one each turn:
+        let carried_irresistible_dose = player
+            .inventory
+            .iter()
+            .find(|i| {
+                i.is_dose()
+                    && formula::player_resist_radius(i.irresistible, player.will.to_int()) > 0
+            })
+            .map(|i| i.kind);
+        if let Some(kind) = carried_irresistible_dose {
+            action = Action::Use(kind);
+        }
+
+        log::trace!("Final Action: {:?}", action);
+        match action {
+            Action::Move(dest) => {
+                let dest_walkable =
+                    world.walkable(dest, Blocker::WALL | Blocker::MONSTER, player.pos);
+                let bumping_into_monster = world.monster_on_pos(dest).is_some();
+                if bumping_into_monster {
+                    player.spend_ap(1);
+                    // info!("Player attacks {:?}", monster);
+                    player.motion_animation = animation::Move::bounce(
+                        player.pos * (tile_size / 3),
+                        dest * (tile_size / 3),
+                        formula::ANIMATION_ATTACK_DURATION,
+                    );
+                    if let Some(kind) = world.monster_on_pos(dest).map(|m| m.kind) {
+                        events.push(GameEvent::PlayerAttacked { kind, pos: dest });
+                        match kind {
+                            monster::Kind::Anxiety => {
+                                log::debug!(
+                                    "Bumped into anxiety! Current anxiety counter: {:?}",
+                                    player.anxiety_counter
+                                );
+                                let increment =
+                                    if player.bonuses.contains(&CompanionBonus::DoubleWillGrowth) {
+                                        2
+                                    } else {
+                                        1
+                                    };
+                                log::debug!("Anxiety increment: {:?}", increment);
+                                player.anxiety_counter += increment;
+                                log::debug!("New anxiety counter: {:?}", player.anxiety_counter);
+                                events.push(GameEvent::AnxietyFaced {
+                                    counter: player.anxiety_counter.to_int(),
+                                    max: player.anxiety_counter.max(),
+                                });
+                                if player.anxiety_counter.is_max() {
+                                    log::info!("Increasing player's will");
+                                    player.will += 1;
+                                    player.anxiety_counter.set_to_min();
+                                    events.push(GameEvent::WillGrew {
+                                        will: player.will.to_int(),
+                                    });
+                                }
+                            }
+                            monster::Kind::Hunger => {
+                                let modifier = Modifier::Attribute {
+                                    state_of_mind: 3,
+                                    will: 0,
+                                };
+                                player.take_effect(modifier);
+                                events.push(GameEvent::HungerFought { effect: modifier });
+                            }
+                            // NOTE: NPCs don't give bonuses or accompany the player when high.
+                            monster::Kind::Npc if player.mind.is_sober() => {
+                                if let Some(monster) = world.monster_on_pos(dest) {
+                                    log::info!("Bumped into NPC: {}", monster);
+                                }
+
+                                // Clear any existing monsters accompanying the player. The player
+                                // can have only one companion at a time right now.
+                                //
+                                // TODO: it also sounds like we could just track the followers in
+                                // the Player/State struct but that needs Monster IDs.
+                                let npcs = world
+                                    .monsters_mut(simulation_area)
+                                    .filter(|m| m.kind == monster::Kind::Npc);
+                                for npc in npcs {
+                                    if npc.position == dest {
+                                        log::info!("NPC {} accompanies the player.", npc);
+                                        npc.accompanying_player = true;
+                                        player.npcs_met += 1;
+                                        assert!(npc.companion_bonus.is_some());
+                                        if let Some(bonus) = npc.companion_bonus {
+                                            events.push(GameEvent::NpcMet(bonus));
+                                        }
+                                    } else if npc.accompanying_player {
+                                        log::info!("NPC {} leaves the player.", npc);
+                                        npc.accompanying_player = false;
+                                        events.push(GameEvent::CompanionLeft);
+                                    }
+                                }
+                            }
+
+                            monster::Kind::Signpost => {
+                                log::info!("Bumped into a signpost!");
+                                let message = "\"I thought you were going to stay sober for good. I was wrong. Goodbye.\"";
+                                events.push(GameEvent::MessageShown {
+                                    title: "The signpost says".to_string(),
+                                    message: message.to_string(),
+                                    ttl: None,
+                                });
+                            }
+
+                            _ => {}
+                        }
+                        if kill_monster(dest, world, events) {
+                            player.monsters_killed += 1;
+                        }
+                    }
+                } else if dest_walkable {
+                    player.spend_ap(1);
+                    player.motion_animation = animation::Move::ease(
+                        player.pos * tile_size,
+                        dest * tile_size,
+                        formula::ANIMATION_MOVE_DURATION,
+                    );
+                    let previous_pos = player.pos;
+                    player.move_to(dest);
+                    events.push(GameEvent::PlayerMoved {
+                        from: previous_pos,
+                        to: dest,
+                    });
+                    spot_monsters_in_view(world, previous_pos, player, events);
+                    while let Some(item) = world.pickup_item(dest) {
+                        use crate::item::Kind::*;
+                        match item.kind {
+                            Food => {
+                                events.push(GameEvent::ItemPickedUp(item.kind));
+                                player.inventory.push(item);
+                            }
+                            Dose | StrongDose | CardinalDose | DiagonalDose => {
+                                let resist_radius = formula::player_resist_radius(
+                                    item.irresistible,
+                                    player.will.to_int(),
+                                );
+                                if resist_radius == 0 {
+                                    events.push(GameEvent::ItemPickedUp(item.kind));
+                                    player.inventory.push(item);
+                                } else {
+                                    events.push(GameEvent::DoseIrresistible(item.kind));
+                                    use_dose(player, explosion_animation, item, palette, events);
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    // NOTE: we bumped into a wall, don't do anything
+                }
+            }
+
+            Action::Use(item::Kind::Food) => {
+                if let Some(food_idx) = player
+                    .inventory
+                    .iter()
+                    .position(|&i| i.kind == item::Kind::Food)
+                {
+                    player.spend_ap(1);
+                    let food = player.inventory.remove(food_idx);
+                    player.take_effect(food.modifier);
+                    events.push(GameEvent::FoodEaten {
+                        effect: food.modifier,
+                    });
+                    let food_explosion_radius = 2;
+                    let animation = animation::SquareExplosion::new(
+                        player.pos,
+                        food_explosion_radius,
+                        1,
+                        palette.explosion,
+                    );
+                    *explosion_animation = Some(Box::new(animation));
+                }
+            }
+
+            Action::Use(item::Kind::Dose) => {
+                if let Some(dose_index) = player
+                    .inventory
+                    .iter()
+                    .position(|&i| i.kind == item::Kind::Dose)
+                {
+                    player.spend_ap(1);
+                    let dose = player.inventory.remove(dose_index);
+                    use_dose(player, explosion_animation, dose, palette, events);
+                }
+            }
+
+            Action::Use(item::Kind::StrongDose) => {
+                if let Some(dose_index) = player
+                    .inventory
+                    .iter()
+                    .position(|&i| i.kind == item::Kind::StrongDose)
+                {
+                    player.spend_ap(1);
+                    let dose = player.inventory.remove(dose_index);
+                    use_dose(player, explosion_animation, dose, palette, events);
+                }
+            }
+
+            Action::Use(item::Kind::CardinalDose) => {
+                if let Some(dose_index) = player
+                    .inventory
+                    .iter()
+                    .position(|&i| i.kind == item::Kind::CardinalDose)
+                {
+                    player.spend_ap(1);
+                    let dose = player.inventory.remove(dose_index);
+                    use_dose(player, explosion_animation, dose, palette, events);
+                }
+            }
+
+            Action::Use(item::Kind::DiagonalDose) => {
+                if let Some(dose_index) = player
+                    .inventory
+                    .iter()
+                    .position(|&i| i.kind == item::Kind::DiagonalDose)
+                {
+                    player.spend_ap(1);
+                    let dose = player.inventory.remove(dose_index);
+                    use_dose(player, explosion_animation, dose, palette, events);
+                }
+            }
+
+            Action::Attack(destination, player_modifier) => {
+                log::error!(
+                    "Player tried to use the `Attack` action. This should not be possible (attack is done via moving). Destination: {:?}, player modifier: {:?}",
+                    destination,
+                    player_modifier
+                );
+                player.spend_ap(1);
+            }
+        }
+    } else {
+        log::trace!("No Command found");
+    }
+}
+
+fn process_player(state: &mut State, tile_size: i32, simulation_area: Rectangle) {
+    {
+        log::trace!("Processing player");
+
+        // appease borrowck
+        let player = &mut state.player;
+
+        // NPCs should unfollow an intoxicated player:
+        if player.mind.is_high() {
+            let npcs = state.world.monsters_mut(simulation_area).filter(|m| {
+                m.kind == monster::Kind::Npc && m.accompanying_player && m.companion_bonus.is_some()
+            });
+            for npc in npcs {
+                log::info!("{:?} will not accompany an intoxicated player.", npc);
+                npc.accompanying_player = false;
+            }
+        }
+
+        let world = &state.world;
+
+        // NOTE: if the player manages to outrun the NPC (they follow
+        // the player but it still can happen), the bonus will
+        // disappear.
+        let npc_bonuses = world
+            .monsters(simulation_area)
+            .filter(|m| {
+                m.kind == monster::Kind::Npc && m.accompanying_player && m.companion_bonus.is_some()
+            })
+            .map(|m| {
+                // NOTE: this unwrap should always succeed due to the
+                // filter check above. Providing a fallback to prevent
+                // any crasches caused by future refactoring.
+                m.companion_bonus.unwrap_or_else(|| {
+                    log::error!(
+                        "Trying to get a companion bonus where one doesn't exist, but  it should."
+                    );
+                    CompanionBonus::DoubleWillGrowth
+                })
+            });
+        player.bonuses.clear();
+        player.bonuses.extend(npc_bonuses);
+
+        for &bonus in &player.bonuses {
+            if bonus != CompanionBonus::Victory && !player.companions_met.contains(&bonus) {
+                player.companions_met.push(bonus);
+                state.events.push(GameEvent::CompanionMet(bonus));
+            }
+        }
+    }
+
+    let path_allowed = state.mouse_path_allowed();
+
+    log::trace!(
+        "left down: {}, path allowed: {}, walking timer done: {}",
+        state.mouse.left_is_down,
+        path_allowed,
+        state.path_walking_timer.finished()
+    );
+    log::trace!("Player path: {:?}", state.player_path);
+
+    let walk_the_path_command = state.commands.front() == Some(&Command::WalkPath);
+    if walk_the_path_command {
+        state.commands.pop_front();
+    }
+
+    // NOTE: If the player is following a path move them one step along the path
+    if (walk_the_path_command || (state.mouse.left_is_down && path_allowed))
+        && state.path_walking_timer.finished()
+    {
+        state.path_walking_timer.reset();
+        if let Some(destination) = state.player_path.next() {
+            let command = match destination - state.player.pos {
+                Point { x: 0, y: -1 } => Some(Command::N),
+                Point { x: 0, y: 1 } => Some(Command::S),
+                Point { x: -1, y: 0 } => Some(Command::W),
+                Point { x: 1, y: 0 } => Some(Command::E),
+
+                Point { x: -1, y: -1 } => Some(Command::NW),
+                Point { x: -1, y: 1 } => Some(Command::SW),
+                Point { x: 1, y: -1 } => Some(Command::NE),
+                Point { x: 1, y: 1 } => Some(Command::SE),
+
+                unexpected => {
+                    log::debug!("Unexpected command point: {:?}", unexpected);
+                    None
+                }
+            };
+            if let Some(command) = command {
+                log::trace!("Pushing mouse trail command: {:?}", command);
+                state.commands.push_front(command);
+            } else {
+                log::trace!("We're in the player path section, but no command is pushed.");
+            }
+        }
+    }
+
+    log::trace!("Commands: {:?}", state.commands);
+    let previous_action_points = state.player.ap();
+    let first_action_event = state.events.len();
+    process_player_action(
+        &mut state.player,
+        &mut state.commands,
+        &mut state.world,
+        simulation_area,
+        &mut state.explosion_animation,
+        &mut state.rng,
+        tile_size,
+        &state.palette,
+        &mut state.events,
+        &mut state.pathfinding,
+    );
+    log::trace!("player action processed");
+
+    // NOTE: the endgame screen gives the player tips based on these
+    for event in &state.events[first_action_event..] {
+        match event {
+            GameEvent::ItemPickedUp(kind) if kind.is_dose() => {
+                state.player_picked_up_a_dose = true;
+            }
+            GameEvent::PlayerAttacked { kind, .. } if kind.is_monster() => {
+                state.player_bumped_into_a_monster = true;
+            }
+            _ => {}
+        }
+    }
+
+    let spent_ap_this_turn = previous_action_points > state.player.ap();
+    log::trace!("Player spent AP this turn: {}", spent_ap_this_turn);
+
+    // Place the Victory NPC if the player behaved themself.
+    if state.player.will.is_max() && !state.player.mind.is_high() && state.victory_npc_id.is_none()
+    {
+        place_victory_npc(state);
+    }
+
+    if spent_ap_this_turn {
+        update_streaks(&mut state.player);
+    }
+
+    // NOTE: The player has reached the Victory NPC. Win the game! \o/
+    if state.player.bonuses.contains(&CompanionBonus::Victory) {
+        win_the_game(state);
+    }
+
+    state.world.explore(
+        state.player.pos,
+        formula::exploration_radius(state.player.mind),
+    );
+}
+
+/// Kill the monster at `monster_position` (if any). Returns `true`
+/// if it was an actual monster (not an NPC or a signpost) and it died.
+fn kill_monster(monster_position: Point, world: &mut World, events: &mut Vec<GameEvent>) -> bool {
+    let invincible = world
+        .monster_on_pos(monster_position)
+        .is_some_and(|m| m.invincible);
+    let mut killed_a_monster = false;
+    if invincible {
+        // It's invincible: no-op
+    } else {
+        if let Some(monster) = world.monster_on_pos(monster_position) {
+            log::debug!("Killing monster: {:?}", monster);
+            monster.dead = true;
+            events.push(GameEvent::MonsterKilled {
+                kind: monster.kind,
+                pos: monster_position,
+            });
+            if monster.kind.is_monster() {
+                killed_a_monster = true;
+            }
+        }
+        world.remove_monster(monster_position);
+    }
+    killed_a_monster
+}
+
+/// Report the monsters that were not visible from `previous_pos`
+/// but are visible now that the player moved.
+fn spot_monsters_in_view(
+    world: &World,
+    previous_pos: Point,
+    player: &player::Player,
+    events: &mut Vec<GameEvent>,
+) {
+    let radius = formula::exploration_radius(player.mind);
+    let area = Rectangle::center(player.pos, Point::from_i32(radius));
+    for monster in world.monsters(area) {
+        let visible = monster.position.inside_circular_area(player.pos, radius);
+        let was_visible = monster.position.inside_circular_area(previous_pos, radius);
+        if monster.kind.is_monster() && visible && !was_visible {
+            events.push(GameEvent::MonsterSpotted {
+                kind: monster.kind,
+                pos: monster.position,
+                player_pos: player.pos,
+            });
+        }
+    }
+}
+
+fn use_dose(
+    player: &mut player::Player,
+    explosion_animation: &mut Option<Box<dyn AreaOfEffect>>,
+    item: item::Item,
+    palette: &Palette,
+    events: &mut Vec<GameEvent>,
+) {
+    use crate::{item::Kind::*, player::Modifier::*};
+    log::debug!("Using dose");
+    if let Intoxication { state_of_mind, .. } = item.modifier {
+        let radius = if state_of_mind <= 100 { 4 } else { 6 };
+        player.take_effect(item.modifier);
+        player.doses_used += 1;
+        events.push(GameEvent::DoseUsed {
+            kind: item.kind,
+            mind: player.mind,
+        });
+        let animation: Box<dyn AreaOfEffect> = match item.kind {
+            Dose | StrongDose => Box::new(animation::SquareExplosion::new(
+                player.pos,
+                radius,
+                2,
+                palette.explosion,
+            )),
+            CardinalDose => Box::new(animation::CardinalExplosion::new(
+                player.pos,
+                radius,
+                2,
+                palette.explosion,
+                palette.shattering_explosion,
+            )),
+            DiagonalDose => Box::new(animation::DiagonalExplosion::new(
+                player.pos,
+                radius,
+                2,
+                palette.explosion,
+                palette.shattering_explosion,
+            )),
+            Food => {
+                wtf!("Tried to use `Food` in `use_dose`. This shouldn't happen.");
+                Box::new(animation::SquareExplosion::new(
+                    player.pos,
+                    0,
+                    0,
+                    palette.explosion,
+                ))
+            }
+        };
+        *explosion_animation = Some(animation);
+    } else {
+        wtf!(
+            "Tried to use a dose but `item.modifier` was not of the `Intoxication` variant: {:?}",
+            item.modifier
+        );
+    }
+}
+
+/// Set the longest high and sober streaks after a turn the player
+/// spent. Withdrawal breaks both of them.
+fn update_streaks(player: &mut player::Player) {
+    match player.mind {
+        Mind::High(_) => {
+            player.current_high_streak += 1;
+            player.longest_high_streak = player.longest_high_streak.max(player.current_high_streak);
+            player.current_sober_streak = 0;
+        }
+        Mind::Sober(_) => {
+            player.current_high_streak = 0;
+            player.current_sober_streak += 1;
+            player.longest_sober_streak =
+                player.longest_sober_streak.max(player.current_sober_streak);
+        }
+        Mind::Withdrawal(_) => {
+            player.current_high_streak = 0;
+            player.current_sober_streak = 0;
+        }
+    }
+}
+
+pub(crate) fn place_victory_npc(state: &mut State) -> Point {
+    log::info!("Generating the Victory NPC!");
+    let mut distance_range = formula::VICTORY_NPC_DISTANCE;
+    // NOTE: Compute path to Victory NPC that is reachable by the
+    // player. This may take several attempts. Leave the position
+    // immutable at the end.
+    let mut vnpc_pos;
+    let mut attempts = 250;
+    let blockers = Blocker::WALL | Blocker::MONSTER;
+    loop {
+        if attempts <= 0 {
+            // TODO: generate VNPC at a shorter distance instead of crashing?
+            log::warn!("Could not find a viable Victory NPC position in 250 tries.");
+            let min = distance_range.0 - 20;
+            let max = distance_range.1 - 20;
+            if min > 5 && max > 5 {
+                distance_range = InclusiveRange(min, max);
+                attempts = 20;
+                log::info!("Reduced VNPC spawn range to: {:?}.", distance_range);
+            } else {
+                log::warn!(
+                    "Could not find a viable Victory NPC position anywhere! Winning game instead."
+                );
+                state.player.bonuses.push(monster::CompanionBonus::Victory);
+                win_the_game(state);
+                return state.player.pos;
+            }
+        } else {
+            attempts -= 1;
+        }
+
+        // NOTE: this is a little convoluted. We test if the Victory
+        // NPC position is walkable. And if it's not, we try other
+        // positions in its immediate vicinity instead of generating a
+        // new candidate position via `formula::victory_npc_position`.
+        //
+        // We do this, because the walkability test requires we have a
+        // World Chunk in place and generating these can be expensive.
+        // So if we picked a completely random position every time, we
+        // could end up generating a lot of chunks for no immediate
+        // reason.
+        //
+        // What we do instead is generate one Chunk for the given
+        // position and then try nearby areas (which will be
+        // overwhelmingly likely in the same Chunk).
+        vnpc_pos = formula::victory_npc_position(&mut state.rng, state.player.pos, distance_range);
+        log::info!("Trying to find test NPC position {:?}", vnpc_pos);
+        state.world.ensure_chunk_at_pos(vnpc_pos);
+        if let Some(pos) = walkable_place_nearby(&state.world, vnpc_pos, blockers, state.player.pos)
+        {
+            log::info!("Position {:?} is walkable!", pos);
+            vnpc_pos = pos;
+            for cell_pos in point::Line::new(state.player.pos, vnpc_pos) {
+                state.world.ensure_chunk_at_pos(cell_pos);
+            }
+        } else {
+            log::warn!(
+                "Failed to find empty place around the candidate VNPC position {:?}",
+                vnpc_pos
+            );
+            continue;
+        }
+
+        log::info!(
+            "player pos: {:?}, vnpc pos: {:?}",
+            state.player.pos,
+            vnpc_pos
+        );
+        // TODO: make sure the world chunks exist before trying to find path
+        let path_to_vnpc = if state.rules_version >= 3 {
+            state.chunk_graph.find(
+                state.player.pos,
+                vnpc_pos,
+                &state.world,
+                blockers,
+                state.player.pos,
+                formula::PATHFINDING_VNPC_CHUNK_LIMIT,
+                &mut state.pathfinding,
+                &DirectCost,
+            )
+        } else {
+            state.pathfinding.find(
+                state.player.pos,
+                vnpc_pos,
+                &state.world,
+                blockers,
+                state.player.pos,
+                formula::PATHFINDING_VNPC_REACHABILITY_LIMIT,
+                &DirectCost,
+            )
+        };
+        if path_to_vnpc.is_empty() {
+            log::warn!("Failed to find path from player to Victory NPC!")
+        } else {
+            log::info!("Path to Victory NPC takes {} steps", path_to_vnpc.len());
+            break;
+        }
+    }
+    let vnpc_pos = vnpc_pos;
+
+    if let Some(prev_npc_id) = state.victory_npc_id.take() {
+        log::warn!("Replacing an existing NPC! {:?}", prev_npc_id);
+        state.world.remove_monster_by_id(prev_npc_id);
+    }
+
+    // NOTE: Uncover the map leading to the Victory NPC position
+    let positions = point::Line::new(state.player.pos, vnpc_pos);
+    for cell_pos in positions {
+        state.world.ensure_chunk_at_pos(cell_pos);
+        // NOTE: the map area depends on the window and tile size so
+        // it must not change which chunks get generated.
+        let display_half_size = if state.rules_version >= 4 {
+            Point::from_i32(crate::DISPLAYED_MAP_SIZE) / 2
+        } else {
+            state.map_size / 2
+        };
+        // NOTE: make sure every cell that will be shown has a chunk.
+        //
+        // If we didn't do this, we would get blank places when the line would cross a boundary
+        // of two chunks, but the surrounding chunks were not brought in.
+        state
+            .world
+            .ensure_chunk_at_pos(cell_pos + (display_half_size.x, display_half_size.y));
+        state
+            .world
+            .ensure_chunk_at_pos(cell_pos + (-display_half_size.x, display_half_size.y));
+        state
+            .world
+            .ensure_chunk_at_pos(cell_pos + (display_half_size.x, -display_half_size.y));
+        state
+            .world
+            .ensure_chunk_at_pos(cell_pos + (-display_half_size.x, -display_half_size.y));
+        state.world.always_visible(cell_pos, 2);
+        state.world.explore(cell_pos, 4);
+    }
+    state.world.explore(vnpc_pos, 5);
+    state.world.always_visible(vnpc_pos, 2);
+
+    if let Some(chunk) = state.world.chunk_mut(vnpc_pos) {
+        let mut monster = monster::Monster::new(monster::Kind::Npc, vnpc_pos, state.challenge);
+        monster.companion_bonus = Some(CompanionBonus::Victory);
+        // NOTE: The NPCs have the same colour range as the player,
+        // but let's always pick a colour that's different from the
+        // current player's one.
+        monster.npc_color_index = {
+            let mut index: usize = 0;
+            for _ in 0..5 {
+                let pick = state
+                    .rng
+                    .range_inclusive(0, state.palette.player.len() as i32 - 1)
+                    as usize;
+                if pick != state.player.color_index {
+                    index = pick;
+                    break;
+                }
+            }
+            index
+        };
+
+        monster.ai_state = ai::AIState::NoOp;
+        let id = chunk.add_monster(monster);
+        state.victory_npc_id = Some(id);
+    }
+
+    // NOTE: Scroll to the Victory NPC position
+    let ms = if state.replay_full_speed { 1000 } else { 2000 };
+    state.pos_timer = Timer::new(Duration::from_millis(ms));
+    state.old_screen_pos = state.screen_position_in_world;
+    state.new_screen_pos = vnpc_pos;
+
+    state.events.push(GameEvent::VictoryNpcPlaced {
+        pos: vnpc_pos,
+        player_pos: state.player.pos,
+    });
+
+    vnpc_pos
+}
+
+/// The player has reached the Victory NPC.
+fn win_the_game(state: &mut State) {
+    state.side = Side::Victory;
+    state.game_session = GameSession::Ended;
+    state.timeline.record(state.turn, &state.player);
+    state.events.push(GameEvent::Victory);
+}
+
+/// Return a point close to the given one that is walkable.
+fn walkable_place_nearby(
+    world: &World,
+    pos: Point,
+    blockers: Blocker,
+    player_pos: Point,
+) -> Option<Point> {
+    // Radius `2` means the central point and the eight surrounding ones.
+    point::SquareArea::new(pos, 2).find(|&point| world.walkable(point, blockers, player_pos))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{palette::Palette, state::Challenge};
+
+    fn new_game(seed: u32) -> State {
+        let exit_after = false;
+        let debug = false;
+        let replay_path = None;
+        let mut state = State::new_game(
+            crate::WORLD_SIZE,
+            Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+            crate::PANEL_WIDTH,
+            exit_after,
+            debug,
+            seed,
+            replay_path,
+            Challenge::default(),
+            Palette::classic(),
+        );
+        state.generate_world();
+        state.game_session = GameSession::InProgress;
+        state
+    }
+
+    #[test]
+    fn test_step_plays_the_command_out() {
+        let mut state = new_game(1);
+        state.player.inventory.push(formula::FOOD_PREFAB);
+        let events = step(&mut state, Command::UseFood);
+        assert!(events.contains(&GameEvent::FoodEaten {
+            effect: formula::FOOD_PREFAB.modifier
+        }));
+        assert!(state.commands.is_empty());
+        assert!(state.explosion_animation.is_none());
+    }
+
+    #[test]
+    fn test_step_records_bumping_into_a_monster() {
+        let mut state = new_game(1);
+        let pos = state.player.pos + (1, 0);
+        state.world.remove_monster(pos);
+        let hunger = monster::Monster::new(monster::Kind::Hunger, pos, state.challenge);
+        if let Some(chunk) = state.world.chunk_mut(pos) {
+            chunk.add_monster(hunger);
+        }
+        assert!(!state.player_bumped_into_a_monster);
+
+        let events = step(&mut state, Command::E);
+        assert!(events.contains(&GameEvent::PlayerAttacked {
+            kind: monster::Kind::Hunger,
+            pos,
+        }));
+        assert!(state.player_bumped_into_a_monster);
+    }
+
+    #[test]
+    fn test_step_leaves_the_event_log_to_the_frontend() {
+        let mut state = new_game(1);
+        state.player.inventory.push(formula::FOOD_PREFAB);
+        let mut events = step(&mut state, Command::UseFood);
+        for command in [Command::N, Command::E, Command::S, Command::W] {
+            events.extend(step(&mut state, command));
+        }
+        assert!(!events.is_empty());
+        assert!(state.event_log.is_empty());
+    }
+
+    #[test]
+    fn test_message_box_is_an_event() {
+        let mut state = new_game(1);
+        let events = step(
+            &mut state,
+            Command::ShowMessageBox {
+                ttl: Duration::from_secs(1),
+                title: "Title".to_string(),
+                message: "Message".to_string(),
+            },
+        );
+        assert_eq!(
+            events,
+            vec![GameEvent::MessageShown {
+                title: "Title".to_string(),
+                message: "Message".to_string(),
+                ttl: Some(Duration::from_secs(1)),
+            }]
+        );
+        assert!(state.events.is_empty());
+    }
+
+    #[test]
+    fn test_victory_npc_is_reachable() {
+        let mut state = new_game(1);
+        assert!(state.rules_version >= 3);
+        let vnpc_pos = place_victory_npc(&mut state);
+        assert_ne!(vnpc_pos, state.player.pos);
+        assert_eq!(state.side, Side::Player);
+        let path = state.chunk_graph.find(
+            state.player.pos,
+            vnpc_pos,
+            &state.world,
+            Blocker::WALL,
+            state.player.pos,
+            formula::PATHFINDING_VNPC_CHUNK_LIMIT,
+            &mut state.pathfinding,
+            &DirectCost,
+        );
+        assert!(path.len() >= formula::VICTORY_NPC_DISTANCE.0 as usize - 20);
+    }
+
+    #[test]
+    fn test_undo_goes_back_a_turn() {
+        let mut state = new_game(1);
+        state.challenge.practice = true;
+        state.player.inventory.push(formula::FOOD_PREFAB);
+        state.save_undo_snapshot();
+        let before = state.verification();
+        assert!(!state.can_undo());
+
+        step(&mut state, Command::UseFood);
+        assert!(state.turn > before.turn);
+        assert!(state.can_undo());
+
+        assert!(state.undo());
+        assert_eq!(state.verification(), before);
+        assert_eq!(state.undos, 1);
+        assert!(!state.undo());
+    }
+
+    #[test]
+    fn test_undo_forgets_the_cached_paths() {
+        let mut state = new_game(1);
+        state.challenge.practice = true;
+        state.save_undo_snapshot();
+        for command in [Command::N, Command::E, Command::S, Command::W] {
+            step(&mut state, command);
+        }
+        let vnpc_pos = place_victory_npc(&mut state);
+        state.chunk_graph.find(
+            state.player.pos,
+            vnpc_pos,
+            &state.world,
+            Blocker::WALL,
+            state.player.pos,
+            formula::PATHFINDING_VNPC_CHUNK_LIMIT,
+            &mut state.pathfinding,
+            &DirectCost,
+        );
+        assert!(!state.chunk_graph.is_empty());
+
+        assert!(state.undo());
+        assert!(state.chunk_graph.is_empty());
+        assert_eq!(state.chase_map.value(state.player.pos), None);
+    }
+
+    #[test]
+    fn test_npc_keeps_away_from_the_high_player() {
+        let mut state = new_game(1);
+        assert!(state.rules_version >= 5);
+        let npc_pos = state.player.pos + (2, 0);
+        state.world.remove_monster(npc_pos);
+        let mut npc = monster::Monster::new(monster::Kind::Npc, npc_pos, state.challenge);
+        npc.new_turn();
+        if let Some(chunk) = state.world.chunk_mut(npc_pos) {
+            chunk.add_monster(npc);
+        }
+        state.player.mind = Mind::High(Ranged::new(formula::HIGH.1 / 2, formula::HIGH));
+
+        let mut npc_steps = 0;
+        for _ in 0..5 {
+            for event in step(&mut state, Command::W) {
+                if let GameEvent::MonsterMoved {
+                    kind: monster::Kind::Npc,
+                    from,
+                    to,
+                    ..
+                } = event
+                {
+                    npc_steps += 1;
+                    assert!(state.player.pos.distance(to) > state.player.pos.distance(from));
+                }
+            }
+        }
+        assert!(npc_steps > 0);
+    }
+
+    #[test]
+    fn test_withdrawal_breaks_the_sober_streak() {
+        let mut state = new_game(1);
+        let player = &mut state.player;
+
+        player.mind = Mind::Sober(Ranged::new_max(formula::SOBER));
+        update_streaks(player);
+        update_streaks(player);
+        assert_eq!(player.current_sober_streak, 2);
+
+        player.mind = Mind::Withdrawal(Ranged::new_max(formula::WITHDRAWAL));
+        update_streaks(player);
+        assert_eq!(player.current_sober_streak, 0);
+        assert_eq!(player.current_high_streak, 0);
+        assert_eq!(player.longest_sober_streak, 2);
+
+        player.mind = Mind::High(Ranged::new_max(formula::HIGH));
+        update_streaks(player);
+        assert_eq!(player.current_high_streak, 1);
+        assert_eq!(player.current_sober_streak, 0);
+    }
+
+    #[test]
+    fn test_step_is_deterministic() {
+        let commands = [
+            Command::N,
+            Command::E,
+            Command::E,
+            Command::S,
+            Command::UseDose,
+            Command::W,
+            Command::SW,
+            Command::NW,
+        ];
+        let mut first = new_game(42);
+        let mut second = new_game(42);
+        for command in commands {
+            let first_events = step(&mut first, command.clone());
+            let second_events = step(&mut second, command);
+            assert_eq!(first_events, second_events);
+            assert_eq!(first.verification(), second.verification());
+        }
+    }
+}
@@ -1,26 +1,31 @@
 use crate::{
+    achievements,
     animation::{self, AreaOfEffect, ScreenFade},
     color::Color,
+    daily,
     engine::Mouse,
+    event_log::EventLog,
     formula,
     graphic::Graphic,
+    history::RunRecord,
     keys::{Key, Keys},
     monster,
     palette::Palette,
-    pathfinding::Path,
-    player::Player,
+    pathfinding::{ChunkGraph, DijkstraMap, Path, PathfindingContext},
+    player::{Bonus, Player},
     point::Point,
     random::Random,
+    simulation::GameEvent,
     stats::Stats,
+    timeline::Timeline,
     timer::Timer,
-    util,
     window::Window,
     windows,
     world::{MonsterId, World},
 };
 
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     error::Error,
     fs::File,
     io::{self, Write},
@@ -28,9 +33,6 @@ use std::{
     time::Duration,
 };
 
-#[cfg(feature = "replay")]
-use std::fs;
-
 use serde::{Deserialize, Serialize};
 
 const CHUNK_SIZE: i32 = 32;
@@ -89,6 +91,13 @@ pub enum Command {
     WalkPath,
 }
 
+/// A name the player gave to a tile, e.g. "dose cache".
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub pos: Point,
+    pub name: String,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum VerificationWrapper {
     Verification(Verification),
@@ -102,6 +111,12 @@ pub struct Input {
     pub mouse: Mouse,
     pub tick_id: i32,
     pub verification: VerificationWrapper,
+    /// The waypoint the player picked on the map since the last tick.
+    #[serde(default)]
+    pub waypoint: Option<Point>,
+    /// The player went back a turn since the last tick.
+    #[serde(default)]
+    pub undo: bool,
 }
 
 pub fn generate_replay_path() -> Option<PathBuf> {
@@ -113,13 +128,10 @@ pub fn generate_replay_path() -> Option<PathBuf> {
         // Timestamp in format: 2016-11-20T20-04-39.123. We can't use the
         // colons in the timestamp -- Windows don't allow them in a path.
         let timestamp = local_time.format("%FT%H-%M-%S%.3f");
-        let replay_dir = &std::path::Path::new("replays");
-        assert!(replay_dir.is_relative());
-        if !replay_dir.exists() {
-            let _ = fs::create_dir_all(replay_dir);
-        }
-        let replay_path = &replay_dir.join(format!("replay-{timestamp}"));
-        Some(replay_path.into())
+        let replay_dir = crate::paths::replay_dir();
+        crate::paths::ensure_dir(&replay_dir);
+        let replay_path = replay_dir.join(format!("replay-{timestamp}"));
+        Some(replay_path)
     }
 
     #[cfg(not(feature = "replay"))]
@@ -169,6 +181,113 @@ impl Verification {
 
         hasher.finalize()
     }
+
+    /// Describe every way the `actual` state differs from this one.
+    /// An empty result means they match.
+    pub fn differences(&self, actual: &Verification) -> Vec<String> {
+        let expected = self;
+        let mut differences = vec![];
+
+        if expected.tick_id != actual.tick_id {
+            differences.push(format!(
+                "Expected tick_id: {}, actual: {}",
+                expected.tick_id, actual.tick_id
+            ));
+        }
+
+        if expected.turn != actual.turn {
+            differences.push(format!(
+                "Expected turn: {}, actual: {}",
+                expected.turn, actual.turn
+            ));
+        }
+
+        if expected.chunk_count != actual.chunk_count {
+            differences.push(format!(
+                "Expected chunks: {}, actual: {}",
+                expected.chunk_count, actual.chunk_count
+            ));
+        }
+
+        if expected.player_pos != actual.player_pos {
+            differences.push(format!(
+                "Expected player position: {}, actual: {}",
+                expected.player_pos, actual.player_pos
+            ));
+        }
+
+        if expected.monsters.len() != actual.monsters.len() {
+            differences.push(format!(
+                "Expected monster count: {}, actual: {}",
+                expected.monsters.len(),
+                actual.monsters.len()
+            ));
+        }
+
+        if expected.monsters != actual.monsters {
+            let expected_monsters: HashMap<Point, (Point, monster::Kind)> = expected
+                .monsters
+                .iter()
+                .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster)))
+                .collect();
+            let actual_monsters: HashMap<Point, (Point, monster::Kind)> = actual
+                .monsters
+                .iter()
+                .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster)))
+                .collect();
+
+            for (pos, expected) in &expected_monsters {
+                match actual_monsters.get(pos) {
+                    Some(actual) => {
+                        if expected != actual {
+                            differences.push(format!(
+                                "Monster at {pos} differ. Expected: {expected:?}, actual: {actual:?}"
+                            ));
+                        }
+                    }
+                    None => {
+                        differences.push(format!(
+                            "Monster expected at {pos}: {expected:?}, but it's not there."
+                        ));
+                    }
+                }
+            }
+
+            for (pos, actual) in &actual_monsters {
+                if !expected_monsters.contains_key(pos) {
+                    differences.push(format!(
+                        "There is an unexpected monster at: {pos}: {actual:?}."
+                    ));
+                }
+            }
+        }
+
+        differences
+    }
+}
+
+/// What a replay does when the game stops matching the recording.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OnDivergence {
+    /// Crash. The game must play out exactly as it was recorded.
+    #[default]
+    Panic,
+    /// Stop the replay and record the divergence.
+    Stop,
+    /// Record the first divergence, report the rest and keep playing.
+    /// For replays recorded before a deliberate gameplay change.
+    Continue,
+}
+
+/// The first place where a replayed game stopped matching the
+/// recorded one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub tick_id: i32,
+    pub turn: i32,
+    /// What didn't match. Replays recorded without `--debug` only
+    /// store a hash of the state so we can't tell which fields differ.
+    pub differences: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -194,6 +313,8 @@ pub struct State {
 
     pub screen_position_in_world: Point,
     pub seed: u32,
+    /// The `RULES_VERSION` the game is played by.
+    pub rules_version: u32,
     pub rng: Random,
     // Keys pressed this turn (or loaded from the replay file)
     pub keys: Keys,
@@ -203,6 +324,31 @@ pub struct State {
     pub inputs: VecDeque<Input>,
     pub commands: VecDeque<Command>,
     pub player_path: Path,
+    /// The notes the player left on the map.
+    pub annotations: Vec<Annotation>,
+    /// The tile the player wants to walk to.
+    pub waypoint: Option<Point>,
+    /// The player is walking to the `waypoint` on their own.
+    pub auto_walking: bool,
+    /// The waypoint picked on the map. It's set by the next game
+    /// tick so it ends up in the replay.
+    pub pending_waypoint: Option<Point>,
+    /// The player asked to go back a turn. Like `pending_waypoint`,
+    /// it's done by the next game tick.
+    pub pending_undo: bool,
+    /// Buffers reused by every path search the game makes.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub pathfinding: PathfindingContext,
+    /// Leads the monsters chasing the player. Rebuilt every monster turn.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub chase_map: DijkstraMap,
+    /// Leads the NPCs away from the high player. Rebuilt every
+    /// monster turn one of them needs it.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub flee_map: DijkstraMap,
+    /// Finds the paths too long for `pathfinding`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub chunk_graph: ChunkGraph,
     // #[serde(skip_serializing, skip_deserializing)]
     // pub verifications: HashMap<i32, Verification>,
     #[serde(skip_serializing, skip_deserializing, default = "empty_command_logger")]
@@ -214,12 +360,38 @@ pub struct State {
     pub cheating: bool,
     pub replay: bool,
     pub replay_full_speed: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub on_divergence: OnDivergence,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub divergence: Option<Divergence>,
+    /// The file this game is being recorded into (if any).
+    ///
+    /// NOTE: a loaded game doesn't continue the original recording
+    /// so this is not saved.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_path: Option<PathBuf>,
     pub exit_after: bool,
     pub debug: bool,
     pub clock: Duration,
     pub replay_step: Duration,
     #[serde(skip_serializing, skip_deserializing)]
     pub stats: Stats,
+    /// Show the frame timings on top of the game. Toggled with F3.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_performance_overlay: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub event_log: EventLog,
+    /// What happened in the game since the frontend last handled the
+    /// events. The `simulation` queues them up.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub events: Vec<GameEvent>,
+    /// The serialized state at the start of the recent turns. Only
+    /// kept in the practice mode.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub undo_snapshots: VecDeque<UndoSnapshot>,
+    /// How many times the player went back a turn.
+    pub undos: u32,
+    pub timeline: Timeline,
     pub pos_timer: Timer,
     pub path_walking_timer: Timer,
     pub paused: bool,
@@ -248,8 +420,29 @@ pub struct State {
     pub selected_endgame_window_action: Option<windows::endgame::Action>,
     #[serde(skip_serializing, skip_deserializing)]
     pub selected_sidebar_action: Option<windows::sidebar::Action>,
+    /// The finished games shown in the Statistics window. Loaded
+    /// when the window opens.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub run_history: Vec<RunRecord>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub statistics_sort: windows::statistics::Sort,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub map_view: windows::map::View,
+    /// The name being typed in the Annotate window.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub annotation_draft: String,
+    /// Shown in the Achievements window. Loaded when the window opens.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub unlocked_achievements: achievements::Unlocked,
+    /// Shown in the Daily Challenge window. Loaded when the window opens.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub daily_leaderboard: daily::Leaderboard,
     pub current_help_window: windows::help::Page,
     pub inventory_focused: bool,
+    /// The tile (in world coordinates) the player is examining or
+    /// `None` if they're not in the examine mode.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub examine_cursor: Option<Point>,
     /// Used for help contents pagination: how much are we scrolling by
     pub keyboard_scroll_delta: [f32; 2],
 
@@ -264,6 +457,8 @@ pub struct State {
 
     pub challenge: Challenge,
     pub palette: Palette,
+    /// The date of the daily challenge if this game is one.
+    pub daily: Option<String>,
 }
 
 impl State {
@@ -323,12 +518,22 @@ impl State {
             panel_width,
             screen_position_in_world: world_centre,
             seed,
+            rules_version: RULES_VERSION,
             rng,
             keys: Keys::new(),
             mouse: Default::default(),
             inputs,
             commands: VecDeque::new(),
             player_path: Path::default(),
+            annotations: vec![],
+            waypoint: None,
+            pending_waypoint: None,
+            pending_undo: false,
+            auto_walking: false,
+            pathfinding: PathfindingContext::new(),
+            chase_map: DijkstraMap::new(),
+            flee_map: DijkstraMap::new(),
+            chunk_graph: ChunkGraph::new(),
             input_logger: Box::new(log_writer),
             side: Side::Player,
             turn: 0,
@@ -337,11 +542,20 @@ impl State {
             cheating,
             replay,
             replay_full_speed,
+            on_divergence: OnDivergence::Panic,
+            divergence: None,
+            replay_path: None,
             exit_after,
             debug,
             clock: Duration::new(0, 0),
             replay_step: Duration::new(0, 0),
             stats: Default::default(),
+            show_performance_overlay: false,
+            event_log: EventLog::new(),
+            events: vec![],
+            undo_snapshots: VecDeque::new(),
+            undos: 0,
+            timeline: Timeline::default(),
             pos_timer: Timer::new(Duration::from_millis(0)),
             path_walking_timer: Timer::new_elapsed(formula::PLAYER_PATH_WALKING_DELAY, 1.0),
             old_screen_pos: (0, 0).into(),
@@ -364,14 +578,22 @@ impl State {
             selected_settings_position: None,
             selected_endgame_window_action: None,
             selected_sidebar_action: None,
+            run_history: vec![],
+            statistics_sort: Default::default(),
+            map_view: Default::default(),
+            annotation_draft: String::new(),
+            unlocked_achievements: Default::default(),
+            daily_leaderboard: Default::default(),
             current_help_window: windows::help::Page::DoseResponse,
             inventory_focused: false,
+            examine_cursor: None,
             keyboard_scroll_delta: [0.0, 0.0],
             show_endscreen_and_uncover_map_during_fadein: false,
             uncovered_map: false,
 
             challenge,
             palette,
+            daily: None,
         }
     }
 
@@ -381,12 +603,12 @@ impl State {
         panel_width: i32,
         exit_after: bool,
         debug: bool,
+        seed: u32,
         replay_path: Option<PathBuf>,
         challenge: Challenge,
         palette: Palette,
     ) -> State {
         let inputs = VecDeque::new();
-        let seed = util::random_seed();
 
         let replay_path = replay_path.and_then(|p| {
             if p.exists() {
@@ -401,10 +623,12 @@ impl State {
             }
         });
 
+        let mut recording_path = None;
         let mut writer: Box<dyn Write> = if let Some(replay_path) = replay_path {
             match File::create_new(&replay_path) {
                 Ok(f) => {
                     log::info!("Recording the gameplay to '{}'", replay_path.display());
+                    recording_path = Some(replay_path);
                     Box::new(f)
                 }
                 Err(err) => {
@@ -428,12 +652,12 @@ Reason: '{}'.",
             Box::new(io::sink())
         };
 
-        log_header(&mut writer, seed);
+        log_header(&mut writer, seed, challenge);
         let cheating = false;
         let replay = false;
         let invincible = false;
         let replay_full_speed = false;
-        State::new(
+        let mut state = State::new(
             world_size,
             map_size,
             panel_width,
@@ -448,9 +672,14 @@ Reason: '{}'.",
             debug,
             challenge,
             palette,
-        )
+        );
+        state.replay_path = recording_path;
+        state
     }
 
+    /// Load the replay at `replay_path`. The game is played with the
+    /// challenge stored in the replay, `challenge` is only used by the
+    /// older replays that don't have it.
     #[cfg_attr(not(feature = "replay"), allow(dead_code, unused_variables))]
     pub fn replay_game(
         world_size: Point,
@@ -528,6 +757,34 @@ Reason: '{}'.",
                 None => throw!("The replay file is missing the commit hash."),
             };
 
+            // NOTE: the replays recorded before we started storing
+            // the rules version are all played by the original rules.
+            let mut lines = lines.peekable();
+            let mut rules_version = 1;
+            if let Some(Ok(line)) = lines.peek()
+                && let Ok(version) = line.parse::<u32>()
+            {
+                rules_version = version;
+                lines.next();
+            }
+            if rules_version > RULES_VERSION {
+                log::warn!(
+                    "The replay file's rules version is: {}, but the program only knows up to: {}.",
+                    rules_version,
+                    RULES_VERSION
+                );
+            }
+
+            // NOTE: the older replays don't store their challenge
+            // either. They're played with the one that was passed in.
+            let mut challenge = challenge;
+            if let Some(Ok(line)) = lines.peek()
+                && let Ok(replay_challenge) = serde_json::from_str::<Challenge>(line)
+            {
+                challenge = replay_challenge;
+                lines.next();
+            }
+
             for line in lines {
                 let line = line?;
                 // Try parsing it as an `Input` first, otherwise it's a `Verification`
@@ -569,6 +826,7 @@ Reason: '{}'.",
                 challenge,
                 palette,
             );
+            state.rules_version = rules_version;
             state.game_session = GameSession::InProgress;
             state.generate_world();
             Ok(state)
@@ -582,6 +840,7 @@ Reason: '{}'.",
                 panel_width,
                 exit_after,
                 debug,
+                crate::util::random_seed(),
                 None,
                 challenge,
                 palette,
@@ -628,14 +887,15 @@ Reason: '{}'.",
 
     pub fn save_to_file(&self) -> Result<(), Box<dyn Error>> {
         // TODO: select the filename dynamically!
-        let filename = "SAVEDGAME.sav";
+        let filename = crate::paths::savegame_file();
+        crate::paths::ensure_dir(&crate::paths::data_dir());
         let version_data = bincode::serialize(crate::metadata::VERSION)?;
         let commit_data = bincode::serialize(crate::metadata::GIT_HASH)?;
         let state_data = bincode::serialize(self)?;
 
         // TODO: this can be compressed nicely!
 
-        let mut file = File::create(filename)?;
+        let mut file = File::create(&filename)?;
         file.write_all(&version_data)?;
         file.write_all(&commit_data)?;
         file.write_all(&state_data)?;
@@ -645,9 +905,9 @@ Reason: '{}'.",
     }
 
     pub fn load_from_file() -> Result<State, Box<dyn Error>> {
-        let filename = "SAVEDGAME.sav";
+        let filename = crate::paths::savegame_file();
         let state = {
-            let file = File::open(filename)?;
+            let file = File::open(&filename)?;
             let version: String = bincode::deserialize_from(&file)?;
             log::info!("Savefile version {}", version);
             if version != crate::metadata::VERSION {
@@ -669,7 +929,7 @@ Reason: '{}'.",
             bincode::deserialize_from(&file)?
         };
 
-        if let Err(error) = ::std::fs::remove_file(filename) {
+        if let Err(error) = ::std::fs::remove_file(&filename) {
             log::error!(
                 "Failed to delete the successfully loaded savegame. Error: {:?}",
                 error
@@ -690,6 +950,163 @@ Reason: '{}'.",
     pub fn mouse_world_position(&self) -> Point {
         self.screen_left_top_corner() + self.mouse.tile_pos
     }
+
+    /// Whether the player can walk to the tile under the mouse
+    /// pointer. That's any tile they can see and (since rules version
+    /// 3) any they've explored before.
+    pub fn mouse_path_allowed(&self) -> bool {
+        let pos = self.mouse_world_position();
+        let visible = pos.inside_circular_area(
+            self.player.pos,
+            formula::exploration_radius(self.player.mind),
+        );
+        let explored =
+            self.rules_version >= 3 && self.world.cell(pos).is_some_and(|cell| cell.explored);
+        visible || explored
+    }
+
+    /// Whether the player can go back a turn right now.
+    ///
+    /// NOTE: this holds during a replay, too. The undos are part of
+    /// the replay's input (see `Input::undo`) and the replay keeps
+    /// the snapshots to go back to.
+    pub fn can_undo(&self) -> bool {
+        self.challenge.practice
+            && self.game_session == GameSession::InProgress
+            && self
+                .undo_snapshots
+                .iter()
+                .any(|snapshot| snapshot.turn < self.turn)
+    }
+
+    /// Remember the state at the start of the current turn so the
+    /// player can come back to it in the practice mode.
+    pub fn save_undo_snapshot(&mut self) {
+        if self
+            .undo_snapshots
+            .back()
+            .is_some_and(|snapshot| snapshot.turn == self.turn)
+        {
+            return;
+        }
+        match bincode::serialize(self) {
+            Ok(data) => {
+                if self.undo_snapshots.len() >= formula::UNDO_TURNS {
+                    self.undo_snapshots.pop_front();
+                }
+                self.undo_snapshots.push_back(UndoSnapshot {
+                    turn: self.turn,
+                    data,
+                });
+            }
+            Err(err) => log::error!("Could not save the undo snapshot: {}", err),
+        }
+    }
+
+    /// Go back to the start of the previous turn. Returns `false` if
+    /// there's nothing to go back to.
+    ///
+    /// Only the game world is restored. The windows, inputs, replay
+    /// and timing stay as they are.
+    pub fn undo(&mut self) -> bool {
+        while self
+            .undo_snapshots
+            .back()
+            .is_some_and(|snapshot| snapshot.turn >= self.turn)
+        {
+            self.undo_snapshots.pop_back();
+        }
+        let Some(snapshot) = self.undo_snapshots.pop_back() else {
+            return false;
+        };
+        let restored: State = match bincode::deserialize(&snapshot.data) {
+            Ok(restored) => restored,
+            Err(err) => {
+                log::error!("Could not restore the undo snapshot: {}", err);
+                return false;
+            }
+        };
+
+        self.player = restored.player;
+        self.world = restored.world;
+        self.rng = restored.rng;
+        self.side = restored.side;
+        self.turn = restored.turn;
+        self.timeline = restored.timeline;
+        self.game_session = restored.game_session;
+        self.victory_npc_id = restored.victory_npc_id;
+        self.show_anxiety_counter = restored.show_anxiety_counter;
+        self.player_picked_up_a_dose = restored.player_picked_up_a_dose;
+        self.player_bumped_into_a_monster = restored.player_bumped_into_a_monster;
+        self.screen_position_in_world = restored.screen_position_in_world;
+        self.old_screen_pos = restored.old_screen_pos;
+        self.new_screen_pos = restored.new_screen_pos;
+        self.pos_timer = restored.pos_timer;
+        self.offset_px = restored.offset_px;
+        self.screen_fading = None;
+
+        // NOTE: the cached paths and distances describe the world
+        // we've just thrown away.
+        self.chunk_graph = ChunkGraph::new();
+        self.chase_map = DijkstraMap::new();
+        self.flee_map = DijkstraMap::new();
+
+        self.commands.clear();
+        self.player_path = Path::default();
+        self.auto_walking = false;
+        self.explosion_animation = None;
+        self.extra_animations.clear();
+        self.events.clear();
+        self.examine_cursor = None;
+
+        self.undos += 1;
+        true
+    }
+
+    pub fn annotation(&self, pos: Point) -> Option<&Annotation> {
+        self.annotations
+            .iter()
+            .find(|annotation| annotation.pos == pos)
+    }
+
+    /// Name the `pos` tile. An empty name removes the annotation.
+    pub fn set_annotation(&mut self, pos: Point, name: &str) {
+        let name = name.trim();
+        self.annotations.retain(|annotation| annotation.pos != pos);
+        if !name.is_empty() {
+            self.annotations.push(Annotation {
+                pos,
+                name: name.to_string(),
+            });
+        }
+    }
+
+    /// Start walking to `pos`. Setting it to where the player stands
+    /// removes the waypoint instead.
+    pub fn set_waypoint(&mut self, pos: Point) {
+        if pos == self.player.pos {
+            self.waypoint = None;
+            self.auto_walking = false;
+        } else {
+            self.waypoint = Some(pos);
+            self.auto_walking = true;
+        }
+    }
+
+    /// Whether the entire map is shown regardless of what the player
+    /// has explored or can see.
+    pub fn map_uncovered(&self) -> bool {
+        (cfg!(feature = "cheating") && self.cheating)
+            || self.player.bonus == Bonus::UncoverMap  // player bonus
+            || self.uncovered_map  // map uncovered after the endgame fade
+            || !self.challenge.hide_unseen_tiles // challenge Settings option
+    }
+}
+
+/// The serialized `State` at the start of a turn.
+pub struct UndoSnapshot {
+    pub turn: i32,
+    data: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
@@ -708,6 +1125,10 @@ pub struct Challenge {
     pub hide_unseen_tiles: bool,
     pub fast_depression: bool,
     pub one_chance: bool,
+    /// The player can undo their turns. These games don't count in
+    /// the statistics and achievements.
+    #[serde(default)]
+    pub practice: bool,
 }
 
 impl Default for Challenge {
@@ -716,6 +1137,7 @@ impl Default for Challenge {
             hide_unseen_tiles: true,
             fast_depression: true,
             one_chance: true,
+            practice: false,
         }
     }
 }
@@ -724,10 +1146,37 @@ fn empty_command_logger() -> Box<dyn Write> {
     Box::new(io::sink())
 }
 
-pub fn log_header<W: Write>(writer: &mut W, seed: u32) {
+/// The version of the gameplay rules. Bump it whenever a change makes
+/// the same inputs play out differently and keep the old behaviour
+/// around for the older versions. That way the replays recorded
+/// before the change still match.
+///
+/// 1. The original rules.
+/// 2. Chasing monsters walk down a shared `DijkstraMap` rather than
+///    each finding its own path.
+/// 3. The player can walk to any explored tile and the Victory NPC
+///    reachability is checked with the `ChunkGraph`.
+/// 4. The view can be zoomed and the chunks brought in around the
+///    path to the Victory NPC no longer depend on the map size.
+/// 5. The NPCs keep away from a high player by walking down a flee
+///    map.
+pub const RULES_VERSION: u32 = 5;
+
+/// Write the replay header: the seed, the game version and commit,
+/// the rules version and the challenge the game is played with.
+pub fn log_header<W: Write>(writer: &mut W, seed: u32, challenge: Challenge) {
     let _ = writeln!(writer, "{seed}");
     let _ = writeln!(writer, "{}", crate::metadata::VERSION);
     let _ = writeln!(writer, "{}", crate::metadata::GIT_HASH);
+    let _ = writeln!(writer, "{RULES_VERSION}");
+    match serde_json::to_string(&challenge) {
+        Ok(json_challenge) => {
+            let _ = writeln!(writer, "{json_challenge}");
+        }
+        Err(err) => {
+            log::error!("Could not serialise {:?} to JSON: {}", challenge, err);
+        }
+    }
 }
 
 pub fn log_input<W: Write>(writer: &mut W, input: Input) {
@@ -740,3 +1189,70 @@ pub fn log_input<W: Write>(writer: &mut W, input: Input) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn verification() -> Verification {
+        Verification {
+            turn: 10,
+            tick_id: 42,
+            chunk_count: 4,
+            player_pos: Point::new(3, 4),
+            monsters: vec![(Point::new(5, 5), Point::zero(), monster::Kind::Anxiety)],
+        }
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut state = State::new_game(
+            crate::WORLD_SIZE,
+            Point::from_i32(crate::DISPLAYED_MAP_SIZE),
+            crate::PANEL_WIDTH,
+            false,
+            false,
+            1,
+            None,
+            Challenge::default(),
+            Palette::classic(),
+        );
+        let pos = Point::new(3, 4);
+
+        state.set_annotation(pos, " dose cache ");
+        assert_eq!(
+            state.annotation(pos).map(|a| a.name.as_str()),
+            Some("dose cache")
+        );
+
+        state.set_annotation(pos, "Depression den");
+        assert_eq!(state.annotations.len(), 1);
+        assert_eq!(
+            state.annotation(pos).map(|a| a.name.as_str()),
+            Some("Depression den")
+        );
+
+        state.set_annotation(pos, "");
+        assert!(state.annotation(pos).is_none());
+        assert!(state.annotations.is_empty());
+    }
+
+    #[test]
+    fn test_no_differences() {
+        assert!(verification().differences(&verification()).is_empty());
+    }
+
+    #[test]
+    fn test_differences() {
+        let expected = verification();
+        let mut actual = verification();
+        actual.player_pos = Point::new(3, 5);
+        actual.monsters = vec![(Point::new(6, 5), Point::zero(), monster::Kind::Anxiety)];
+        let differences = expected.differences(&actual);
+        assert_eq!(differences.len(), 3);
+        assert_eq!(
+            differences[0],
+            "Expected player position: (3, 4), actual: (3, 5)"
+        );
+    }
+}
@@ -4,6 +4,8 @@ use crate::formula;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct FrameStats {
+    /// The time since the previous frame.
+    pub frame: Duration,
     pub update: Duration,
     pub drawcalls: Duration,
 }
@@ -17,6 +19,8 @@ pub struct Stats {
     lowest_fps: i32,
     current_fps_average: f32,
     total_fps_entries_processes: f32,
+    drawcall_count: usize,
+    vertex_count: usize,
 }
 
 impl Stats {
@@ -29,6 +33,8 @@ impl Stats {
             lowest_fps: formula::FPS.floor() as i32,
             current_fps_average: formula::FPS,
             total_fps_entries_processes: 1.0,
+            drawcall_count: 0,
+            vertex_count: 0,
         }
     }
 
@@ -73,7 +79,24 @@ impl Stats {
         }
     }
 
-    #[allow(dead_code)]
+    /// What went into the last rendered frame. The engine renders
+    /// after the game update so this is always a frame behind.
+    pub fn record_render(&mut self, drawcalls: usize, vertices: usize) {
+        if cfg!(not(feature = "stats")) {
+            return;
+        }
+        self.drawcall_count = drawcalls;
+        self.vertex_count = vertices;
+    }
+
+    pub fn drawcall_count(&self) -> usize {
+        self.drawcall_count
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
     pub fn last_frames(&self, count: usize) -> FrameStatsIterator<'_> {
         let size = if count > self.frame_stats.len() {
             self.frame_stats.len()
@@ -117,6 +140,26 @@ impl Stats {
             / (self.frame_stats.len() as f32)
     }
 
+    /// The mean time between two frames in milliseconds.
+    ///
+    /// NOTE: this goes up in the power-saving mode when the game
+    /// waits for the player's input.
+    pub fn mean_frame(&self) -> f32 {
+        self.frame_stats
+            .iter()
+            .map(|fs| fs.frame.as_secs_f32() * 1000.0)
+            .fold(0.0, |acc, dur| acc + dur)
+            / (self.frame_stats.len() as f32)
+    }
+
+    pub fn longest_frame(&self) -> Duration {
+        self.frame_stats
+            .iter()
+            .map(|fs| fs.frame)
+            .max()
+            .unwrap_or_else(|| Duration::new(0, 0))
+    }
+
     pub fn longest_update_durations(&self) -> &[Duration] {
         &self.longest_updates
     }
@@ -0,0 +1,166 @@
+use crate::{
+    player::{Mind, Player},
+    point::Point,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The player's state at the end of a turn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sample {
+    pub turn: i32,
+    pub pos: Point,
+    pub mind: Mind,
+    pub will: i32,
+    pub tolerance: i32,
+    pub anxiety: i32,
+    pub doses_used: i32,
+    pub monsters_killed: i32,
+    pub npcs_met: i32,
+}
+
+impl Sample {
+    pub fn new(turn: i32, player: &Player) -> Self {
+        Self {
+            turn,
+            pos: player.pos,
+            mind: player.mind,
+            will: player.will.to_int(),
+            tolerance: player.tolerance,
+            anxiety: player.anxiety_counter.to_int(),
+            doses_used: player.doses_used,
+            monsters_killed: player.monsters_killed,
+            npcs_met: player.npcs_met,
+        }
+    }
+}
+
+/// Something notable that happened during a turn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Marker {
+    Dose,
+    Kill,
+    Npc,
+}
+
+/// The history of the player's state, one `Sample` per turn. Used
+/// for the end-of-run report.
+///
+/// NOTE: this is recorded in every game so it needs to stay cheap.
+/// Don't add anything here that's not a handful of integers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timeline {
+    samples: Vec<Sample>,
+}
+
+impl Timeline {
+    /// Record the player's state at the end of `turn`. Recording the
+    /// same turn again replaces the previous sample.
+    pub fn record(&mut self, turn: i32, player: &Player) {
+        let sample = Sample::new(turn, player);
+        match self.samples.last_mut() {
+            Some(last) if last.turn == turn => *last = sample,
+            _ => self.samples.push(sample),
+        }
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The turns where the player used a dose, killed a monster or
+    /// met an NPC. At most one marker of each kind per turn.
+    pub fn markers(&self) -> Vec<(Sample, Marker)> {
+        let mut markers = vec![];
+        let mut previous: Option<&Sample> = None;
+        for sample in &self.samples {
+            let (doses_used, monsters_killed, npcs_met) = match previous {
+                Some(previous) => (
+                    previous.doses_used,
+                    previous.monsters_killed,
+                    previous.npcs_met,
+                ),
+                None => (0, 0, 0),
+            };
+            if sample.doses_used > doses_used {
+                markers.push((*sample, Marker::Dose));
+            }
+            if sample.monsters_killed > monsters_killed {
+                markers.push((*sample, Marker::Kill));
+            }
+            if sample.npcs_met > npcs_met {
+                markers.push((*sample, Marker::Npc));
+            }
+            previous = Some(sample);
+        }
+        markers
+    }
+}
+
+/// The state of mind on a single scale: `0.0` is exhausted, `1.0` is
+/// the start of sobriety, `2.0` is the start of being high and `3.0`
+/// is an overdose.
+pub fn mind_scale(mind: Mind) -> f32 {
+    match mind {
+        Mind::Withdrawal(value) => value.percent(),
+        Mind::Sober(value) => 1.0 + value.percent(),
+        Mind::High(value) => 2.0 + value.percent(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{formula, ranged_int::Ranged};
+
+    #[test]
+    fn test_record_replaces_the_same_turn() {
+        let mut player = Player::new(Point::zero(), false);
+        let mut timeline = Timeline::default();
+        timeline.record(0, &player);
+        player.pos = Point::new(1, 0);
+        timeline.record(0, &player);
+        timeline.record(1, &player);
+        assert_eq!(timeline.samples().len(), 2);
+        assert_eq!(timeline.samples()[0].pos, Point::new(1, 0));
+    }
+
+    #[test]
+    fn test_markers() {
+        let mut player = Player::new(Point::zero(), false);
+        let mut timeline = Timeline::default();
+        timeline.record(0, &player);
+        player.doses_used += 1;
+        player.monsters_killed += 2;
+        timeline.record(1, &player);
+        timeline.record(2, &player);
+        player.npcs_met += 1;
+        timeline.record(3, &player);
+
+        let markers = timeline
+            .markers()
+            .into_iter()
+            .map(|(sample, marker)| (sample.turn, marker))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            markers,
+            vec![(1, Marker::Dose), (1, Marker::Kill), (3, Marker::Npc)]
+        );
+    }
+
+    #[test]
+    fn test_mind_scale_is_ordered() {
+        let exhausted = mind_scale(Mind::Withdrawal(Ranged::new_min(formula::WITHDRAWAL)));
+        let withdrawal = mind_scale(Mind::Withdrawal(Ranged::new_max(formula::WITHDRAWAL)));
+        let sober = mind_scale(Mind::Sober(Ranged::new_max(formula::SOBER)));
+        let overdosed = mind_scale(Mind::High(Ranged::new_max(formula::HIGH)));
+        assert_eq!(exhausted, 0.0);
+        assert!(withdrawal <= sober);
+        assert!(sober <= overdosed);
+        assert_eq!(overdosed, 3.0);
+    }
+}
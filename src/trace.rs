@@ -0,0 +1,187 @@
+//! Records how long the parts of each frame take so we can profile
+//! the slow turns.
+//!
+//! The recording is exported in the Chrome trace-event format. Open
+//! it in `chrome://tracing` or <https://ui.perfetto.dev>.
+//!
+//! NOTE: the spans are recorded from deep within the simulation and
+//! pathfinding code that doesn't have access to the `State`. So the
+//! recording is kept per thread rather than passed around. The game
+//! runs on a single thread anyway.
+
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// Stop recording new spans past this point so a forgotten recording
+/// doesn't eat up all the memory. That's minutes of gameplay at 60
+/// FPS.
+const MAX_EVENTS: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct TraceEvent {
+    pub name: &'static str,
+    /// The event type. `X` is a "complete" event: a span with a
+    /// duration.
+    pub ph: &'static str,
+    /// Start of the span in microseconds since the recording started.
+    pub ts: u64,
+    /// Duration of the span in microseconds.
+    pub dur: u64,
+    pub pid: u32,
+    pub tid: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Trace {
+    #[serde(rename = "traceEvents")]
+    pub trace_events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+struct Recording {
+    start: Instant,
+    trace: Trace,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+}
+
+/// Start recording the spans. Any recording in progress is discarded.
+pub fn start() {
+    RECORDING.with_borrow_mut(|recording| {
+        *recording = Some(Recording {
+            start: Instant::now(),
+            trace: Trace::default(),
+        });
+    });
+}
+
+/// Stop the recording and return everything recorded so far.
+pub fn stop() -> Option<Trace> {
+    RECORDING.with_borrow_mut(|recording| recording.take().map(|recording| recording.trace))
+}
+
+pub fn recording() -> bool {
+    RECORDING.with_borrow(Option::is_some)
+}
+
+/// The number of spans recorded so far.
+pub fn event_count() -> usize {
+    RECORDING.with_borrow(|recording| {
+        recording
+            .as_ref()
+            .map_or(0, |recording| recording.trace.trace_events.len())
+    })
+}
+
+/// Measure the time until the returned `Span` is dropped.
+///
+/// This does nothing unless a recording is in progress.
+pub fn span(name: &'static str) -> Span {
+    let start = if recording() {
+        Some(Instant::now())
+    } else {
+        None
+    };
+    Span { name, start }
+}
+
+#[must_use = "the span is recorded when it's dropped"]
+pub struct Span {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(span_start) = self.start else {
+            return;
+        };
+        let duration = span_start.elapsed();
+        RECORDING.with_borrow_mut(|recording| {
+            if let Some(recording) = recording
+                && recording.trace.trace_events.len() < MAX_EVENTS
+            {
+                let ts = span_start
+                    .checked_duration_since(recording.start)
+                    .unwrap_or(Duration::ZERO);
+                recording.trace.trace_events.push(TraceEvent {
+                    name: self.name,
+                    ph: "X",
+                    ts: ts.as_micros() as u64,
+                    dur: duration.as_micros() as u64,
+                    pid: 1,
+                    tid: 1,
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spans_outside_of_a_recording_are_ignored() {
+        {
+            let _span = span("ignored");
+        }
+        start();
+        {
+            let _span = span("recorded");
+        }
+        let trace = stop().unwrap_or_default();
+        assert_eq!(trace.trace_events.len(), 1);
+        assert_eq!(trace.trace_events[0].name, "recorded");
+        assert!(!recording());
+    }
+
+    #[test]
+    fn nested_spans_are_recorded_in_the_order_they_finish() {
+        start();
+        {
+            let _outer = span("outer");
+            let _inner = span("inner");
+        }
+        let trace = stop().unwrap_or_default();
+        let names = trace
+            .trace_events
+            .iter()
+            .map(|event| event.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["inner", "outer"]);
+        assert!(trace.trace_events[1].ts <= trace.trace_events[0].ts);
+        assert!(trace.trace_events[1].dur >= trace.trace_events[0].dur);
+    }
+
+    #[test]
+    fn exports_chrome_trace_events() {
+        let trace = Trace {
+            trace_events: vec![TraceEvent {
+                name: "pathfinding",
+                ph: "X",
+                ts: 10,
+                dur: 5,
+                pid: 1,
+                tid: 1,
+            }],
+        };
+        assert_eq!(
+            trace.to_json().ok().as_deref(),
+            Some(
+                r#"{"traceEvents":[{"name":"pathfinding","ph":"X","ts":10,"dur":5,"pid":1,"tid":1}]}"#
+            )
+        );
+    }
+}
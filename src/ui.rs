@@ -2,8 +2,8 @@
 
 use crate::{
     color::{self, Color},
-    engine::{TextOptions, Texture},
-    graphic::Graphic,
+    engine::{TextOptions, Texture, glyph_atlas::GlyphAtlas},
+    graphic::{Graphic, Tileset},
     palette::Palette,
     point::Point,
 };
@@ -49,35 +49,45 @@ pub fn sized_button(
     ui.add_enabled(enabled, button)
 }
 
-pub fn image_uv_tilesize(texture: Texture, graphic: Graphic, text_size: f32) -> (egui::Rect, f32) {
-    let (x, y, tw, th, tilesize) = match texture {
+pub fn image_uv_tilesize(
+    texture: Texture,
+    graphic: Graphic,
+    text_size: f32,
+    tileset: &Tileset,
+    glyph_atlas: &GlyphAtlas,
+) -> (egui::Rect, f32) {
+    let (x, y, tw, th, texture_tilesize, tilesize) = match texture {
         Texture::Tilemap => {
-            let tilesize = crate::graphic::TILE_SIZE as f32;
-            let tilemap_width = crate::engine::TILEMAP_TEXTURE_WIDTH as f32;
-            let tilemap_height = crate::engine::TILEMAP_TEXTURE_HEIGHT as f32;
-            let (x, y) = crate::graphic::tilemap_coords_px(0, graphic).unwrap_or((0, 0));
-            (x, y, tilemap_width, tilemap_height, tilesize)
+            let tilesize = tileset.tile_size as f32;
+            let tilemap_width = tileset.texture_size_px.0 as f32;
+            let tilemap_height = tileset.texture_size_px.1 as f32;
+            let (x, y) = tileset.coords_px(graphic).unwrap_or((0, 0));
+            (x, y, tilemap_width, tilemap_height, tilesize, tilesize)
         }
         Texture::Glyph => {
             let tilesize = text_size;
-            let tilemap_width = crate::engine::GLYPHMAP_TEXTURE_WIDTH as f32;
-            let tilemap_height = crate::engine::GLYPHMAP_TEXTURE_HEIGHT as f32;
-            let (x, y) = crate::engine::glyph_coords_px_from_char(tilesize as u32, graphic.into())
+            // NOTE: on a high-DPI display, the glyphs in the texture
+            // are bigger than the `text_size` they cover on screen.
+            let glyph_size = glyph_atlas.glyph_size_px(tilesize as i32) as f32;
+            let tilemap_width = glyph_atlas.texture_size_px.0 as f32;
+            let tilemap_height = glyph_atlas.texture_size_px.1 as f32;
+            let (x, y) = glyph_atlas
+                .coords_px(tilesize as i32, graphic.into())
                 .unwrap_or((0, 0));
-            (x, y, tilemap_width, tilemap_height, tilesize)
+            (x, y, tilemap_width, tilemap_height, glyph_size, tilesize)
         }
         texture => {
             log::error!(
                 "ERROR: ImageTextButton: unexpected texture type: {:?}",
                 texture
             );
-            (0, 0, 0.0, 0.0, 0.0)
+            (0, 0, 0.0, 0.0, 0.0, 0.0)
         }
     };
 
     let uv = egui::Rect::from_min_size(
         (x as f32 / tw, y as f32 / th).into(),
-        Vec2::new(tilesize / tw, tilesize / th),
+        Vec2::new(texture_tilesize / tw, texture_tilesize / th),
     );
 
     (uv, tilesize)
@@ -94,6 +104,8 @@ pub struct ImageTextButton {
     frame: bool,
     selected: bool,
     graphic: Graphic,
+    tileset: Tileset,
+    glyph_atlas: GlyphAtlas,
     tile_offset_px: Vec2,
     image_color: Color32,
     text_color: Color32,
@@ -110,6 +122,8 @@ impl ImageTextButton {
             frame: true,
             selected: true,
             graphic: Graphic::default(),
+            tileset: Tileset::default(),
+            glyph_atlas: GlyphAtlas::default(),
             tile_offset_px: Vec2::ZERO,
             image_color: color::WHITE.into(),
             text_color: color::WHITE.into(),
@@ -130,6 +144,20 @@ impl ImageTextButton {
         self
     }
 
+    /// Use this tileset to look up the tile graphic. Defaults to the
+    /// embedded tilemap.
+    pub fn tileset(mut self, tileset: &Tileset) -> Self {
+        self.tileset = tileset.clone();
+        self
+    }
+
+    /// Use this atlas to look up the glyphs. Defaults to the glyphs
+    /// baked into the binary.
+    pub fn glyph_atlas(mut self, glyph_atlas: &GlyphAtlas) -> Self {
+        self.glyph_atlas = glyph_atlas.clone();
+        self
+    }
+
     /// Move the tile in the button by this much.
     pub fn tile_offset_px(mut self, offset: impl Into<Vec2>) -> Self {
         self.tile_offset_px = offset.into();
@@ -182,6 +210,8 @@ impl Widget for ImageTextButton {
             frame,
             selected,
             graphic,
+            tileset,
+            glyph_atlas,
             tile_offset_px,
             image_color,
             text_color,
@@ -195,7 +225,13 @@ impl Widget for ImageTextButton {
         let text_galley =
             ui.fonts(|reader| reader.layout_no_wrap(text, font_id.clone(), text_color));
 
-        let (uv, _tilesize) = image_uv_tilesize(texture, graphic, text_galley.rect.height());
+        let (uv, _tilesize) = image_uv_tilesize(
+            texture,
+            graphic,
+            text_galley.rect.height(),
+            &tileset,
+            &glyph_atlas,
+        );
 
         let sized_texture =
             egui::load::SizedTexture::new(texture, Vec2::splat(text_galley.rect.height()));
@@ -35,3 +35,26 @@ pub fn random_seed() -> u32 {
     // Poor man's RNG: get the least significant digits from the current time:
     local_time.timestamp_subsec_nanos()
 }
+
+/// The current time in seconds since the Unix epoch.
+pub fn unix_timestamp() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Format the Unix `timestamp` as a `YYYY-MM-DD` date (UTC).
+pub fn format_date(timestamp: u64) -> String {
+    let timestamp = i64::try_from(timestamp).unwrap_or_default();
+    match time::OffsetDateTime::from_unix_timestamp(timestamp) {
+        Ok(datetime) => format!(
+            "{}-{:02}-{:02}",
+            datetime.year(),
+            u8::from(datetime.month()),
+            datetime.day()
+        ),
+        Err(_) => "Unknown".to_string(),
+    }
+}
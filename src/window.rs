@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use crate::point::Point;
+
 use serde::{Deserialize, Serialize};
 
 /// An enum of windows in the game.
@@ -14,7 +16,15 @@ pub enum Window {
     Game,
     Help,
     Settings,
+    Statistics,
+    Achievements,
+    Daily,
     Endgame,
+    Report,
+    Map,
+    Annotate {
+        pos: Point,
+    },
     Message {
         title: String,
         message: String,
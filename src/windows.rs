@@ -1,12 +1,19 @@
 use serde::{Deserialize, Serialize};
 
+pub mod achievements;
+pub mod annotate;
 pub mod call_to_action;
+pub mod daily;
 pub mod endgame;
 pub mod help;
 pub mod main_menu;
+pub mod map;
 pub mod message;
+pub mod performance;
+pub mod report;
 pub mod settings;
 pub mod sidebar;
+pub mod statistics;
 
 /// A stack of windows.
 ///
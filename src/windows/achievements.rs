@@ -0,0 +1,76 @@
+use crate::{
+    achievements::Achievement,
+    audio::{Audio, Effect},
+    engine::Display,
+    game::RunningState,
+    gamepad::Gamepad,
+    keys::KeyCode,
+    state::State,
+};
+
+use std::time::Duration;
+
+use egui::{self, Grid, Ui};
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    _gamepad: &Gamepad,
+    display: &Display,
+    audio: &mut Audio,
+) -> RunningState {
+    let mut visible = true;
+
+    let screen_size_px = display.screen_size_px;
+    let window_size_px = [
+        (screen_size_px.x - 150).min(800) as f32,
+        (screen_size_px.y - 350).min(400) as f32,
+    ];
+    let window_pos_px = [(screen_size_px.x as f32 - window_size_px[0]) / 2.0, 100.0];
+
+    egui::Window::new("Achievements")
+        .open(&mut visible)
+        .collapsible(false)
+        .fixed_pos(window_pos_px)
+        .fixed_size(window_size_px)
+        .show(ui.ctx(), |ui| {
+            ui.label(format!(
+                "Unlocked {} of {}",
+                state.unlocked_achievements.count(),
+                Achievement::ALL.len()
+            ));
+            ui.separator();
+
+            Grid::new("Achievements Gallery")
+                .striped(true)
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for achievement in Achievement::ALL {
+                        let unlocked_on = state.unlocked_achievements.unlocked_on(achievement);
+                        let title = achievement.title();
+                        if unlocked_on.is_some() {
+                            ui.label(title);
+                        } else {
+                            ui.label(egui::RichText::new(title).weak());
+                        }
+                        ui.label(achievement.description());
+                        ui.label(unlocked_on.unwrap_or_else(|| "Locked".to_string()));
+                        ui.end_row();
+                    }
+                });
+
+            ui.separator();
+            ui.label("[Esc] Close");
+        });
+
+    if !visible
+        || state.keys.matches_code(KeyCode::Esc)
+        || state.keys.matches_code(KeyCode::Enter)
+        || state.mouse.right_clicked
+    {
+        audio.play_sound(Effect::Click, Duration::from_millis(0));
+        state.window_stack.pop();
+    }
+
+    RunningState::Running
+}
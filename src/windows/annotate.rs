@@ -0,0 +1,127 @@
+use crate::{
+    audio::{Audio, Effect},
+    engine::Display,
+    game::RunningState,
+    keys::{Key, KeyCode},
+    point::Point,
+    state::State,
+};
+
+use std::time::Duration;
+
+use egui::{self, Ui};
+
+/// The longest name a tile can get. Any more than this won't fit on
+/// the map.
+const MAX_NAME_LENGTH: usize = 24;
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    pos: Point,
+    display: &Display,
+    audio: &mut Audio,
+) -> RunningState {
+    let display_size_px = display.screen_size_px;
+    let window_size_px = [400.0, 120.0];
+    let window_pos_px = [
+        (display_size_px.x as f32 - window_size_px[0]) / 2.0,
+        (display_size_px.y as f32 - window_size_px[1]) / 2.0,
+    ];
+
+    let mut save = false;
+    let mut close = state.mouse.right_clicked;
+    while let Some(key) = state.keys.get() {
+        match key.code {
+            KeyCode::Enter => save = true,
+            KeyCode::Esc => close = true,
+            KeyCode::Backspace => {
+                state.annotation_draft.pop();
+            }
+            _ => {
+                if let Some(c) = key_char(key)
+                    && state.annotation_draft.chars().count() < MAX_NAME_LENGTH
+                {
+                    state.annotation_draft.push(c);
+                }
+            }
+        }
+    }
+
+    let mut window_open = true;
+    egui::Window::new("Mark the tile")
+        .open(&mut window_open)
+        .collapsible(false)
+        .fixed_pos(window_pos_px)
+        .fixed_size(window_size_px)
+        .show(ui.ctx(), |ui| {
+            ui.label(format!("Name: {}_", state.annotation_draft));
+            ui.separator();
+            ui.label("[Enter] Save, [Esc] Cancel");
+            ui.label("Save an empty name to remove the mark.");
+        });
+
+    if save {
+        let name = std::mem::take(&mut state.annotation_draft);
+        state.set_annotation(pos, &name);
+    }
+
+    if save || close || !window_open {
+        audio.play_sound(Effect::Click, Duration::from_millis(0));
+        state.annotation_draft.clear();
+        state.window_stack.pop();
+    }
+
+    RunningState::Running
+}
+
+/// The character a key types into the name.
+fn key_char(key: Key) -> Option<char> {
+    use KeyCode::*;
+    let c = match key.code {
+        D0 | NumPad0 => '0',
+        D1 | NumPad1 => '1',
+        D2 | NumPad2 => '2',
+        D3 | NumPad3 => '3',
+        D4 | NumPad4 => '4',
+        D5 | NumPad5 => '5',
+        D6 | NumPad6 => '6',
+        D7 | NumPad7 => '7',
+        D8 | NumPad8 => '8',
+        D9 | NumPad9 => '9',
+        A => 'a',
+        B => 'b',
+        C => 'c',
+        D => 'd',
+        E => 'e',
+        F => 'f',
+        G => 'g',
+        H => 'h',
+        I => 'i',
+        J => 'j',
+        K => 'k',
+        L => 'l',
+        M => 'm',
+        N => 'n',
+        O => 'o',
+        P => 'p',
+        Q => 'q',
+        R => 'r',
+        S => 's',
+        T => 't',
+        U => 'u',
+        V => 'v',
+        W => 'w',
+        X => 'x',
+        Y => 'y',
+        Z => 'z',
+        Space => ' ',
+        QuestionMark => '?',
+        _ => return None,
+    };
+    if key.shift {
+        Some(c.to_ascii_uppercase())
+    } else {
+        Some(c)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    audio::{Audio, Effect},
+    daily,
+    engine::Display,
+    game::{self, RunningState},
+    gamepad::Gamepad,
+    keys::KeyCode,
+    state::{GameSession, State},
+    ui,
+};
+
+use std::time::Duration;
+
+use egui::{self, Grid, ScrollArea, Ui};
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    _gamepad: &Gamepad,
+    display: &Display,
+    audio: &mut Audio,
+) -> RunningState {
+    let mut visible = true;
+    let mut play = false;
+
+    let today = daily::today();
+    let attempted = state.daily_leaderboard.attempted(&today);
+
+    let screen_size_px = display.screen_size_px;
+    let window_size_px = [
+        (screen_size_px.x - 150).min(800) as f32,
+        (screen_size_px.y - 150).min(600) as f32,
+    ];
+    let window_pos_px = [(screen_size_px.x as f32 - window_size_px[0]) / 2.0, 75.0];
+
+    egui::Window::new("Daily Challenge")
+        .open(&mut visible)
+        .collapsible(false)
+        .fixed_pos(window_pos_px)
+        .fixed_size(window_size_px)
+        .show(ui.ctx(), |ui| {
+            ui.label(format!("Today: {today}"));
+            ui.label(
+                "Everyone gets the same world and all the challenges are on. \
+                 You only get one attempt per day.",
+            );
+            match state.daily_leaderboard.entry(&today) {
+                Some(entry) => {
+                    ui.label(format!(
+                        "Today's result: {}, turns: {}",
+                        entry.outcome(),
+                        entry.turns
+                    ));
+                }
+                None => {
+                    if ui::button(ui, "[P]lay today's challenge", true, &state.palette).clicked() {
+                        play = true;
+                    }
+                }
+            }
+            ui.separator();
+
+            let entries = state.daily_leaderboard.ranked();
+            if entries.is_empty() {
+                ui.label("You haven't played any daily challenges yet.");
+            } else {
+                ScrollArea::vertical().show(ui, |ui| {
+                    Grid::new("Daily Leaderboard")
+                        .striped(true)
+                        .num_columns(6)
+                        .show(ui, |ui| {
+                            for header in ["#", "Date", "Outcome", "Turns", "Seed", "Replay"] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+
+                            for (index, entry) in entries.iter().enumerate() {
+                                ui.label(format!("{}", index + 1));
+                                ui.label(&entry.date);
+                                ui.label(entry.outcome());
+                                ui.label(format!("{}", entry.turns));
+                                ui.label(format!("{}", entry.seed));
+                                match &entry.replay {
+                                    Some(replay) => {
+                                        let file_name = replay
+                                            .file_name()
+                                            .map(|name| name.to_string_lossy().into_owned())
+                                            .unwrap_or_default();
+                                        ui.label(file_name)
+                                            .on_hover_text(replay.display().to_string());
+                                    }
+                                    None => {
+                                        ui.label("-");
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            }
+
+            ui.separator();
+            ui.label("[Esc] Back");
+        });
+
+    if !attempted && state.keys.matches_code(KeyCode::P) {
+        play = true;
+    }
+
+    if play && state.daily_leaderboard.start(&today) {
+        audio.play_sound(Effect::Click, Duration::from_millis(0));
+        if let Err(err) = state.daily_leaderboard.save() {
+            log::error!("Could not save the daily leaderboard: {}", err);
+        }
+        let mut new_state = Box::new(game::create_daily_game_state(state, &today));
+        new_state.game_session = GameSession::InProgress;
+        return RunningState::NewGame(new_state);
+    }
+
+    if !visible || state.keys.matches_code(KeyCode::Esc) || state.mouse.right_clicked {
+        audio.play_sound(Effect::Click, Duration::from_millis(0));
+        state.window_stack.pop();
+    }
+
+    RunningState::Running
+}
@@ -18,6 +18,7 @@ use egui::{self, Ui};
 #[derive(Copy, Clone, PartialEq)]
 pub enum Action {
     NewGame,
+    Report,
     Help,
     Menu,
     Close,
@@ -96,6 +97,10 @@ pub fn process(
                     "Longest High streak: {} turns",
                     state.player.longest_high_streak
                 ));
+                ui.label(format!(
+                    "Longest sober streak: {} turns",
+                    state.player.longest_sober_streak
+                ));
                 ui.label("");
                 let carrying_doses_text = if state.player_picked_up_a_dose {
                     let doses_in_inventory = state
@@ -116,7 +121,7 @@ pub fn process(
                 }
 
                 ui.separator();
-                ui.columns(3, |c| {
+                ui.columns(4, |c| {
                     c[0].with_layout(egui::Layout::top_down(egui::Align::Min), |ui| {
                         let button = ui::button(ui, "[N]ew Game", active, &state.palette);
                         if state.selected_endgame_window_action == Some(Action::NewGame) {
@@ -127,6 +132,15 @@ pub fn process(
                         };
                     });
                     c[1].with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        let button = ui::button(ui, "[R]eport", active, &state.palette);
+                        if state.selected_endgame_window_action == Some(Action::Report) {
+                            button.request_focus();
+                        }
+                        if button.clicked() {
+                            action = Some(Action::Report);
+                        };
+                    });
+                    c[2].with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
                         let button = ui::button(ui, "[?] Help", active, &state.palette);
                         if state.selected_endgame_window_action == Some(Action::Help) {
                             button.request_focus();
@@ -135,7 +149,7 @@ pub fn process(
                             action = Some(Action::Help);
                         };
                     });
-                    c[2].with_layout(egui::Layout::top_down(egui::Align::Max), |ui| {
+                    c[3].with_layout(egui::Layout::top_down(egui::Align::Max), |ui| {
                         let button = ui::button(ui, "[Esc] Main Menu", active, &state.palette);
                         if state.selected_endgame_window_action == Some(Action::Menu) {
                             button.request_focus();
@@ -161,6 +175,8 @@ pub fn process(
             action = Some(Action::NewGame);
         } else if state.keys.matches_code(KeyCode::Esc) {
             action = Some(Action::Menu);
+        } else if state.keys.matches_code(KeyCode::R) {
+            action = Some(Action::Report);
         } else if state.keys.matches_code(KeyCode::QuestionMark)
             || state.keys.matches_code(KeyCode::H)
         {
@@ -170,14 +186,16 @@ pub fn process(
         } else if state.keys.matches_code(KeyCode::Left) || stick_flicked_left {
             state.selected_endgame_window_action = match state.selected_endgame_window_action {
                 Some(Action::NewGame) => Some(Action::Menu),
-                Some(Action::Help) => Some(Action::NewGame),
+                Some(Action::Report) => Some(Action::NewGame),
+                Some(Action::Help) => Some(Action::Report),
                 Some(Action::Menu) => Some(Action::Help),
                 _ => Some(Action::NewGame),
             };
             audio.play_sound(Effect::Click, Duration::from_millis(0));
         } else if state.keys.matches_code(KeyCode::Right) || stick_flicked_right {
             state.selected_endgame_window_action = match state.selected_endgame_window_action {
-                Some(Action::NewGame) => Some(Action::Help),
+                Some(Action::NewGame) => Some(Action::Report),
+                Some(Action::Report) => Some(Action::Help),
                 Some(Action::Help) => Some(Action::Menu),
                 Some(Action::Menu) => Some(Action::NewGame),
                 _ => Some(Action::NewGame),
@@ -205,6 +223,10 @@ pub fn process(
             state.window_stack.push(window::Window::MainMenu);
             RunningState::Running
         }
+        Some(Action::Report) => {
+            state.window_stack.push(window::Window::Report);
+            RunningState::Running
+        }
         Some(Action::Help) => {
             state.window_stack.push(window::Window::Help);
             RunningState::Running
@@ -3,7 +3,10 @@ use crate::{
     engine::Display,
     game::RunningState,
     gamepad::Gamepad,
+    item,
     keys::KeyCode,
+    level::TileKind,
+    monster,
     state::State,
     ui,
 };
@@ -90,6 +93,14 @@ pub const CONTROLS_HEADER: &str = "You control the @ character. It moves just li
 
 pub const CONTROLS_FOOTER: &str = "Using items: you can use an item you're carrying (food and later on, doses) by clicking on it in the sidebar or pressing its number on the keyboard (not numpad -- that's for movement).";
 
+pub const EXAMINE: &str = "Examining: press [L] (or Y / Triangle on a controller) to look around. Move the cursor the same way you move your character or click on a tile to see what's there. Press [L] or [Esc] to stop.";
+
+pub const ZOOM: &str = "Zooming: press [+] and [-], scroll the mouse wheel over the map or pull the right and left triggers on a controller to see less or more of the world around you.";
+
+pub const MAP: &str = "Map: press [M] (or Select / Share on a controller) or click the minimap in the sidebar to see everything you've explored. Drag it with the mouse or use the arrow keys, D-pad or the left stick to look around. Zoom with the mouse wheel, [Z] and [X], [+] and [-] (or the triggers on a controller). Press [M] or [Esc] to close it.";
+
+pub const MARKS: &str = "Marks and waypoints: while examining, press [A] to name the tile (e.g. \"dose cache\") or [G] to walk there. You'll also walk to any tile you click on the map. Walking stops when something needs your attention, press [G] to carry on.";
+
 pub const NUMPAD_TEXT: &str = r"Numpad:
 
 Imagine your @ is in the middle (where [5] is) and you just pick a direction.";
@@ -146,26 +157,86 @@ To progress, your Will needs to get stronger. Defeat enough Anxieties to make it
 
 If you see another player characters, they are friendly. They will give you a bonus and follow you around, but only while you're Sober. You can have only one bonus active at a time."#;
 
-pub const LEGEND: &str = "Monsters:
-Anxiety: takes Will away when it hits you. Defeat them to win the game.
-Depression: moves twice as fast. You lose immediately when it hits you.
-Hunger: summons other Hungers nearby. Reduces your mind state.
-Hearing Voices: paralyzes you for three turns.
-Seeing Shadows: makes you move randomly for three turns.
+/// The monsters in the order they're listed in the Legend.
+const LEGEND_MONSTERS: [monster::Kind; 5] = [
+    monster::Kind::Anxiety,
+    monster::Kind::Depression,
+    monster::Kind::Hunger,
+    monster::Kind::Voices,
+    monster::Kind::Shadows,
+];
+
+/// Name and description of the monster as shown in the Legend.
+pub fn monster_legend(kind: monster::Kind) -> (&'static str, &'static str) {
+    use crate::monster::Kind::*;
+    match kind {
+        Anxiety => (
+            "Anxiety",
+            "takes Will away when it hits you. Defeat them to win the game.",
+        ),
+        Depression => (
+            "Depression",
+            "moves twice as fast. You lose immediately when it hits you.",
+        ),
+        Hunger => (
+            "Hunger",
+            "summons other Hungers nearby. Reduces your mind state.",
+        ),
+        Voices => ("Hearing Voices", "paralyzes you for three turns."),
+        Shadows => ("Seeing Shadows", "makes you move randomly for three turns."),
+        Npc => (
+            "NPC",
+            "ignores you when High. Talk to them Sober for a bonus.",
+        ),
+        Signpost => ("Signpost", "has a message for you."),
+    }
+}
 
-NPC: ignores you when High. Talk to them Sober for a bonus.
+/// Description of the item as shown in the Legend.
+pub fn item_legend(kind: item::Kind) -> &'static str {
+    use crate::item::Kind::*;
+    match kind {
+        Food => "prolongs being Sober or in a Withdrawal. Kills monsters around you.",
+        Dose => "makes you High. When you're High already, you'll likely Overdose.",
+        CardinalDose => "Destroys trees in the horizontal and vertical lines.",
+        DiagonalDose => "Destroys trees in the diagonal lines.",
+        StrongDose => "very strong Dose. Don't use it by accident.",
+    }
+}
 
-Items:
-Food: prolongs being Sober or in a Withdrawal. Kills monsters around you.
-Dose: makes you High. When you're High already, you'll likely Overdose.
-Cardinal Dose: Destroys trees in the horizontal and vertical lines.
-Diagonal Dose: Destroys trees in the diagonal lines.
-Strong Dose: very strong Dose. Don't use it by accident.
+/// Name and description of the tile.
+pub fn tile_legend(kind: TileKind) -> (&'static str, &'static str) {
+    match kind {
+        TileKind::Empty => ("Ground", "you can walk here."),
+        TileKind::Tree => ("Tree", "blocks your way. Some Doses destroy trees."),
+    }
+}
 
-Each Dose has a faint glow around it. If you step into it, you will not be able to resist.
+pub const DOSE_GLOW: &str = "Each Dose has a faint glow around it. If you step into it, you will not be able to resist.
 
 When the glow disappears completely, you can pick the dose up and use it later. Don't lose Will if you're carrying doses though!";
 
+pub fn legend() -> String {
+    let mut lines = vec!["Monsters:".to_string()];
+    for kind in LEGEND_MONSTERS {
+        let (name, description) = monster_legend(kind);
+        lines.push(format!("{name}: {description}"));
+    }
+    lines.push(String::new());
+    let (npc, npc_description) = monster_legend(monster::Kind::Npc);
+    lines.push(format!("{npc}: {npc_description}"));
+    lines.push(String::new());
+
+    lines.push("Items:".to_string());
+    for kind in item::Kind::iter() {
+        lines.push(format!("{}: {}", kind.name(), item_legend(kind)));
+    }
+    lines.push(String::new());
+    lines.push(DOSE_GLOW.to_string());
+
+    lines.join("\n")
+}
+
 pub const CREDITS_DEV: &str = "Design and development by Tomas Sedovic: https://tomas.sedovic.cz/";
 pub const TOMAS_URL: &str = "https://tomas.sedovic.cz/";
 
@@ -281,6 +352,14 @@ pub fn process(
                         });
                         ui.label(CONTROLS_FOOTER);
                         ui.label("");
+                        ui.label(EXAMINE);
+                        ui.label("");
+                        ui.label(ZOOM);
+                        ui.label("");
+                        ui.label(MAP);
+                        ui.label("");
+                        ui.label(MARKS);
+                        ui.label("");
                         ui.label(CONTROLLER);
                     }
 
@@ -289,7 +368,7 @@ pub fn process(
                     }
 
                     Page::Legend => {
-                        ui.label(LEGEND);
+                        ui.label(legend());
                     }
 
                     Page::Credits => {
@@ -1,9 +1,12 @@
 use crate::{
+    achievements,
     audio::{Audio, Effect},
+    daily,
     engine::{Display, TextMetrics},
     game,
     game::RunningState,
     gamepad::Gamepad,
+    history,
     keys::KeyCode,
     settings::Settings,
     state::{GameSession, State},
@@ -22,8 +25,11 @@ use std::time::Duration;
 pub enum MenuItem {
     Resume,
     NewGame,
+    Daily,
     Help,
     Settings,
+    Statistics,
+    Achievements,
     SaveAndQuit,
     Load,
     Quit,
@@ -106,9 +112,12 @@ pub fn process(
             // TODO: this is ignoring any disabled items, we need to handle those!
             let new_selected_action = match state.selected_menu_action {
                 Some(Resume) => NewGame,
-                Some(NewGame) => Help,
+                Some(NewGame) => Daily,
+                Some(Daily) => Help,
                 Some(Help) => Settings,
-                Some(Settings) => {
+                Some(Settings) => Statistics,
+                Some(Statistics) => Achievements,
+                Some(Achievements) => {
                     if game_in_progress {
                         SaveAndQuit
                     } else {
@@ -149,14 +158,17 @@ pub fn process(
                         Quit
                     }
                 }
-                Some(Help) => NewGame,
+                Some(Daily) => NewGame,
+                Some(Help) => Daily,
                 Some(Settings) => Help,
-                Some(SaveAndQuit) => Settings,
+                Some(Statistics) => Settings,
+                Some(Achievements) => Statistics,
+                Some(SaveAndQuit) => Achievements,
                 Some(Load) => {
                     if game_in_progress {
                         SaveAndQuit
                     } else {
-                        Settings
+                        Achievements
                     }
                 }
                 Some(Quit) => Load,
@@ -195,6 +207,16 @@ pub fn process(
             }
         }
 
+        {
+            let resp = ui::button(ui, "[D]aily Challenge", active, &state.palette);
+            if active && state.selected_menu_action == Some(MenuItem::Daily) {
+                resp.request_focus();
+            }
+            if resp.clicked() {
+                action = Some(MenuItem::Daily);
+            }
+        }
+
         {
             let resp = ui::button(ui, "[H]elp", active, &state.palette);
             if active && state.selected_menu_action == Some(MenuItem::Help) {
@@ -219,6 +241,26 @@ pub fn process(
             }
         }
 
+        {
+            let resp = ui::button(ui, "Sta[t]istics", active, &state.palette);
+            if active && state.selected_menu_action == Some(MenuItem::Statistics) {
+                resp.request_focus();
+            }
+            if resp.clicked() {
+                action = Some(MenuItem::Statistics);
+            }
+        }
+
+        {
+            let resp = ui::button(ui, "[A]chievements", active, &state.palette);
+            if active && state.selected_menu_action == Some(MenuItem::Achievements) {
+                resp.request_focus();
+            }
+            if resp.clicked() {
+                action = Some(MenuItem::Achievements);
+            }
+        }
+
         if game_in_progress {
             let resp = ui::button(ui, "[S]ave and Quit", active, &state.palette);
             if active && state.selected_menu_action == Some(MenuItem::SaveAndQuit) {
@@ -260,7 +302,11 @@ pub fn process(
     });
 
     if action.is_none() && active {
-        if state.keys.matches_code(KeyCode::E) {
+        if state.keys.matches_code(KeyCode::A) {
+            action = Some(MenuItem::Achievements);
+        } else if state.keys.matches_code(KeyCode::D) {
+            action = Some(MenuItem::Daily);
+        } else if state.keys.matches_code(KeyCode::E) {
             action = Some(MenuItem::Settings);
         } else if state.keys.matches_code(KeyCode::H)
             || state.keys.matches_code(KeyCode::QuestionMark)
@@ -279,6 +325,8 @@ pub fn process(
             action = Some(MenuItem::Resume);
         } else if state.keys.matches_code(KeyCode::S) {
             action = Some(MenuItem::SaveAndQuit);
+        } else if state.keys.matches_code(KeyCode::T) {
+            action = Some(MenuItem::Statistics);
         } else if state.keys.matches_code(KeyCode::Enter) {
             action = state.selected_menu_action;
         }
@@ -310,6 +358,12 @@ pub fn process(
                 return RunningState::NewGame(new_state);
             }
 
+            MenuItem::Daily => {
+                state.daily_leaderboard = daily::Leaderboard::load();
+                state.window_stack.push(Window::Daily);
+                return RunningState::Running;
+            }
+
             MenuItem::Help => {
                 state.window_stack.push(Window::Help);
                 return RunningState::Running;
@@ -320,6 +374,19 @@ pub fn process(
                 return RunningState::Running;
             }
 
+            MenuItem::Statistics => {
+                state.run_history = history::load();
+                state.statistics_sort.apply(&mut state.run_history);
+                state.window_stack.push(Window::Statistics);
+                return RunningState::Running;
+            }
+
+            MenuItem::Achievements => {
+                state.unlocked_achievements = achievements::Unlocked::load();
+                state.window_stack.push(Window::Achievements);
+                return RunningState::Running;
+            }
+
             MenuItem::SaveAndQuit => {
                 if state.game_session == GameSession::InProgress {
                     match state.save_to_file() {
@@ -338,7 +405,8 @@ pub fn process(
             }
 
             MenuItem::Load => match State::load_from_file() {
-                Ok(new_state) => {
+                Ok(mut new_state) => {
+                    new_state.event_log = state.event_log.for_new_game();
                     *state = new_state;
                     if state.window_stack.top() == Window::MainMenu {
                         state.window_stack.pop();
@@ -0,0 +1,360 @@
+use crate::{
+    audio::{Audio, Effect},
+    color::Color,
+    engine::Display,
+    game::RunningState,
+    gamepad::Gamepad,
+    item::Item,
+    keys::KeyCode,
+    level::{Cell, TileKind},
+    monster,
+    palette::Palette,
+    point::Point,
+    rect::Rectangle,
+    state::State,
+};
+
+use std::time::Duration;
+
+use egui::{self, Painter, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+
+/// The pixels per tile the full-screen map can be zoomed to. The
+/// first one is the default.
+const ZOOM_LEVELS: [f32; 5] = [1.0, 2.0, 3.0, 4.0, 6.0];
+
+/// How far a single key press moves the map.
+const KEY_PAN_PX: f32 = 48.0;
+
+/// How fast the map moves with the stick tilted all the way.
+const STICK_PAN_PX_PER_SECOND: f32 = 600.0;
+
+/// The smallest size of the player, NPC, signpost and dose markers.
+/// Otherwise they'd be lost at one pixel per tile.
+const MARKER_MIN_SIZE_PX: f32 = 3.0;
+
+/// The size of the annotation and waypoint names.
+const LABEL_FONT_SIZE: f32 = 14.0;
+
+/// Where the full-screen map is looking and how close.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct View {
+    /// The world position in the middle of the screen. It's in
+    /// tiles, but fractional so we can pan by less than a tile.
+    pub centre: [f32; 2],
+    /// Index into `ZOOM_LEVELS`.
+    pub zoom: usize,
+}
+
+impl View {
+    pub fn centre_on(&mut self, pos: Point) {
+        self.centre = [pos.x as f32 + 0.5, pos.y as f32 + 0.5];
+    }
+
+    pub fn pixels_per_tile(self) -> f32 {
+        ZOOM_LEVELS[self.zoom.min(ZOOM_LEVELS.len() - 1)]
+    }
+
+    /// Zoom in (positive `steps`) or out (negative) keeping the tile
+    /// at `anchor` (offset from the centre of the map in pixels) in
+    /// the same place on the screen.
+    fn zoom_by(&mut self, steps: i32, anchor: Vec2) {
+        let old_pixels_per_tile = self.pixels_per_tile();
+        self.zoom = (self.zoom as i32 + steps).clamp(0, ZOOM_LEVELS.len() as i32 - 1) as usize;
+        let new_pixels_per_tile = self.pixels_per_tile();
+        let shift = 1.0 / old_pixels_per_tile - 1.0 / new_pixels_per_tile;
+        self.centre[0] += anchor.x * shift;
+        self.centre[1] += anchor.y * shift;
+    }
+
+    fn pan_px(&mut self, delta: Vec2) {
+        let pixels_per_tile = self.pixels_per_tile();
+        self.centre[0] += delta.x / pixels_per_tile;
+        self.centre[1] += delta.y / pixels_per_tile;
+    }
+}
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    gamepad: &Gamepad,
+    display: &Display,
+    audio: &mut Audio,
+    dt: Duration,
+) -> RunningState {
+    let screen_rect = Rect::from_min_max(Pos2::ZERO, display.screen_size_px.into());
+    let response = ui.interact(screen_rect, ui.id().with("Map"), Sense::click_and_drag());
+
+    let mut view = state.map_view;
+
+    if response.clicked()
+        && let Some(pos) = response.interact_pointer_pos()
+    {
+        let pixels_per_tile = view.pixels_per_tile();
+        let offset = (pos - screen_rect.center()) / pixels_per_tile;
+        let waypoint = Point::new(
+            (view.centre[0] + offset.x).floor() as i32,
+            (view.centre[1] + offset.y).floor() as i32,
+        );
+        let known = state.map_uncovered()
+            || state
+                .world
+                .cell(waypoint)
+                .is_some_and(|cell| cell.explored || cell.always_visible);
+        if known {
+            audio.play_sound(Effect::Click, Duration::from_millis(0));
+            state.pending_waypoint = Some(waypoint);
+            state.window_stack.pop();
+            return RunningState::Running;
+        }
+    }
+
+    if response.dragged() {
+        view.pan_px(-response.drag_delta());
+    }
+
+    let pointer = response
+        .hover_pos()
+        .map_or(Vec2::ZERO, |pos| pos - screen_rect.center());
+    let scroll = state.mouse.scroll_delta[1];
+    if scroll > 0.0 {
+        view.zoom_by(1, pointer);
+    } else if scroll < 0.0 {
+        view.zoom_by(-1, pointer);
+    }
+
+    let mut key_pan = Vec2::ZERO;
+    if state.keys.matches_code(KeyCode::Up) || state.keys.matches_code(KeyCode::NumPad8) {
+        key_pan.y -= KEY_PAN_PX;
+    }
+    if state.keys.matches_code(KeyCode::Down) || state.keys.matches_code(KeyCode::NumPad2) {
+        key_pan.y += KEY_PAN_PX;
+    }
+    if state.keys.matches_code(KeyCode::Left) || state.keys.matches_code(KeyCode::NumPad4) {
+        key_pan.x -= KEY_PAN_PX;
+    }
+    if state.keys.matches_code(KeyCode::Right) || state.keys.matches_code(KeyCode::NumPad6) {
+        key_pan.x += KEY_PAN_PX;
+    }
+    view.pan_px(key_pan);
+
+    // NOTE: the gamepad triggers come in as `Plus` and `Minus`.
+    if state.keys.matches_code(KeyCode::Z) || state.keys.matches_code(KeyCode::Plus) {
+        view.zoom_by(1, Vec2::ZERO);
+    }
+    if state.keys.matches_code(KeyCode::X) || state.keys.matches_code(KeyCode::Minus) {
+        view.zoom_by(-1, Vec2::ZERO);
+    }
+    if state.keys.matches_code(KeyCode::C) {
+        view.centre_on(state.player.pos);
+    }
+
+    // NOTE: the stick's Y axis points up, the screen's down
+    let stick = Vec2::new(gamepad.left_stick_x, -gamepad.left_stick_y);
+    view.pan_px(stick * STICK_PAN_PX_PER_SECOND * dt.as_secs_f32());
+
+    state.map_view = view;
+
+    draw(
+        ui.painter(),
+        screen_rect,
+        state,
+        view.centre,
+        view.pixels_per_tile(),
+        true,
+    );
+
+    let legend_height = 2.5 * display.text_size as f32;
+    let legend_rect = Rect::from_min_max(
+        screen_rect.left_bottom() - Vec2::new(0.0, legend_height),
+        screen_rect.right_bottom(),
+    );
+    ui.painter()
+        .rect_filled(legend_rect, 0.0, state.palette.gui_window_background);
+    let mut legend_ui = ui.new_child(egui::UiBuilder::new().max_rect(legend_rect.shrink(10.0)));
+    legend(&mut legend_ui, &state.palette, state.player.color_index);
+
+    if state.keys.matches_code(KeyCode::Esc)
+        || state.keys.matches_code(KeyCode::M)
+        || state.mouse.right_clicked
+    {
+        audio.play_sound(Effect::Click, Duration::from_millis(0));
+        state.window_stack.pop();
+    }
+
+    RunningState::Running
+}
+
+fn legend(ui: &mut Ui, palette: &Palette, player_color_index: usize) {
+    ui.horizontal_wrapped(|ui| {
+        ui.colored_label(palette.player(player_color_index), "* You");
+        ui.colored_label(palette.npc_dim, "* NPC");
+        ui.colored_label(palette.signpost, "* Signpost");
+        ui.colored_label(palette.dose, "* Dose");
+        ui.colored_label(palette.gui_text, "[] Mark");
+        ui.colored_label(palette.player(player_color_index), "[] Waypoint");
+        ui.colored_label(
+            palette.gui_text,
+            "[Arrows] Pan, [Z]/[+] Zoom in, [X]/[-] Zoom out, [C] Centre, [Click] Walk there, [M] Close",
+        );
+    });
+}
+
+fn tile_color(kind: TileKind, palette: &Palette) -> Color {
+    match kind {
+        TileKind::Empty => palette.explored_background,
+        TileKind::Tree => palette.tree(0),
+    }
+}
+
+/// Draw every explored cell within `rect` at `pixels_per_tile` with
+/// the `centre` world position (in tiles) in the middle. Along with
+/// the player, any known NPCs, signposts and doses and the player's
+/// annotations and waypoint (named if `show_labels` is set).
+pub fn draw(
+    painter: &Painter,
+    rect: Rect,
+    state: &State,
+    centre: [f32; 2],
+    pixels_per_tile: f32,
+    show_labels: bool,
+) {
+    let painter = painter.with_clip_rect(rect);
+    let palette = &state.palette;
+    painter.rect_filled(rect, 0.0, palette.unexplored_background);
+
+    let to_screen = |x: f32, y: f32| {
+        Pos2::new(
+            rect.center().x + (x - centre[0]) * pixels_per_tile,
+            rect.center().y + (y - centre[1]) * pixels_per_tile,
+        )
+    };
+    let tile_rect = |pos: Point, size_px: f32| {
+        let tile_centre = to_screen(pos.x as f32 + 0.5, pos.y as f32 + 0.5);
+        Rect::from_center_size(tile_centre, Vec2::splat(size_px))
+    };
+
+    let half_size = Point::new(
+        (rect.width() / pixels_per_tile / 2.0).ceil() as i32 + 1,
+        (rect.height() / pixels_per_tile / 2.0).ceil() as i32 + 1,
+    );
+    let area = Rectangle::center(
+        Point::new(centre[0].floor() as i32, centre[1].floor() as i32),
+        half_size,
+    );
+
+    let uncovered = state.map_uncovered();
+    let known = |cell: &Cell| uncovered || cell.explored || cell.always_visible;
+
+    // NOTE: a rectangle per tile would overflow the vertex buffer on
+    // a large map. The cells are stored row by row so we merge the
+    // neighbouring ones of the same colour into a single rectangle.
+    let mut doses = vec![];
+    let fill_run = |start: Point, length: i32, color: Color| {
+        let run_rect = Rect::from_min_max(
+            to_screen(start.x as f32, start.y as f32),
+            to_screen((start.x + length) as f32, (start.y + 1) as f32),
+        );
+        painter.rect_filled(run_rect, 0.0, color);
+    };
+    for chunk in state.world.chunks(area) {
+        let mut run: Option<(Point, i32, Color)> = None;
+        for (pos, cell) in chunk.cells() {
+            if !known(cell) || !area.contains_inclusive(pos) {
+                if let Some((start, length, color)) = run.take() {
+                    fill_run(start, length, color);
+                }
+                continue;
+            }
+            if cell.items.iter().any(Item::is_dose) {
+                doses.push(pos);
+            }
+            let color = tile_color(cell.tile.kind, palette);
+            match &mut run {
+                Some((start, length, run_color))
+                    if start.y == pos.y && start.x + *length == pos.x && *run_color == color =>
+                {
+                    *length += 1;
+                }
+                _ => {
+                    if let Some((start, length, color)) = run.take() {
+                        fill_run(start, length, color);
+                    }
+                    run = Some((pos, 1, color));
+                }
+            }
+        }
+        if let Some((start, length, color)) = run {
+            fill_run(start, length, color);
+        }
+    }
+
+    let marker_size = pixels_per_tile.max(MARKER_MIN_SIZE_PX);
+    for pos in doses {
+        painter.rect_filled(tile_rect(pos, marker_size), 0.0, palette.dose);
+    }
+
+    let known_monsters = state.world.monsters(area).filter(|monster| {
+        matches!(monster.kind, monster::Kind::Npc | monster::Kind::Signpost)
+            && state.world.cell(monster.position).is_some_and(known)
+    });
+    for monster in known_monsters {
+        painter.rect_filled(
+            tile_rect(monster.position, marker_size),
+            0.0,
+            monster.color(palette),
+        );
+    }
+
+    let player_color = palette.player(state.player.color_index);
+    for annotation in &state.annotations {
+        let rect = tile_rect(annotation.pos, marker_size + 2.0);
+        if show_labels {
+            mark_tile(&painter, rect, &annotation.name, palette.gui_text, palette);
+        } else {
+            painter.rect_stroke(
+                rect,
+                0.0,
+                Stroke::new(1.0, palette.gui_text),
+                egui::StrokeKind::Outside,
+            );
+        }
+    }
+    if let Some(waypoint) = state.waypoint {
+        let rect = tile_rect(waypoint, marker_size + 2.0);
+        if show_labels {
+            mark_tile(&painter, rect, "Waypoint", player_color, palette);
+        } else {
+            painter.rect_stroke(
+                rect,
+                0.0,
+                Stroke::new(1.0, player_color),
+                egui::StrokeKind::Outside,
+            );
+        }
+    }
+
+    let player_rect = tile_rect(state.player.pos, marker_size + 2.0);
+    painter.rect_filled(player_rect, 0.0, player_color);
+    painter.rect_stroke(
+        player_rect.expand(1.0),
+        0.0,
+        Stroke::new(1.0, palette.gui_text),
+        egui::StrokeKind::Outside,
+    );
+}
+
+/// Outline the tile at `rect` and put the `name` above it.
+pub fn mark_tile(painter: &Painter, rect: Rect, name: &str, color: Color, palette: &Palette) {
+    painter.rect_stroke(
+        rect,
+        0.0,
+        Stroke::new(2.0, color),
+        egui::StrokeKind::Outside,
+    );
+    let font = egui::FontId::proportional(LABEL_FONT_SIZE);
+    let galley = painter.layout_no_wrap(name.to_string(), font, color.into());
+    let label_rect = egui::Align2::CENTER_BOTTOM
+        .anchor_size(rect.center_top() - Vec2::new(0.0, 4.0), galley.size());
+    painter.rect_filled(label_rect.expand(2.0), 2.0, palette.gui_window_background);
+    painter.galley(label_rect.min, galley, color.into());
+}
@@ -0,0 +1,134 @@
+use crate::{formula, paths, state::State, trace, util};
+
+use std::{fs, io, path::PathBuf};
+
+use egui::{self, Rect, Sense, Shape, Stroke, Ui};
+
+/// The number of the most recent frames shown in the graph.
+const GRAPH_FRAMES: usize = 120;
+
+const GRAPH_SIZE_PX: [f32; 2] = [240.0, 60.0];
+
+/// The frame duration at the top of the graph in milliseconds. Longer
+/// frames are cut off.
+const GRAPH_MAX_MS: f32 = 50.0;
+
+/// Draw the frame timings on top of whatever window is open.
+///
+/// NOTE: the overlay doesn't take any input so it can't get in the
+/// way of the game. The trace recording is toggled by F4 instead.
+pub fn show(state: &State, ui: &mut Ui) {
+    let stats = &state.stats;
+    egui::Window::new("Performance")
+        .id(egui::Id::new("Performance Overlay"))
+        .anchor(egui::Align2::LEFT_TOP, [10.0, 10.0])
+        .collapsible(false)
+        .resizable(false)
+        .interactable(false)
+        .show(ui.ctx(), |ui| {
+            let (rect, _response) = ui.allocate_exact_size(GRAPH_SIZE_PX.into(), Sense::hover());
+            paint_graph(state, ui, rect);
+
+            // NOTE: `mean_update` is NaN until the first frame's
+            // recorded.
+            let mean_update = if stats.last_frames(1).next().is_some() {
+                stats.mean_update()
+            } else {
+                0.0
+            };
+            ui.label(format!(
+                "Update: {:.2} ms mean, {:.2} ms worst",
+                mean_update,
+                stats.longest_update().as_secs_f32() * 1000.0
+            ));
+            ui.label(format!(
+                "Drawcalls: {}, vertices: {}",
+                stats.drawcall_count(),
+                stats.vertex_count()
+            ));
+            if trace::recording() {
+                ui.label(format!(
+                    "Recording a trace: {} spans (F4 to save)",
+                    trace::event_count()
+                ));
+            } else {
+                ui.label("F4 to record a trace");
+            }
+        });
+}
+
+/// A bar per frame, the newest on the right. The full bar is the
+/// time between the frames, the brighter part is the game update.
+fn paint_graph(state: &State, ui: &Ui, rect: Rect) {
+    let palette = &state.palette;
+    let painter = ui.painter_at(rect);
+    painter.add(Shape::rect_filled(rect, 0.0, palette.gui_window_background));
+
+    let bar_width = rect.width() / GRAPH_FRAMES as f32;
+    let height_px = |ms: f32| (ms / GRAPH_MAX_MS).min(1.0) * rect.height();
+    for (index, frame_stats) in state.stats.last_frames(GRAPH_FRAMES).enumerate() {
+        let right = rect.right() - index as f32 * bar_width;
+        let bar = |ms: f32| {
+            Rect::from_min_max(
+                [right - bar_width, rect.bottom() - height_px(ms)].into(),
+                [right, rect.bottom()].into(),
+            )
+        };
+        let frame_ms = frame_stats.frame.as_secs_f32() * 1000.0;
+        let update_ms = frame_stats.update.as_secs_f32() * 1000.0;
+        painter.add(Shape::rect_filled(
+            bar(frame_ms),
+            0.0,
+            palette.gui_text_inactive,
+        ));
+        painter.add(Shape::rect_filled(bar(update_ms), 0.0, palette.gui_text));
+    }
+
+    // The frame budget at full speed
+    let target_y = rect.bottom() - height_px(1000.0 / formula::FPS);
+    painter.add(Shape::hline(
+        rect.x_range(),
+        target_y,
+        Stroke::new(1.0, palette.gui_window_edge),
+    ));
+    painter.add(Shape::rect_stroke(
+        rect,
+        0.0,
+        Stroke::new(1.0, palette.gui_window_edge),
+        egui::StrokeKind::Inside,
+    ));
+}
+
+/// Start recording a trace or stop and save the one in progress.
+pub fn toggle_trace_recording() {
+    if trace::recording() {
+        save_trace_recording();
+    } else {
+        log::info!("Recording a performance trace.");
+        trace::start();
+    }
+}
+
+/// Stop the trace recording (if any) and write it into the trace
+/// directory.
+pub fn save_trace_recording() {
+    let Some(trace) = trace::stop() else {
+        return;
+    };
+    match write_trace(&trace) {
+        Ok(path) => log::info!(
+            "Saved {} trace events to '{}'.",
+            trace.trace_events.len(),
+            path.display()
+        ),
+        Err(err) => log::error!("Could not save the performance trace: {}", err),
+    }
+}
+
+fn write_trace(trace: &trace::Trace) -> io::Result<PathBuf> {
+    let trace_dir = paths::trace_dir();
+    paths::ensure_dir(&trace_dir);
+    let path = trace_dir.join(format!("trace-{}.json", util::unix_timestamp()));
+    fs::write(&path, trace.to_json()?)?;
+    Ok(path)
+}
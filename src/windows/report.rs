@@ -0,0 +1,285 @@
+use crate::{
+    audio::{Audio, Effect},
+    color::Color,
+    engine::Display,
+    formula,
+    game::RunningState,
+    keys::KeyCode,
+    palette::Palette,
+    state::State,
+    timeline::{self, Marker, Sample},
+};
+
+use std::time::Duration;
+
+use egui::{self, Pos2, Sense, Shape, Stroke, Ui, Vec2};
+
+const TIMELINE_HEIGHT: f32 = 220.0;
+const MINIMAP_SIZE: f32 = 260.0;
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    display: &Display,
+    audio: &mut Audio,
+) -> RunningState {
+    let mut visible = true;
+
+    let padding = 50.0;
+    let window_size = [
+        (display.screen_size_px.x as f32 - padding).min(1100.0),
+        (display.screen_size_px.y as f32 - padding).min(800.0),
+    ];
+    let window_pos_px = [
+        (display.screen_size_px.x as f32 - window_size[0]) / 2.0,
+        (display.screen_size_px.y as f32 - window_size[1]) / 2.0,
+    ];
+
+    egui::Window::new("Run Report")
+        .open(&mut visible)
+        .collapsible(false)
+        .fixed_pos(window_pos_px)
+        .fixed_size(window_size)
+        .show(ui.ctx(), |ui| {
+            let player = &state.player;
+            ui.label(format!(
+                "Turns: {}, doses used: {}, monsters defeated: {}, NPCs met: {}",
+                state.turn, player.doses_used, player.monsters_killed, player.npcs_met
+            ));
+            ui.separator();
+
+            if state.timeline.is_empty() {
+                ui.label("The game ended before the first turn was over.");
+            } else {
+                let samples = state.timeline.samples();
+                let markers = state.timeline.markers();
+                ui.label("Timeline");
+                timeline_graph(ui, samples, &markers, &state.palette);
+                legend(ui, &state.palette);
+                ui.separator();
+                ui.label("Explored path");
+                minimap(ui, samples, &markers, &state.palette);
+            }
+
+            ui.separator();
+            ui.label("[Esc] Back");
+        });
+
+    if !visible
+        || state.keys.matches_code(KeyCode::Esc)
+        || state.keys.matches_code(KeyCode::Enter)
+        || state.mouse.right_clicked
+    {
+        audio.play_sound(Effect::Click, Duration::from_millis(0));
+        state.window_stack.pop();
+    }
+
+    RunningState::Running
+}
+
+/// The lines drawn in the timeline.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Series {
+    Mind,
+    Will,
+    Tolerance,
+    Anxiety,
+}
+
+impl Series {
+    const ALL: [Series; 4] = [
+        Series::Mind,
+        Series::Will,
+        Series::Tolerance,
+        Series::Anxiety,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Series::Mind => "Mind",
+            Series::Will => "Will",
+            Series::Tolerance => "Tolerance",
+            Series::Anxiety => "Anxiety",
+        }
+    }
+
+    fn color(self, palette: &Palette) -> Color {
+        match self {
+            Series::Mind => palette.high,
+            Series::Will => palette.npc_will,
+            Series::Tolerance => palette.strong_dose,
+            Series::Anxiety => palette.anxiety,
+        }
+    }
+
+    /// The sample's value normalised to `0.0..=1.0`. Tolerance has no
+    /// upper bound so it's relative to the highest value in the game.
+    fn value(self, sample: &Sample, max_tolerance: i32) -> f32 {
+        match self {
+            Series::Mind => timeline::mind_scale(sample.mind) / 3.0,
+            Series::Will => sample.will as f32 / formula::WILL.1 as f32,
+            Series::Tolerance => sample.tolerance as f32 / max_tolerance.max(1) as f32,
+            Series::Anxiety => sample.anxiety as f32 / formula::ANXIETIES_PER_WILL.1 as f32,
+        }
+    }
+}
+
+fn marker_color(marker: Marker, palette: &Palette) -> Color {
+    match marker {
+        Marker::Dose => palette.dose,
+        Marker::Kill => palette.depression,
+        Marker::Npc => palette.npc_mind,
+    }
+}
+
+fn timeline_graph(
+    ui: &mut Ui,
+    samples: &[Sample],
+    markers: &[(Sample, Marker)],
+    palette: &Palette,
+) {
+    let width = ui.available_width();
+    let (response, painter) =
+        ui.allocate_painter(Vec2::new(width, TIMELINE_HEIGHT), Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, palette.unexplored_background);
+
+    let first_turn = samples.first().map_or(0, |sample| sample.turn);
+    let last_turn = samples.last().map_or(0, |sample| sample.turn);
+    let turn_span = (last_turn - first_turn).max(1) as f32;
+    let x_for_turn =
+        |turn: i32| rect.left() + rect.width() * (turn - first_turn) as f32 / turn_span;
+    let y_for_value = |value: f32| rect.bottom() - rect.height() * value.clamp(0.0, 1.0);
+
+    // NOTE: the Sober and High thresholds of the Mind line
+    for threshold in [1.0 / 3.0, 2.0 / 3.0] {
+        let y = y_for_value(threshold);
+        painter.line_segment(
+            [Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
+            Stroke::new(1.0, palette.gui_text_inactive),
+        );
+    }
+
+    // NOTE: there's no point drawing more than one point per pixel.
+    // This keeps the long games cheap to draw.
+    let step = (samples.len() as f32 / rect.width().max(1.0))
+        .ceil()
+        .max(1.0) as usize;
+    let max_tolerance = samples
+        .iter()
+        .map(|sample| sample.tolerance)
+        .max()
+        .unwrap_or(0);
+    for series in Series::ALL {
+        let points = samples
+            .iter()
+            .step_by(step)
+            .chain(samples.last())
+            .map(|sample| {
+                let value = series.value(sample, max_tolerance);
+                Pos2::new(x_for_turn(sample.turn), y_for_value(value))
+            })
+            .collect::<Vec<_>>();
+        painter.add(Shape::line(points, Stroke::new(2.0, series.color(palette))));
+    }
+
+    let marker_height = 8.0;
+    for (sample, marker) in markers {
+        let x = x_for_turn(sample.turn);
+        let offset = match marker {
+            Marker::Dose => 0.0,
+            Marker::Kill => 1.0,
+            Marker::Npc => 2.0,
+        };
+        let top = rect.top() + offset * marker_height;
+        painter.line_segment(
+            [Pos2::new(x, top), Pos2::new(x, top + marker_height)],
+            Stroke::new(2.0, marker_color(*marker, palette)),
+        );
+    }
+
+    if let Some(pointer) = response.hover_pos() {
+        let turn =
+            first_turn + ((pointer.x - rect.left()) / rect.width() * turn_span).round() as i32;
+        let hovered = samples
+            .iter()
+            .min_by_key(|sample| (sample.turn - turn).abs());
+        if let Some(sample) = hovered {
+            let x = x_for_turn(sample.turn);
+            painter.line_segment(
+                [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+                Stroke::new(1.0, palette.gui_text),
+            );
+            response.on_hover_text_at_pointer(format!(
+                "Turn {}\n{}\nWill: {}\nTolerance: {}\nAnxiety: {}",
+                sample.turn, sample.mind, sample.will, sample.tolerance, sample.anxiety
+            ));
+        }
+    }
+}
+
+fn legend(ui: &mut Ui, palette: &Palette) {
+    ui.horizontal_wrapped(|ui| {
+        for series in Series::ALL {
+            ui.colored_label(series.color(palette), format!("-- {}", series.name()));
+        }
+        for (name, marker) in [
+            ("Dose", Marker::Dose),
+            ("Monster defeated", Marker::Kill),
+            ("NPC met", Marker::Npc),
+        ] {
+            ui.colored_label(marker_color(marker, palette), format!("| {name}"));
+        }
+    });
+}
+
+fn minimap(ui: &mut Ui, samples: &[Sample], markers: &[(Sample, Marker)], palette: &Palette) {
+    let (response, painter) = ui.allocate_painter(Vec2::splat(MINIMAP_SIZE), Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, palette.unexplored_background);
+
+    let mut min = samples
+        .first()
+        .map_or_else(Default::default, |sample| sample.pos);
+    let mut max = min;
+    for sample in samples {
+        min.x = min.x.min(sample.pos.x);
+        min.y = min.y.min(sample.pos.y);
+        max.x = max.x.max(sample.pos.x);
+        max.y = max.y.max(sample.pos.y);
+    }
+    // NOTE: keep the aspect ratio and leave a bit of a margin around the path
+    let span = (max.x - min.x).max(max.y - min.y).max(1) as f32;
+    let inner = rect.shrink(8.0);
+    let scale = inner.width() / span;
+    let to_screen = |pos: crate::point::Point| {
+        Pos2::new(
+            inner.left() + (pos.x - min.x) as f32 * scale,
+            inner.top() + (pos.y - min.y) as f32 * scale,
+        )
+    };
+
+    let path = samples
+        .iter()
+        .map(|sample| to_screen(sample.pos))
+        .collect::<Vec<_>>();
+    painter.add(Shape::line(
+        path,
+        Stroke::new(1.5, palette.gui_text_inactive),
+    ));
+
+    for (sample, marker) in markers {
+        painter.circle_filled(to_screen(sample.pos), 3.0, marker_color(*marker, palette));
+    }
+
+    if let Some(first) = samples.first() {
+        painter.circle_stroke(
+            to_screen(first.pos),
+            5.0,
+            Stroke::new(2.0, palette.gui_text),
+        );
+    }
+    if let Some(last) = samples.last() {
+        painter.circle_filled(to_screen(last.pos), 5.0, palette.player(0));
+    }
+}
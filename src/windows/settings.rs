@@ -18,6 +18,9 @@ pub enum Action {
     FastDepression,
     Permadeath,
     HideUnseenTiles,
+    Practice,
+    Vsync,
+    PowerSaving,
     Fullscreen,
     Window,
     VisualStyle(VisualStyle),
@@ -59,6 +62,9 @@ pub fn process(
     const FAST_DEPRESSION: Option<(i32, i32)> = Some((0, 0));
     const PERMADEATH: Option<(i32, i32)> = Some((0, 1));
     const HIDE_UNSEEN_TILES: Option<(i32, i32)> = Some((0, 2));
+    const PRACTICE: Option<(i32, i32)> = Some((0, 3));
+    const VSYNC: Option<(i32, i32)> = Some((0, 4));
+    const POWER_SAVING: Option<(i32, i32)> = Some((0, 5));
     const BACKGROUND_VOLUME: Option<(i32, i32)> = Some((1, 6));
     const SOUND_VOLUME: Option<(i32, i32)> = Some((1, 7));
     const FULLSCREEN: Option<(i32, i32)> = Some((2, 0));
@@ -69,11 +75,11 @@ pub fn process(
     const ACCESSIBLE: Option<(i32, i32)> = Some((2, 5));
     const GREYSCALE: Option<(i32, i32)> = Some((2, 6));
 
-    let max_rows: [i32; 3] = [3, 8, 7];
+    let max_rows: [i32; 3] = [6, 8, 7];
 
     // NOTE: these buttons are outside of the `max_rows` table.
     // They'll be treaded specially in the UI.
-    const APPLY: Option<(i32, i32)> = Some((0, 3));
+    const APPLY: Option<(i32, i32)> = Some((0, 6));
     const BACK: Option<(i32, i32)> = Some((1, 8));
 
     let previous_settings_position = state.selected_settings_position;
@@ -246,6 +252,72 @@ Off: the entire map is uncovered.",
                     clear_keyboard_focus = true;
                 }
 
+                let resp = c[0]
+                    .checkbox(&mut settings.practice, "[P]ractice mode")
+                    .on_hover_text(
+                        "On: you can undo your turns and losing doesn't end the game. \
+These games don't count in the statistics and achievements.
+
+Off: every turn is final.",
+                    );
+                if state.selected_settings_position == PRACTICE {
+                    resp.request_focus();
+                    if option_pressed(&mut state.keys) {
+                        settings.practice = !settings.practice;
+                        audio.play_sound(Effect::Click, Duration::from_millis(0));
+                    }
+                } else {
+                    resp.surrender_focus();
+                }
+                if resp.clicked() {
+                    audio.play_sound(Effect::Click, Duration::from_millis(0));
+                    clear_keyboard_focus = true;
+                }
+
+                c[0].label("");
+                c[0].label("Performance:");
+                let resp = c[0].checkbox(&mut settings.vsync, "[V]Sync").on_hover_text(
+                    "On: wait for the monitor to refresh before showing a new frame. \
+This prevents tearing.
+
+Off: show every frame as soon as it's ready.",
+                );
+                if state.selected_settings_position == VSYNC {
+                    resp.request_focus();
+                    if option_pressed(&mut state.keys) {
+                        settings.vsync = !settings.vsync;
+                        audio.play_sound(Effect::Click, Duration::from_millis(0));
+                    }
+                } else {
+                    resp.surrender_focus();
+                }
+                if resp.clicked() {
+                    audio.play_sound(Effect::Click, Duration::from_millis(0));
+                    clear_keyboard_focus = true;
+                }
+
+                let resp = c[0]
+                    .checkbox(&mut settings.power_saving, "Power savi[n]g")
+                    .on_hover_text(
+                        "On: when nothing's moving, the game waits for your input \
+instead of redrawing the screen all the time. Uses less CPU and battery.
+
+Off: the screen is always redrawn at the full frame rate.",
+                    );
+                if state.selected_settings_position == POWER_SAVING {
+                    resp.request_focus();
+                    if option_pressed(&mut state.keys) {
+                        settings.power_saving = !settings.power_saving;
+                        audio.play_sound(Effect::Click, Duration::from_millis(0));
+                    }
+                } else {
+                    resp.surrender_focus();
+                }
+                if resp.clicked() {
+                    audio.play_sound(Effect::Click, Duration::from_millis(0));
+                    clear_keyboard_focus = true;
+                }
+
                 let mut available_key_shortcut = 1;
                 let mut c1_row_index = 0;
 
@@ -524,8 +596,12 @@ Off: the entire map is uncovered.",
                 false => 0.0,
             };
             action = Some(Action::MusicVolume(volume))
+        } else if state.keys.matches_code(KeyCode::N) {
+            action = Some(Action::PowerSaving)
         } else if state.keys.matches_code(KeyCode::O) {
             action = Some(Action::Permadeath)
+        } else if state.keys.matches_code(KeyCode::P) {
+            action = Some(Action::Practice)
         } else if state.keys.matches_code(KeyCode::R) {
             action = Some(Action::Palette(Palette::Greyscale));
         } else if state.keys.matches_code(KeyCode::S) {
@@ -536,6 +612,8 @@ Off: the entire map is uncovered.",
                 false => 0.0,
             };
             action = Some(Action::SoundVolume(volume));
+        } else if state.keys.matches_code(KeyCode::V) {
+            action = Some(Action::Vsync)
         } else if state.keys.matches_code(KeyCode::W) {
             action = Some(Action::Window);
         } else if state.keys.matches_code(KeyCode::T) {
@@ -614,6 +692,18 @@ Off: the entire map is uncovered.",
                 settings.hide_unseen_tiles = !settings.hide_unseen_tiles;
             }
 
+            Action::Practice => {
+                settings.practice = !settings.practice;
+            }
+
+            Action::Vsync => {
+                settings.vsync = !settings.vsync;
+            }
+
+            Action::PowerSaving => {
+                settings.power_saving = !settings.power_saving;
+            }
+
             Action::Fullscreen => {
                 settings.fullscreen = true;
             }
@@ -10,17 +10,26 @@ use crate::{
     rect::Rectangle,
     settings::Settings,
     state::State,
-    ui,
+    ui, windows,
 };
 
 use egui::{self, Pos2, Rect, Ui, Vec2, epaint::Shape};
 
 use std::{collections::HashMap, time::Duration};
 
+/// How many lines of the event log fit into the sidebar.
+const EVENT_LOG_LINES: f32 = 4.0;
+
+/// The height of the minimap. It's drawn at one pixel per tile and
+/// takes the full width of the sidebar.
+const MINIMAP_HEIGHT: f32 = 96.0;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Action {
     MainMenu,
     Help,
+    Map,
+    Undo,
     UseFood,
     UseDose,
     UseCardinalDose,
@@ -76,6 +85,9 @@ pub fn process(
             let stick_flicked_up = gamepad.left_stick_flicked && gamepad.left_stick_y > 0.0;
             let stick_flicked_down = gamepad.left_stick_flicked && gamepad.left_stick_y < 0.0;
 
+            // NOTE: the Undo button is only there in the practice mode
+            let undo_shown = state.challenge.practice;
+
             if state.keys.matches_code(KeyCode::Down) || stick_flicked_down {
                 let new_selected_action = match state.selected_sidebar_action {
                     Some(UseFood) => UseDose,
@@ -83,7 +95,9 @@ pub fn process(
                     Some(UseCardinalDose) => UseDiagonalDose,
                     Some(UseDiagonalDose) => UseStrongDose,
                     Some(UseStrongDose) => Help,
+                    Some(Help) if undo_shown => Undo,
                     Some(Help) => MainMenu,
+                    Some(Undo) => MainMenu,
                     Some(MainMenu) => UseFood,
                     _ => UseFood,
                 };
@@ -98,6 +112,8 @@ pub fn process(
                     Some(UseDiagonalDose) => UseCardinalDose,
                     Some(UseStrongDose) => UseDiagonalDose,
                     Some(Help) => UseStrongDose,
+                    Some(Undo) => Help,
+                    Some(MainMenu) if undo_shown => Undo,
                     Some(MainMenu) => Help,
                     _ => UseStrongDose,
                 };
@@ -282,6 +298,8 @@ pub fn process(
         let button = ui::ImageTextButton::new(texture, button_label)
             .prefix_text(format!("[{}]", inventory_key))
             .tile(graphic)
+            .tileset(&display.tileset)
+            .glyph_atlas(&display.glyph_atlas)
             .tile_offset_px(tile_offset)
             .image_color(item_color)
             .text_color(state.palette.gui_text)
@@ -305,6 +323,41 @@ pub fn process(
         };
     }
 
+    // NOTE: the text description of what's been happening. The
+    // newest entries are at the bottom and we keep scrolling there
+    // unless the player scrolls up.
+    let event_log_height = EVENT_LOG_LINES * settings.text_size as f32;
+    egui::ScrollArea::vertical()
+        .id_salt("Sidebar Event Log")
+        .max_height(event_log_height)
+        .auto_shrink([false, false])
+        .stick_to_bottom(true)
+        .show(&mut ui, |ui| {
+            for entry in state.event_log.entries() {
+                ui.label(&entry.text);
+            }
+        });
+
+    // NOTE: the minimap. Clicking it opens the full-screen map.
+    let (minimap_response, minimap_painter) = ui.allocate_painter(
+        Vec2::new(ui_rect.width(), MINIMAP_HEIGHT),
+        egui::Sense::click(),
+    );
+    windows::map::draw(
+        &minimap_painter,
+        minimap_response.rect,
+        state,
+        [
+            state.player.pos.x as f32 + 0.5,
+            state.player.pos.y as f32 + 0.5,
+        ],
+        1.0,
+        false,
+    );
+    if minimap_response.on_hover_text("[M] Open the map").clicked() {
+        action = Some(Action::Map);
+    }
+
     let mut highlighted_tile = None;
 
     let mut help_rect = Rect::NAN; // Will be filled in later
@@ -323,6 +376,23 @@ pub fn process(
             action = Some(Action::MainMenu);
         }
 
+        if state.challenge.practice {
+            let undo_resp = ui::button(
+                ui,
+                "[U]ndo turn",
+                active && !state.replay && state.can_undo(),
+                &state.palette,
+            );
+            if state.inventory_focused && Some(Action::Undo) == state.selected_sidebar_action {
+                undo_resp.request_focus();
+            } else {
+                undo_resp.surrender_focus();
+            }
+            if undo_resp.clicked() {
+                action = Some(Action::Undo);
+            }
+        }
+
         let help_response = ui::button(ui, "[?] Help", active, &state.palette);
         if state.inventory_focused && Some(Action::Help) == state.selected_sidebar_action {
             help_response.request_focus();
@@ -438,8 +508,13 @@ pub fn process(
                         VisualStyle::Textual => Texture::Glyph,
                     };
                     let text_size = settings.text_size as f32;
-                    let (uv, tilesize) =
-                        ui::image_uv_tilesize(texture, state.player.graphic, text_size);
+                    let (uv, tilesize) = ui::image_uv_tilesize(
+                        texture,
+                        state.player.graphic,
+                        text_size,
+                        &display.tileset,
+                        &display.glyph_atlas,
+                    );
                     let image_color = state.palette.player(state.player.color_index);
                     let sized_texture =
                         egui::load::SizedTexture::new(texture, Vec2::splat(tilesize));
@@ -0,0 +1,273 @@
+use crate::{
+    audio::{Audio, Effect},
+    engine::Display,
+    game::RunningState,
+    gamepad::Gamepad,
+    history::{RunRecord, Summary},
+    keys::KeyCode,
+    state::State,
+};
+
+use std::{cmp::Ordering, time::Duration};
+
+use egui::{self, Grid, ScrollArea, Ui};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Column {
+    #[default]
+    Date,
+    Outcome,
+    Turns,
+    SoberStreak,
+    Seed,
+}
+
+impl Column {
+    const ALL: [Column; 5] = [
+        Column::Date,
+        Column::Outcome,
+        Column::Turns,
+        Column::SoberStreak,
+        Column::Seed,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Column::Date => "Date",
+            Column::Outcome => "Outcome",
+            Column::Turns => "Turns",
+            Column::SoberStreak => "Sober streak",
+            Column::Seed => "Seed",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Column::Date => Column::Outcome,
+            Column::Outcome => Column::Turns,
+            Column::Turns => Column::SoberStreak,
+            Column::SoberStreak => Column::Seed,
+            Column::Seed => Column::Date,
+        }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            Column::Date => Column::Seed,
+            Column::Outcome => Column::Date,
+            Column::Turns => Column::Outcome,
+            Column::SoberStreak => Column::Turns,
+            Column::Seed => Column::SoberStreak,
+        }
+    }
+
+    fn compare(self, a: &RunRecord, b: &RunRecord) -> Ordering {
+        match self {
+            Column::Date => a.finished_at.cmp(&b.finished_at),
+            Column::Outcome => a
+                .victory
+                .cmp(&b.victory)
+                .then_with(|| a.outcome().cmp(&b.outcome())),
+            Column::Turns => a.turns.cmp(&b.turns),
+            Column::SoberStreak => a.longest_sober_streak.cmp(&b.longest_sober_streak),
+            Column::Seed => a.seed.cmp(&b.seed),
+        }
+    }
+}
+
+/// How is the list of runs in the Statistics window sorted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Sort {
+    pub column: Column,
+    pub descending: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        // NOTE: show the most recent games first
+        Self {
+            column: Column::Date,
+            descending: true,
+        }
+    }
+}
+
+impl Sort {
+    pub fn apply(self, records: &mut [RunRecord]) {
+        records.sort_by(|a, b| {
+            let ordering = self.column.compare(a, b);
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Action {
+    SortBy(Column),
+    NextColumn,
+    PrevColumn,
+    Reverse,
+    LineUp,
+    LineDown,
+    Close,
+}
+
+pub fn process(
+    state: &mut State,
+    ui: &mut Ui,
+    gamepad: &Gamepad,
+    display: &Display,
+    audio: &mut Audio,
+) -> RunningState {
+    let mut visible = true;
+    let mut action = None;
+
+    let screen_size_px = display.screen_size_px;
+    let window_size_px = [
+        (screen_size_px.x - 150).min(1024) as f32,
+        (screen_size_px.y - 350).min(600) as f32,
+    ];
+    let window_pos_px = [(screen_size_px.x as f32 - window_size_px[0]) / 2.0, 100.0];
+
+    let summary = Summary::new(&state.run_history);
+    let sort = state.statistics_sort;
+
+    egui::Window::new("Statistics")
+        .open(&mut visible)
+        .collapsible(false)
+        .fixed_pos(window_pos_px)
+        .fixed_size(window_size_px)
+        .show(ui.ctx(), |ui| {
+            ui.label(format!("Games played: {}", summary.games_played));
+            ui.label(format!(
+                "Games won: {} ({:.0}%)",
+                summary.victories,
+                summary.win_rate()
+            ));
+            ui.label(format!("Average turns: {:.0}", summary.average_turns));
+            let perpetrator = match summary.most_common_perpetrator {
+                Some((kind, count)) => format!("{} ({count} games)", kind.name()),
+                None => "None".to_string(),
+            };
+            ui.label(format!("Most common cause of defeat: {perpetrator}"));
+            ui.label(format!(
+                "Longest sober streak: {} turns",
+                summary.longest_sober_streak
+            ));
+            ui.separator();
+
+            if state.run_history.is_empty() {
+                ui.label("You haven't finished any games yet.");
+                return;
+            }
+
+            ScrollArea::vertical()
+                .max_height(window_size_px[1] - 200.0)
+                .show(ui, |ui| {
+                    Grid::new("Statistics Run List")
+                        .striped(true)
+                        .num_columns(Column::ALL.len() + 1)
+                        .show(ui, |ui| {
+                            for column in Column::ALL {
+                                let label = if column == sort.column {
+                                    let arrow = if sort.descending { "v" } else { "^" };
+                                    format!("{} {arrow}", column.label())
+                                } else {
+                                    column.label().to_string()
+                                };
+                                if ui.selectable_label(column == sort.column, label).clicked() {
+                                    action = Some(Action::SortBy(column));
+                                }
+                            }
+                            ui.label("Replay");
+                            ui.end_row();
+
+                            for record in &state.run_history {
+                                ui.label(record.date());
+                                if record.challenge.practice {
+                                    ui.label(format!("{} (practice)", record.outcome()));
+                                } else {
+                                    ui.label(record.outcome());
+                                }
+                                ui.label(record.turns.to_string());
+                                ui.label(record.longest_sober_streak.to_string());
+                                ui.label(record.seed.to_string());
+                                match &record.replay_path {
+                                    Some(path) => {
+                                        let file_name = path.file_name().map_or_else(
+                                            || path.display().to_string(),
+                                            |name| name.to_string_lossy().into_owned(),
+                                        );
+                                        ui.label(file_name)
+                                            .on_hover_text(path.display().to_string());
+                                    }
+                                    None => {
+                                        ui.label("-");
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            ui.separator();
+            ui.label("[<-] [->] Sort by column, [R]everse the order, [Esc] Close");
+        });
+
+    if state.keys.matches_code(KeyCode::Esc) || state.mouse.right_clicked || !visible {
+        action = Some(Action::Close);
+    }
+
+    if action.is_none() {
+        let stick_flicked_up = gamepad.left_stick_flicked && gamepad.left_stick_y > 0.0;
+        let stick_flicked_down = gamepad.left_stick_flicked && gamepad.left_stick_y < 0.0;
+        let stick_flicked_left = gamepad.left_stick_flicked && gamepad.left_stick_x < 0.0;
+        let stick_flicked_right = gamepad.left_stick_flicked && gamepad.left_stick_x > 0.0;
+
+        if state.keys.matches_code(KeyCode::Right) || stick_flicked_right {
+            action = Some(Action::NextColumn);
+        } else if state.keys.matches_code(KeyCode::Left) || stick_flicked_left {
+            action = Some(Action::PrevColumn);
+        } else if state.keys.matches_code(KeyCode::R) || state.keys.matches_code(KeyCode::Enter) {
+            action = Some(Action::Reverse);
+        } else if state.keys.matches_code(KeyCode::Up) || stick_flicked_up {
+            action = Some(Action::LineUp);
+        } else if state.keys.matches_code(KeyCode::Down) || stick_flicked_down {
+            action = Some(Action::LineDown);
+        }
+    }
+
+    if let Some(action) = action {
+        audio.play_sound(Effect::Click, Duration::from_millis(0));
+        let mut new_sort = state.statistics_sort;
+        match action {
+            Action::SortBy(column) => {
+                if column == new_sort.column {
+                    new_sort.descending = !new_sort.descending;
+                } else {
+                    new_sort.column = column;
+                }
+            }
+            Action::NextColumn => new_sort.column = new_sort.column.next(),
+            Action::PrevColumn => new_sort.column = new_sort.column.prev(),
+            Action::Reverse => new_sort.descending = !new_sort.descending,
+            Action::LineUp => state.keyboard_scroll_delta[1] = 50.0,
+            Action::LineDown => state.keyboard_scroll_delta[1] = -50.0,
+            Action::Close => {
+                state.run_history.clear();
+                state.window_stack.pop();
+                return RunningState::Running;
+            }
+        }
+        if new_sort != state.statistics_sort {
+            state.statistics_sort = new_sort;
+            new_sort.apply(&mut state.run_history);
+        }
+    }
+
+    RunningState::Running
+}
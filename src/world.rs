@@ -357,6 +357,12 @@ impl World {
             }
         }
     }
+
+    /// The width (and height) of every chunk in tiles.
+    pub fn chunk_size(&self) -> i32 {
+        self.chunk_size
+    }
+
     /// Return the `ChunkPosition` for a given point within the chunk.
     ///
     /// Chunks have equal width and height and can have negative
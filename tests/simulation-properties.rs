@@ -0,0 +1,252 @@
+//! Play games with random seeds, challenges and commands and check
+//! the simulation never ends up in an impossible state.
+//!
+//! Run more cases with e.g. `PROPTEST_CASES=1000 cargo test --test simulation-properties`.
+
+use dose_response::{
+    audio::Audio,
+    engine::{Display, TextMetrics},
+    game::{self, RunningState},
+    gamepad::Gamepad,
+    palette::Palette,
+    point::Point,
+    ranged_int::Ranged,
+    settings::{NoOpStore, Settings, Store},
+    state::{Challenge, Command, GameSession, State, Verification},
+    window::Window,
+    windows::Windows,
+};
+
+use std::{collections::HashMap, time::Duration};
+
+use proptest::prelude::*;
+
+/// How many frames we give each command to play out before moving
+/// on to the next one.
+const FRAMES_PER_COMMAND: usize = 30;
+
+struct Metrics;
+
+impl TextMetrics for Metrics {
+    fn tile_width_px(&self) -> i32 {
+        32
+    }
+
+    fn text_width_px(&self) -> i32 {
+        16
+    }
+}
+
+/// Everything `game::update` needs apart from the `State`. Nothing
+/// here is rendered or played.
+struct Harness {
+    egui_context: egui::Context,
+    settings_store: NoOpStore,
+    settings: Settings,
+    display: Display,
+    audio: Audio,
+    gamepad: Gamepad,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let settings_store = NoOpStore;
+        let settings = settings_store.load();
+        let egui_context = egui::Context::default();
+        egui_context.begin_pass(Default::default());
+        let display = Display::new(
+            Point::new(settings.window_width as i32, settings.window_height as i32),
+            settings.tile_size,
+            settings.text_size,
+        );
+        Self {
+            egui_context,
+            settings_store,
+            settings,
+            display,
+            audio: Audio::without_backend(),
+            gamepad: Gamepad::new(),
+        }
+    }
+
+    fn update(&mut self, state: &mut State) -> RunningState {
+        game::update(
+            state,
+            &self.egui_context,
+            Duration::from_millis(16),
+            60,
+            &[],
+            Default::default(),
+            &mut self.gamepad,
+            &mut self.settings,
+            &Metrics,
+            &mut self.settings_store,
+            &mut self.display,
+            &mut self.audio,
+        )
+    }
+}
+
+fn new_game(seed: u32, challenge: Challenge) -> Box<State> {
+    let exit_after = false;
+    let debug = false;
+    let replay_path = None;
+    let mut state = Box::new(State::new_game(
+        dose_response::WORLD_SIZE,
+        Point::from_i32(dose_response::DISPLAYED_MAP_SIZE),
+        dose_response::PANEL_WIDTH,
+        exit_after,
+        debug,
+        seed,
+        replay_path,
+        challenge,
+        Palette::classic(),
+    ));
+    state.window_stack = Windows::new(Window::Game);
+    state.generate_world();
+    state.game_session = GameSession::InProgress;
+    state
+}
+
+fn challenge() -> impl Strategy<Value = Challenge> {
+    (any::<bool>(), any::<bool>(), any::<bool>()).prop_map(
+        |(hide_unseen_tiles, fast_depression, one_chance)| Challenge {
+            hide_unseen_tiles,
+            fast_depression,
+            one_chance,
+            practice: false,
+        },
+    )
+}
+
+fn command() -> impl Strategy<Value = Command> {
+    prop_oneof![
+        8 => prop_oneof![
+            Just(Command::N),
+            Just(Command::E),
+            Just(Command::S),
+            Just(Command::W),
+            Just(Command::NE),
+            Just(Command::NW),
+            Just(Command::SE),
+            Just(Command::SW),
+        ],
+        2 => prop_oneof![
+            Just(Command::UseFood),
+            Just(Command::UseDose),
+            Just(Command::UseCardinalDose),
+            Just(Command::UseDiagonalDose),
+            Just(Command::UseStrongDose),
+            Just(Command::WalkPath),
+        ],
+        1 => (0..200_u64, ".{0,20}", ".{0,20}").prop_map(|(ttl, title, message)| {
+            Command::ShowMessageBox {
+                ttl: Duration::from_millis(ttl),
+                title,
+                message,
+            }
+        }),
+    ]
+}
+
+fn fail(err: impl std::fmt::Display) -> TestCaseError {
+    TestCaseError::fail(err.to_string())
+}
+
+fn check_ranged(name: &str, value: Ranged) -> Result<(), TestCaseError> {
+    prop_assert!(
+        value.min() <= value.to_int() && value.to_int() <= value.max(),
+        "{} is out of range: {:?}",
+        name,
+        value
+    );
+    Ok(())
+}
+
+fn check_invariants(state: &State) -> Result<(), TestCaseError> {
+    let player = &state.player;
+    prop_assert!(
+        state.world.within_bounds(player.pos),
+        "The player is out of bounds: {}",
+        player.pos
+    );
+
+    check_ranged("will", player.will)?;
+    check_ranged("panic", player.panic)?;
+    check_ranged("stun", player.stun)?;
+    check_ranged("anxiety counter", player.anxiety_counter)?;
+    let mind = match player.mind {
+        dose_response::player::Mind::Withdrawal(value)
+        | dose_response::player::Mind::Sober(value)
+        | dose_response::player::Mind::High(value) => value,
+    };
+    check_ranged("mind", mind)?;
+
+    let mut occupied = HashMap::new();
+    for chunk_pos in state.world.positions_of_all_chunks() {
+        let Some(chunk) = state.world.chunk(chunk_pos) else {
+            continue;
+        };
+        for monster in chunk.monsters() {
+            check_ranged("monster AP", monster.ap)?;
+            if let Some(other) = occupied.insert(monster.position, monster.kind) {
+                prop_assert!(
+                    false,
+                    "{:?} and {:?} share the cell {}",
+                    other,
+                    monster.kind,
+                    monster.position
+                );
+            }
+        }
+    }
+
+    let verification = state.verification();
+    let json = serde_json::to_string(&verification).map_err(fail)?;
+    let parsed: Verification = serde_json::from_str(&json).map_err(fail)?;
+    prop_assert_eq!(parsed, verification);
+
+    Ok(())
+}
+
+/// Every case generates a new world so we keep the default low.
+fn cases() -> u32 {
+    std::env::var("PROPTEST_CASES")
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(16)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(cases()))]
+
+    #[test]
+    fn test_simulation_invariants(
+        seed in any::<u32>(),
+        challenge in challenge(),
+        commands in prop::collection::vec(command(), 0..80),
+    ) {
+        let mut harness = Harness::new();
+        let mut state = new_game(seed, challenge);
+        check_invariants(&state)?;
+
+        'commands: for command in commands {
+            state.commands.push_back(command);
+            for _ in 0..FRAMES_PER_COMMAND {
+                let result = harness.update(&mut state);
+                check_invariants(&state)?;
+                if matches!(result, RunningState::Stopped) || state.game_session == GameSession::Ended {
+                    break 'commands;
+                }
+                if state.commands.is_empty() {
+                    break;
+                }
+            }
+        }
+
+        // NOTE: this is how the game is saved and loaded
+        let saved = bincode::serialize(&*state).map_err(fail)?;
+        let loaded: State = bincode::deserialize(&saved).map_err(fail)?;
+        prop_assert_eq!(loaded.verification(), state.verification());
+    }
+}